@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE posts ADD COLUMN publish_at TIMESTAMP DEFAULT NULL;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE post_revisions (
+    id SERIAL PRIMARY KEY,
+    post_id INTEGER REFERENCES posts(id) ON DELETE CASCADE NOT NULL,
+    author_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    title VARCHAR NOT NULL,
+    subtitle TEXT NOT NULL DEFAULT '',
+    source TEXT NOT NULL DEFAULT '',
+    creation_date TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX post_revisions_post_id ON post_revisions (post_id);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE post_previews (
+    id SERIAL PRIMARY KEY,
+    post_id INTEGER REFERENCES posts(id) ON DELETE CASCADE NOT NULL,
+    token VARCHAR NOT NULL UNIQUE,
+    expires_at TIMESTAMP NOT NULL,
+    creation_date TIMESTAMP NOT NULL DEFAULT now()
+);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE blog_slugs (
+    id SERIAL PRIMARY KEY,
+    blog_id INTEGER REFERENCES blogs(id) ON DELETE CASCADE NOT NULL,
+    slug VARCHAR NOT NULL UNIQUE,
+    creation_date TIMESTAMP NOT NULL DEFAULT now()
+);
+CREATE TABLE post_slugs (
+    id SERIAL PRIMARY KEY,
+    post_id INTEGER REFERENCES posts(id) ON DELETE CASCADE NOT NULL,
+    blog_id INTEGER REFERENCES blogs(id) ON DELETE CASCADE NOT NULL,
+    slug VARCHAR NOT NULL,
+    creation_date TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT post_slugs_unique UNIQUE (blog_id, slug)
+);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE posts ADD COLUMN language TEXT DEFAULT NULL;
+ALTER TABLE posts ADD COLUMN translation_of INTEGER DEFAULT NULL REFERENCES posts(id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE posts DROP COLUMN reading_time;
+ALTER TABLE posts DROP COLUMN word_count;
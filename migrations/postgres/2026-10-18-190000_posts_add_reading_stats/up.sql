@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE posts ADD COLUMN word_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE posts ADD COLUMN reading_time INTEGER NOT NULL DEFAULT 0;
+--#!|conn: &Connection, _path: &Path| crate::posts::Post::update_all_reading_stats(conn)
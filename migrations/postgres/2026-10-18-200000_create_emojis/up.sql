@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE emojis (
+    id SERIAL PRIMARY KEY,
+    shortcode VARCHAR NOT NULL,
+    media_id INTEGER REFERENCES medias(id) ON DELETE CASCADE NOT NULL,
+    instance_id INTEGER REFERENCES instances(id) ON DELETE CASCADE NOT NULL,
+    ap_url VARCHAR NOT NULL UNIQUE,
+    CONSTRAINT emojis_unique UNIQUE (instance_id, shortcode)
+);
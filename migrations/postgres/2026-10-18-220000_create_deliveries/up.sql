@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE deliveries (
+    id SERIAL PRIMARY KEY,
+    activity_id VARCHAR NOT NULL,
+    inbox VARCHAR NOT NULL,
+    sender VARCHAR NOT NULL,
+    body TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT now(),
+    last_error TEXT,
+    creation_date TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT deliveries_unique UNIQUE (activity_id, inbox)
+);
+CREATE INDEX deliveries_next_attempt_at ON deliveries (next_attempt_at);
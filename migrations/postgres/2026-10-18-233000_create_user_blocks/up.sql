@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE user_blocks (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    blocked_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    domain VARCHAR,
+    ap_url TEXT NOT NULL DEFAULT '',
+    creation_date TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT user_blocks_unique_user UNIQUE (user_id, blocked_id),
+    CONSTRAINT user_blocks_unique_domain UNIQUE (user_id, domain)
+);
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE reports (
+    id SERIAL PRIMARY KEY,
+    reporter_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    post_id INTEGER REFERENCES posts(id) ON DELETE CASCADE,
+    comment_id INTEGER REFERENCES comments(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL DEFAULT '',
+    forwarded BOOLEAN NOT NULL DEFAULT 'f',
+    resolved BOOLEAN NOT NULL DEFAULT 'f',
+    ap_url TEXT NOT NULL DEFAULT '',
+    creation_date TIMESTAMP NOT NULL DEFAULT now()
+);
+CREATE INDEX reports_resolved ON reports (resolved);
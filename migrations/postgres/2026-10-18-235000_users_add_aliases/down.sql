@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users DROP COLUMN moved_to;
+ALTER TABLE users DROP COLUMN also_known_as;
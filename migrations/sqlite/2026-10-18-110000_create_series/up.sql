@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE series (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    blog_id INTEGER REFERENCES blogs(id) ON DELETE CASCADE NOT NULL,
+    title VARCHAR NOT NULL,
+    slug VARCHAR NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    ap_url VARCHAR NOT NULL DEFAULT '',
+    creation_date DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT series_unique_slug UNIQUE (blog_id, slug)
+);
+
+CREATE TABLE series_posts (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    series_id INTEGER REFERENCES series(id) ON DELETE CASCADE NOT NULL,
+    post_id INTEGER REFERENCES posts(id) ON DELETE CASCADE NOT NULL UNIQUE,
+    position INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX series_posts_series_id ON series_posts (series_id);
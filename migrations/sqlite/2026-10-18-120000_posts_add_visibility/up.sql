@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE posts ADD COLUMN visibility INTEGER NOT NULL DEFAULT 0;
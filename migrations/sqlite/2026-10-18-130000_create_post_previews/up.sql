@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE post_previews (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    post_id INTEGER REFERENCES posts(id) ON DELETE CASCADE NOT NULL,
+    token VARCHAR NOT NULL UNIQUE,
+    expires_at DATETIME NOT NULL,
+    creation_date DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
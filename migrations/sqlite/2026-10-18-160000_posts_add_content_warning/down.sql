@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE posts DROP COLUMN content_warning;
+ALTER TABLE posts DROP COLUMN sensitive;
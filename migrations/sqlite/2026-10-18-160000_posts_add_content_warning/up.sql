@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE posts ADD COLUMN sensitive BOOLEAN NOT NULL DEFAULT 'f';
+ALTER TABLE posts ADD COLUMN content_warning TEXT DEFAULT NULL;
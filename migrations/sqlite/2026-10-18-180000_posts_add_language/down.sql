@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE posts DROP COLUMN translation_of;
+ALTER TABLE posts DROP COLUMN language;
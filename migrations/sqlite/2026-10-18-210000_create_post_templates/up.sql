@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE post_templates (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    blog_id INTEGER REFERENCES blogs(id) ON DELETE CASCADE NOT NULL,
+    name VARCHAR NOT NULL,
+    title VARCHAR NOT NULL DEFAULT '',
+    source TEXT NOT NULL DEFAULT '',
+    tags TEXT NOT NULL DEFAULT '',
+    license VARCHAR NOT NULL DEFAULT '',
+    cover_id INTEGER REFERENCES medias(id) ON DELETE SET NULL,
+    CONSTRAINT post_templates_unique UNIQUE (blog_id, name)
+);
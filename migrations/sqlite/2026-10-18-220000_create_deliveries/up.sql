@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE deliveries (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    activity_id VARCHAR NOT NULL,
+    inbox VARCHAR NOT NULL,
+    sender VARCHAR NOT NULL,
+    body TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_error TEXT,
+    creation_date DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT deliveries_unique UNIQUE (activity_id, inbox)
+);
+CREATE INDEX deliveries_next_attempt_at ON deliveries (next_attempt_at);
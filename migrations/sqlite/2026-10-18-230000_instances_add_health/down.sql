@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE instances DROP COLUMN unreachable;
+ALTER TABLE instances DROP COLUMN failures;
+ALTER TABLE instances DROP COLUMN failing_since;
+ALTER TABLE instances DROP COLUMN last_success_at;
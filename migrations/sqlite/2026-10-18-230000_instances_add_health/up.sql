@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE instances ADD COLUMN last_success_at DATETIME DEFAULT NULL;
+ALTER TABLE instances ADD COLUMN failing_since DATETIME DEFAULT NULL;
+ALTER TABLE instances ADD COLUMN failures INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE instances ADD COLUMN unreachable BOOLEAN NOT NULL DEFAULT 'f';
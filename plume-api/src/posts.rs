@@ -12,6 +12,21 @@ pub struct NewPostData {
     pub license: Option<String>,
     pub tags: Option<Vec<String>>,
     pub cover_id: Option<i32>,
+    // RFC 3339 date at which the post should be automatically published.
+    // Only taken into account if it is in the future and `published` is not `false`.
+    pub publish_at: Option<String>,
+    // One of "public" (the default), "unlisted", "followers" or "blog_members".
+    pub visibility: Option<String>,
+    // Marks the post as sensitive, this warning being shown instead of its content.
+    // It may be empty.
+    pub content_warning: Option<String>,
+    // The address of the post on the site it was first published on, if any.
+    pub canonical_url: Option<String>,
+    // A language code, like "en". Detected from the content when not given.
+    pub language: Option<String>,
+    // The id of a template of the blog, used for the title, source, tags, license
+    // and cover that are not given (an empty title or source counts as not given).
+    pub template_id: Option<i32>,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -28,4 +43,13 @@ pub struct PostData {
     pub license: String,
     pub tags: Vec<String>,
     pub cover_id: Option<i32>,
+    pub publish_at: Option<String>,
+    pub visibility: String,
+    pub sensitive: bool,
+    pub content_warning: Option<String>,
+    pub canonical_url: Option<String>,
+    pub language: Option<String>,
+    pub word_count: i32,
+    // The estimated reading time, in minutes
+    pub reading_time: i32,
 }
@@ -0,0 +1,46 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use plume_models::{blogs::Blog, export::export_blog, Connection};
+use std::{fs::File, io::BufWriter};
+
+pub fn command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("blogs")
+        .about("Manage blogs")
+        .subcommand(
+            SubCommand::with_name("export")
+                .arg(
+                    Arg::with_name("blog")
+                        .short("b")
+                        .long("blog")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The blog to export"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .short("p")
+                        .long("path")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Where to write the archive"),
+                )
+                .about("Export a blog, with its posts, media and comments, to an archive that can be imported with posts import"),
+        )
+}
+
+pub fn run<'a>(args: &ArgMatches<'a>, conn: &Connection) {
+    let conn = conn;
+    match args.subcommand() {
+        ("export", Some(x)) => export(x, conn),
+        ("", None) => command().print_help().unwrap(),
+        _ => println!("Unknown subcommand"),
+    }
+}
+
+fn export<'a>(args: &ArgMatches<'a>, conn: &Connection) {
+    let blog = Blog::find_by_fqn(conn, args.value_of("blog").unwrap()).expect("Blog not found");
+    let path = args.value_of("path").unwrap();
+    let file = File::create(path).expect("Couldn't create the archive");
+    export_blog(conn, &blog, BufWriter::new(file)).expect("Couldn't export the blog");
+    println!("Exported {} to {}", blog.fqn, path);
+}
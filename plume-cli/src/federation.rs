@@ -0,0 +1,113 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use plume_models::{deliveries::Delivery, Connection};
+
+/// How many deliveries are listed at most.
+const LIST_LIMIT: i32 = 100;
+
+pub fn command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("federation")
+        .about("Manage federation")
+        .subcommand(
+            SubCommand::with_name("queue")
+                .arg(
+                    Arg::with_name("retry")
+                        .short("r")
+                        .long("retry")
+                        .help("Send the failed activities again"),
+                )
+                .arg(
+                    Arg::with_name("purge")
+                        .short("p")
+                        .long("purge")
+                        .help("Delete the failed activities"),
+                )
+                .arg(
+                    Arg::with_name("id")
+                        .short("i")
+                        .long("id")
+                        .takes_value(true)
+                        .help("Only retry or delete this delivery, even if it didn't fail"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .short("y")
+                        .long("yes")
+                        .help("Confirm the deletion"),
+                )
+                .about("Inspect the activities waiting to be sent to other instances"),
+        )
+}
+
+pub fn run<'a>(args: &ArgMatches<'a>, conn: &Connection) {
+    let conn = conn;
+    match args.subcommand() {
+        ("queue", Some(x)) => queue(x, conn),
+        ("", None) => command().print_help().unwrap(),
+        _ => println!("Unknown subcommand"),
+    }
+}
+
+fn queue<'a>(args: &ArgMatches<'a>, conn: &Connection) {
+    let delivery = args.value_of("id").map(|id| {
+        let id = id.parse::<i32>().expect("Invalid delivery id");
+        Delivery::get(conn, id).expect("Delivery not found")
+    });
+
+    if args.is_present("purge") {
+        if !args.is_present("yes") {
+            panic!(
+                "Warning, this operation is destructive. Add --yes to confirm you want to do it."
+            )
+        }
+        match delivery {
+            Some(delivery) => {
+                delivery.delete(conn).expect("Couldn't delete delivery");
+                println!("Deleted delivery {}", delivery.id);
+            }
+            None => {
+                let count = Delivery::purge_failed(conn).expect("Couldn't purge the queue");
+                println!("Deleted {} failed deliveries", count);
+            }
+        }
+    } else if args.is_present("retry") {
+        match delivery {
+            Some(mut delivery) => {
+                delivery.retry(conn).expect("Couldn't retry delivery");
+                println!("Delivery {} will be sent again", delivery.id);
+            }
+            None => {
+                let count = Delivery::retry_failed(conn).expect("Couldn't retry deliveries");
+                println!("{} failed deliveries will be sent again", count);
+            }
+        }
+    } else {
+        let deliveries = match delivery {
+            Some(delivery) => vec![delivery],
+            None => Delivery::page(conn, (0, LIST_LIMIT)).expect("Couldn't list deliveries"),
+        };
+        for delivery in deliveries {
+            let status = if delivery.is_failed() {
+                format!("failed after {} attempts", delivery.attempts)
+            } else {
+                format!(
+                    "attempt {} on {}",
+                    delivery.attempts + 1,
+                    delivery.next_attempt_at.format("%F %R UTC")
+                )
+            };
+            println!(
+                "{}\t{}\t{}\t{}",
+                delivery.id, delivery.activity_id, delivery.inbox, status
+            );
+            if let Some(error) = delivery.last_error {
+                println!("\tLast error: {}", error);
+            }
+        }
+        println!(
+            "{} deliveries waiting, {} failed",
+            Delivery::count(conn).expect("Couldn't count deliveries"),
+            Delivery::count_failed(conn).expect("Couldn't count failed deliveries")
+        );
+    }
+}
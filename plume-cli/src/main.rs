@@ -3,9 +3,12 @@ use diesel::Connection;
 use plume_models::{instance::Instance, Connection as Conn, CONFIG};
 use std::io::{self, prelude::*};
 
+mod blogs;
+mod federation;
 mod instance;
 mod list;
 mod migration;
+mod posts;
 mod search;
 mod timeline;
 mod users;
@@ -15,8 +18,11 @@ fn main() {
         .bin_name("plm")
         .version(env!("CARGO_PKG_VERSION"))
         .about("Collection of tools to manage your Plume instance.")
+        .subcommand(blogs::command())
+        .subcommand(federation::command())
         .subcommand(instance::command())
         .subcommand(migration::command())
+        .subcommand(posts::command())
         .subcommand(search::command())
         .subcommand(timeline::command())
         .subcommand(list::command())
@@ -32,12 +38,21 @@ fn main() {
     let _ = conn.as_ref().map(Instance::cache_local);
 
     match matches.subcommand() {
+        ("blogs", Some(args)) => {
+            blogs::run(args, &conn.expect("Couldn't connect to the database."))
+        }
+        ("federation", Some(args)) => {
+            federation::run(args, &conn.expect("Couldn't connect to the database."))
+        }
         ("instance", Some(args)) => {
             instance::run(args, &conn.expect("Couldn't connect to the database."))
         }
         ("migration", Some(args)) => {
             migration::run(args, &conn.expect("Couldn't connect to the database."))
         }
+        ("posts", Some(args)) => {
+            posts::run(args, &conn.expect("Couldn't connect to the database."))
+        }
         ("search", Some(args)) => {
             search::run(args, &conn.expect("Couldn't connect to the database."))
         }
@@ -0,0 +1,140 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use plume_models::{
+    blogs::Blog,
+    import::{import_posts, ImportFormat, ImportOptions},
+    instance::Instance,
+    search::Searcher,
+    users::User,
+    Connection, CONFIG,
+};
+use std::path::Path;
+
+pub fn command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("posts")
+        .about("Manage posts")
+        .subcommand(
+            SubCommand::with_name("import")
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["wordpress", "ghost", "markdown", "archive"])
+                        .required(true)
+                        .help("The format of the export"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .short("p")
+                        .long("path")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The export file or archive, or for Markdown a file or a directory"),
+                )
+                .arg(
+                    Arg::with_name("blog")
+                        .short("b")
+                        .long("blog")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The blog to import the posts in"),
+                )
+                .arg(
+                    Arg::with_name("author")
+                        .short("a")
+                        .long("author")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Username of the author of the posts, who must be an author of the blog"),
+                )
+                .arg(
+                    Arg::with_name("license")
+                        .short("l")
+                        .long("license")
+                        .takes_value(true)
+                        .help("License of the posts that don't specify one. Defaults to the instance default license"),
+                )
+                .arg(
+                    Arg::with_name("site-url")
+                        .short("s")
+                        .long("site-url")
+                        .takes_value(true)
+                        .help("Address of the imported site, to find images with a relative address"),
+                )
+                .arg(
+                    Arg::with_name("index")
+                        .long("index")
+                        .help("Add the published posts to the search index. Plume must be stopped"),
+                )
+                .arg(
+                    Arg::with_name("federate")
+                        .long("federate")
+                        .help("Send the published posts to the followers of the author"),
+                )
+                .about("Import posts from WordPress, Ghost, Markdown or Plume archive files"),
+        )
+}
+
+pub fn run<'a>(args: &ArgMatches<'a>, conn: &Connection) {
+    let conn = conn;
+    match args.subcommand() {
+        ("import", Some(x)) => import(x, conn),
+        ("", None) => command().print_help().unwrap(),
+        _ => println!("Unknown subcommand"),
+    }
+}
+
+fn import<'a>(args: &ArgMatches<'a>, conn: &Connection) {
+    let format = args
+        .value_of("format")
+        .unwrap()
+        .parse::<ImportFormat>()
+        .expect("Unknown format");
+    let path = Path::new(args.value_of("path").unwrap());
+    let blog = Blog::find_by_fqn(conn, args.value_of("blog").unwrap()).expect("Blog not found");
+    let instance = Instance::get_local_uncached(conn).expect("Failed to load local instance");
+    let author = User::find_by_name(conn, args.value_of("author").unwrap(), instance.id)
+        .expect("User not found");
+
+    // the search index is locked by Plume while it runs, so it is opened before doing anything
+    let searcher = if args.is_present("index") {
+        Some(
+            Searcher::open(&CONFIG.search_index, &CONFIG.search_tokenizers)
+                .expect("Couldn't open the search index, make sure Plume is stopped"),
+        )
+    } else {
+        None
+    };
+
+    let posts = format.read(path).expect("Couldn't read the export");
+    println!("Importing {} posts", posts.len());
+    let options = ImportOptions {
+        default_license: args
+            .value_of("license")
+            .map(String::from)
+            .unwrap_or(instance.default_license),
+        site_url: args.value_of("site-url").map(String::from),
+        index: false,
+        federate: args.is_present("federate"),
+    };
+    let report =
+        import_posts(conn, &blog, &author, posts, &options).expect("Couldn't import posts");
+
+    for post in &report.imported {
+        println!("Imported \"{}\"", post.title);
+    }
+    for (title, reason) in &report.skipped {
+        eprintln!("Skipped \"{}\": {}", title, reason);
+    }
+
+    if let Some(searcher) = searcher {
+        for post in report.imported.iter().filter(|p| p.published) {
+            searcher
+                .add_document(conn, post)
+                .expect("Couldn't index post");
+        }
+        println!("Commiting result");
+        searcher.commit();
+    }
+}
@@ -8,9 +8,9 @@ use activitystreams::{
     primitives::{AnyString, OneOrMany},
     unparsed::UnparsedMutExt,
 };
-use activitystreams_ext::{Ext1, Ext2, UnparsedExtension};
+use activitystreams_ext::{Ext2, Ext3, UnparsedExtension};
 use array_tool::vec::Uniq;
-use futures::future::join_all;
+use futures::stream::{self, StreamExt};
 use reqwest::{header::HeaderValue, ClientBuilder, RequestBuilder, Url};
 use rocket::{
     http::Status,
@@ -18,6 +18,7 @@ use rocket::{
     response::{Responder, Response},
     Outcome,
 };
+use std::collections::BTreeMap;
 use tokio::{
     runtime,
     time::{sleep, Duration},
@@ -52,7 +53,14 @@ pub fn context() -> serde_json::Value {
         {
             "manuallyApprovesFollowers": "as:manuallyApprovesFollowers",
             "sensitive": "as:sensitive",
-            "movedTo": "as:movedTo",
+            "movedTo": {
+                "@id": "as:movedTo",
+                "@type": "@id"
+            },
+            "alsoKnownAs": {
+                "@id": "as:alsoKnownAs",
+                "@type": "@id"
+            },
             "Hashtag": "as:Hashtag",
             "ostatus":"http://ostatus.org#",
             "atomUri":"ostatus:atomUri",
@@ -125,101 +133,134 @@ impl<'a, 'r> FromRequest<'a, 'r> for ApRequest {
     }
 }
 
-pub fn broadcast<S, A, T, C>(sender: &S, act: A, to: Vec<T>, proxy: Option<reqwest::Proxy>)
+/// Lists the inboxes an activity should be delivered to.
+///
+/// Shared inboxes are used when possible, so that each instance only receives it once.
+pub fn inboxes<T, C>(to: Vec<T>) -> Vec<String>
 where
-    S: sign::Signer,
-    A: Activity + serde::Serialize,
     T: inbox::AsActor<C>,
 {
-    let boxes = to
-        .into_iter()
+    to.into_iter()
         .filter(|u| !u.is_local())
         .map(|u| {
             u.get_shared_inbox_url()
                 .unwrap_or_else(|| u.get_inbox_url())
         })
         .collect::<Vec<String>>()
-        .unique();
+        .unique()
+}
 
-    let mut act = serde_json::to_value(act).expect("activity_pub::broadcast: serialization error");
+/// Serializes an activity with its context, and signs it.
+pub fn sign_activity<S, A>(sender: &S, act: A) -> serde_json::Value
+where
+    S: sign::Signer,
+    A: Activity + serde::Serialize,
+{
+    let mut act =
+        serde_json::to_value(act).expect("activity_pub::sign_activity: serialization error");
     act["@context"] = context();
-    let signed = act
-        .sign(sender)
-        .expect("activity_pub::broadcast: signature error");
+    act.sign(sender)
+        .expect("activity_pub::sign_activity: signature error");
+    act
+}
 
+/// Posts signed activities to some inboxes, a few at a time.
+///
+/// Each delivery is a sender, an inbox and the body to post. The outcome of each of
+/// them is returned, in the same order.
+pub fn deliver(
+    deliveries: Vec<(&dyn sign::Signer, String, String)>,
+    proxy: Option<reqwest::Proxy>,
+) -> Vec<Result<(), String>> {
     let client = if let Some(proxy) = proxy {
         ClientBuilder::new().proxy(proxy)
     } else {
         ClientBuilder::new()
     }
     .connect_timeout(std::time::Duration::from_secs(5))
+    .timeout(std::time::Duration::from_secs(30))
     .build()
     .expect("Can't build client");
+
+    let requests = deliveries
+        .into_iter()
+        .map(|(sender, inbox, body)| {
+            let url = Url::parse(&inbox).map_err(|_| format!("Inbox is invalid URL: {}", inbox))?;
+            let host = url
+                .host_str()
+                .ok_or_else(|| format!("Inbox doesn't have host: {}", inbox))?;
+            let mut headers = request::headers();
+            headers.insert(
+                "Host",
+                HeaderValue::from_str(host)
+                    .map_err(|_| format!("Header value is invalid: {}", host))?,
+            );
+            headers.insert("Digest", request::Digest::digest(&body));
+            headers.insert(
+                "Signature",
+                request::signature(sender, &headers, ("post", url.path(), url.query()))
+                    .map_err(|_| String::from("Request signature error"))?,
+            );
+            Ok(client.post(url).headers(headers).body(body))
+        })
+        .collect::<Vec<Result<RequestBuilder, String>>>();
+
     let rt = runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .expect("Error while initializing tokio runtime for federation");
-    rt.block_on(async {
+    rt.block_on(
         // TODO: should be determined dependent on database connections because
         // after broadcasting, target instance sends request to this instance,
         // and Plume accesses database at that time.
-        let capacity = 6;
-        let (tx, rx) = flume::bounded::<RequestBuilder>(capacity);
-        let mut handles = Vec::with_capacity(capacity);
-        for _ in 0..capacity {
-            let rx = rx.clone();
-            let handle = rt.spawn(async move {
-                while let Ok(request_builder) = rx.recv_async().await {
-                    // After broadcasting, target instance sends request to this instance.
-                    // Sleep here in order to reduce requests at once
-                    sleep(Duration::from_millis(500)).await;
-                    let _ = request_builder
-                        .send()
-                        .await
-                        .map(move |r| {
-                            if r.status().is_success() {
-                                debug!("Successfully sent activity to inbox ({})", &r.url());
-                            } else {
-                                warn!("Error while sending to inbox ({:?})", &r)
-                            }
-                            debug!("Response: \"{:?}\"\n", r);
-                        })
-                        .map_err(|e| warn!("Error while sending to inbox ({:?})", e));
+        stream::iter(requests)
+            .map(|request| async move {
+                let request = match request {
+                    Ok(request) => request,
+                    Err(e) => {
+                        warn!("{}", e);
+                        return Err(e);
+                    }
+                };
+                // After broadcasting, target instance sends request to this instance.
+                // Sleep here in order to reduce requests at once
+                sleep(Duration::from_millis(500)).await;
+                match request.send().await {
+                    Ok(r) if r.status().is_success() => {
+                        debug!("Successfully sent activity to inbox ({})", &r.url());
+                        Ok(())
+                    }
+                    Ok(r) => {
+                        warn!("Error while sending to inbox ({:?})", &r);
+                        Err(format!("{} answered {}", r.url(), r.status()))
+                    }
+                    Err(e) => {
+                        warn!("Error while sending to inbox ({:?})", e);
+                        Err(e.to_string())
+                    }
                 }
-            });
-            handles.push(handle);
-        }
-        for inbox in boxes {
-            let body = signed.to_string();
-            let mut headers = request::headers();
-            let url = Url::parse(&inbox);
-            if url.is_err() {
-                warn!("Inbox is invalid URL: {:?}", &inbox);
-                continue;
-            }
-            let url = url.unwrap();
-            if !url.has_host() {
-                warn!("Inbox doesn't have host: {:?}", &inbox);
-                continue;
-            };
-            let host_header_value = HeaderValue::from_str(url.host_str().expect("Unreachable"));
-            if host_header_value.is_err() {
-                warn!("Header value is invalid: {:?}", url.host_str());
-                continue;
-            }
-            headers.insert("Host", host_header_value.unwrap());
-            headers.insert("Digest", request::Digest::digest(&body));
-            headers.insert(
-                "Signature",
-                request::signature(sender, &headers, ("post", url.path(), url.query()))
-                    .expect("activity_pub::broadcast: request signature error"),
-            );
-            let request_builder = client.post(&inbox).headers(headers.clone()).body(body);
-            let _ = tx.send_async(request_builder).await;
-        }
-        drop(tx);
-        join_all(handles).await;
-    });
+            })
+            .buffered(6)
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Sends an activity right away, without retrying failed deliveries.
+///
+/// It is only meant for actors that are about to be deleted, and that
+/// won't be able to sign their activities anymore later.
+pub fn broadcast<S, A, T, C>(sender: &S, act: A, to: Vec<T>, proxy: Option<reqwest::Proxy>)
+where
+    S: sign::Signer,
+    A: Activity + serde::Serialize,
+    T: inbox::AsActor<C>,
+{
+    let body = sign_activity(sender, act).to_string();
+    let deliveries = inboxes(to)
+        .into_iter()
+        .map(|inbox| (sender as &dyn sign::Signer, inbox, body.clone()))
+        .collect();
+    deliver(deliveries, proxy);
 }
 
 #[derive(Shrinkwrap, Clone, Serialize, Deserialize)]
@@ -297,8 +338,70 @@ where
     }
 }
 
-pub type CustomPerson = Ext1<ApActor<Person>, ApSignature>;
-pub type CustomGroup = Ext2<ApActor<Group>, ApSignature, SourceProperty>;
+/// The collection of the pinned posts of an actor, as used by Mastodon
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeaturedProperty {
+    pub featured: Option<IriString>,
+}
+
+impl<U> UnparsedExtension<U> for FeaturedProperty
+where
+    U: UnparsedMutExt,
+{
+    type Error = serde_json::Error;
+
+    fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
+        Ok(FeaturedProperty {
+            featured: unparsed_mut.remove("featured")?,
+        })
+    }
+
+    fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
+        if let Some(featured) = self.featured {
+            unparsed_mut.insert("featured", featured)?;
+        }
+        Ok(())
+    }
+}
+
+/// The other accounts of an actor, and the one it moved to, used for account migrations
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MovedProperty {
+    pub also_known_as: Vec<IriString>,
+    pub moved_to: Option<IriString>,
+}
+
+impl<U> UnparsedExtension<U> for MovedProperty
+where
+    U: UnparsedMutExt,
+{
+    type Error = serde_json::Error;
+
+    fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
+        let also_known_as: Option<OneOrMany<IriString>> = unparsed_mut.remove("alsoKnownAs")?;
+        Ok(MovedProperty {
+            also_known_as: also_known_as
+                .map(|aliases| aliases.iter().cloned().collect())
+                .unwrap_or_default(),
+            moved_to: unparsed_mut.remove("movedTo")?,
+        })
+    }
+
+    fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
+        if !self.also_known_as.is_empty() {
+            unparsed_mut.insert("alsoKnownAs", self.also_known_as)?;
+        }
+        if let Some(moved_to) = self.moved_to {
+            unparsed_mut.insert("movedTo", moved_to)?;
+        }
+        Ok(())
+    }
+}
+
+pub type CustomPerson = Ext3<ApActor<Person>, ApSignature, FeaturedProperty, MovedProperty>;
+pub type CustomGroup = Ext3<ApActor<Group>, ApSignature, SourceProperty, FeaturedProperty>;
 
 kind!(HashtagType, Hashtag);
 
@@ -427,6 +530,12 @@ impl markers::Base for Hashtag {}
 impl markers::Object for Hashtag {}
 impl<T> HashtagExt for T where T: AsHashtag {}
 
+kind!(EmojiType, Emoji);
+
+/// A custom emoji, as sent by Mastodon: its name is its `:shortcode:`, and its icon the
+/// image that replaces it.
+pub type Emoji = Object<EmojiType>;
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Source {
@@ -479,7 +588,71 @@ where
     }
 }
 
-pub type LicensedArticle = Ext1<ApObject<Article>, Licensed>;
+/// Marks an object as sensitive, its `summary` being the content warning
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SensitiveProperty {
+    pub sensitive: Option<bool>,
+}
+
+impl<U> UnparsedExtension<U> for SensitiveProperty
+where
+    U: UnparsedMutExt,
+{
+    type Error = serde_json::Error;
+
+    fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
+        Ok(SensitiveProperty {
+            sensitive: unparsed_mut.remove("sensitive")?,
+        })
+    }
+
+    fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
+        if let Some(sensitive) = self.sensitive {
+            unparsed_mut.insert("sensitive", sensitive)?;
+        }
+        Ok(())
+    }
+}
+
+/// The content of an object, by language
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentMapProperty {
+    pub content_map: Option<BTreeMap<String, String>>,
+}
+
+impl ContentMapProperty {
+    /// The language of the content, if it is the only one in the map.
+    pub fn language(&self) -> Option<String> {
+        match self.content_map {
+            Some(ref map) if map.len() == 1 => map.keys().next().cloned(),
+            _ => None,
+        }
+    }
+}
+
+impl<U> UnparsedExtension<U> for ContentMapProperty
+where
+    U: UnparsedMutExt,
+{
+    type Error = serde_json::Error;
+
+    fn try_from_unparsed(unparsed_mut: &mut U) -> Result<Self, Self::Error> {
+        Ok(ContentMapProperty {
+            content_map: unparsed_mut.remove("contentMap")?,
+        })
+    }
+
+    fn try_into_unparsed(self, unparsed_mut: &mut U) -> Result<(), Self::Error> {
+        if let Some(content_map) = self.content_map {
+            unparsed_mut.insert("contentMap", content_map)?;
+        }
+        Ok(())
+    }
+}
+
+pub type LicensedArticle = Ext3<ApObject<Article>, Licensed, SensitiveProperty, ContentMapProperty>;
 
 pub trait ToAsString {
     fn to_as_string(&self) -> Option<String>;
@@ -579,6 +752,13 @@ mod tests {
                     public_key_pem: "pubKeyPem".into(),
                 },
             },
+            FeaturedProperty {
+                featured: Some("https://example.com/featured".parse().unwrap()),
+            },
+            MovedProperty {
+                also_known_as: vec!["https://example.org/old".parse().unwrap()],
+                moved_to: None,
+            },
         );
         let expected = json!({
             "inbox": "https://example.com/inbox",
@@ -587,11 +767,41 @@ mod tests {
                 "id": "https://example.com/pubkey",
                 "owner": "https://example.com/owner",
                 "publicKeyPem": "pubKeyPem"
-            }
+            },
+            "featured": "https://example.com/featured",
+            "alsoKnownAs": ["https://example.org/old"]
         });
         assert_eq!(to_value(person).unwrap(), expected);
     }
 
+    #[test]
+    fn de_moved_property() {
+        let person: CustomPerson = from_str(
+            r#"
+              {
+                "id": "https://example.org/old",
+                "type": "Person",
+                "inbox": "https://example.org/old/inbox",
+                "publicKey": {
+                  "id": "https://example.org/old#main-key",
+                  "owner": "https://example.org/old",
+                  "publicKeyPem": "pubKeyPem"
+                },
+                "alsoKnownAs": "https://example.com/new",
+                "movedTo": "https://example.com/new"
+              }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            person.ext_three,
+            MovedProperty {
+                also_known_as: vec!["https://example.com/new".parse().unwrap()],
+                moved_to: Some("https://example.com/new".parse().unwrap()),
+            }
+        );
+    }
+
     #[test]
     fn se_custom_group() {
         let group = CustomGroup::new(
@@ -609,6 +819,7 @@ mod tests {
                     media_type: String::from("text/markdown"),
                 },
             },
+            FeaturedProperty::default(),
         );
         let expected = json!({
             "inbox": "https://example.com/inbox",
@@ -670,7 +881,8 @@ mod tests {
                     content: String::from(""),
                     media_type: String::from("text/markdown")
                 }
-            }
+            },
+            FeaturedProperty::default()
         );
         expected.set_icon(Image::new().into_any_base().unwrap());
         expected.set_id(
@@ -699,10 +911,24 @@ mod tests {
             Licensed {
                 license: Some("CC-0".into()),
             },
+            SensitiveProperty {
+                sensitive: Some(true),
+            },
+            ContentMapProperty {
+                content_map: Some(
+                    vec![("fr".to_owned(), "Bonjour".to_owned())]
+                        .into_iter()
+                        .collect(),
+                ),
+            },
         );
         let expected = json!({
             "type": "Article",
             "license": "CC-0",
+            "sensitive": true,
+            "contentMap": {
+                "fr": "Bonjour",
+            },
         });
         assert_json_eq!(to_value(licensed_article).unwrap(), expected);
     }
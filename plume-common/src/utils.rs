@@ -1,8 +1,11 @@
+use latex2mathml::{latex_to_mathml, DisplayStyle};
 use openssl::rand::rand_bytes;
 use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag};
 use regex_syntax::is_word_character;
 use rocket::http::uri::Uri;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use syntect::html::{ClassStyle, ClassedHTMLGenerator};
 use syntect::parsing::SyntaxSet;
 
@@ -191,7 +194,181 @@ fn inline_tags<'a>(
     }
 }
 
-pub type MediaProcessor<'a> = Box<dyn 'a + Fn(i32) -> Option<(String, Option<String>)>>;
+/// What a `MediaProcessor` knows about a media
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessedMedia {
+    pub url: String,
+    pub alt_text: String,
+    pub content_warning: Option<String>,
+    /// "image", "audio", "video" or "unknown"
+    pub category: String,
+}
+
+pub type MediaProcessor<'a> = Box<dyn 'a + Fn(i32) -> Option<ProcessedMedia>>;
+
+/// The languages of fenced blocks that are expanded into media, see `process_shortcodes`
+const FIGURE_BLOCK: &str = "figure";
+const GALLERY_BLOCK: &str = "gallery";
+
+/// Parses the lines of a figure or gallery block, that are a media id optionally
+/// followed by a caption.
+fn shortcode_items(content: &str) -> impl Iterator<Item = (i32, &str)> {
+    content.lines().filter_map(|line| {
+        let line = line.trim();
+        let (id, caption) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        Some((id.parse().ok()?, caption.trim()))
+    })
+}
+
+fn media_html(media: &ProcessedMedia) -> String {
+    let alt = escape(&media.alt_text);
+    let element = match media.category.as_str() {
+        "image" => format!(r#"<img src="{}" alt="{}" title="{}">"#, media.url, alt, alt),
+        "audio" => format!(
+            r#"<audio src="{}" title="{}" controls></audio>"#,
+            media.url, alt
+        ),
+        "video" => format!(
+            r#"<video src="{}" title="{}" controls></video>"#,
+            media.url, alt
+        ),
+        _ => format!(r#"<a href="{}">{}</a>"#, media.url, alt),
+    };
+    match media.content_warning {
+        Some(ref cw) => format!(
+            r#"<label for="postcontent-cw-{id}"><input type="checkbox" id="postcontent-cw-{id}" checked="checked" class="cw-checkbox"><span class="cw-container"><span class="cw-text">{cw}</span>{element}</span></label>"#,
+            id = random_hex(),
+            cw = escape(cw),
+            element = element
+        ),
+        None => element,
+    }
+}
+
+/// Renders a figure or gallery block, or returns `None` if none of its media could be found.
+///
+/// Inline content can't contain figures, so they are replaced with links to the media.
+fn render_shortcode(
+    gallery: bool,
+    content: &str,
+    inline: bool,
+    processor: &MediaProcessor<'_>,
+) -> Option<String> {
+    let items = shortcode_items(content)
+        .filter_map(|(id, caption)| Some((processor(id)?, caption)))
+        .collect::<Vec<_>>();
+    if items.is_empty() {
+        return None;
+    }
+    if inline {
+        return Some(
+            items
+                .iter()
+                .map(|(media, caption)| {
+                    let label = if caption.is_empty() {
+                        media.alt_text.as_str()
+                    } else {
+                        *caption
+                    };
+                    format!(r#"<a href="{}">{}</a>"#, media.url, escape(label))
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+    }
+    let figures = items
+        .iter()
+        .map(|(media, caption)| {
+            if caption.is_empty() {
+                format!("<figure>{}</figure>", media_html(media))
+            } else {
+                format!(
+                    r#"<figure>{}<figcaption dir="auto">{}</figcaption></figure>"#,
+                    media_html(media),
+                    escape(caption)
+                )
+            }
+        })
+        .collect::<String>();
+    if gallery {
+        Some(format!(r#"<div class="media-gallery">{}</div>"#, figures) + "\n")
+    } else {
+        Some(figures + "\n")
+    }
+}
+
+/// Expands the ```` ```figure ```` and ```` ```gallery ```` fenced blocks into media.
+///
+/// Each line of these blocks is the id of a media, optionally followed by a caption.
+/// If none of the media can be found, the block is rendered as code.
+fn process_shortcodes<'a>(
+    state: &mut Option<(bool, String)>,
+    evt: Event<'a>,
+    inline: bool,
+    processor: &Option<MediaProcessor<'_>>,
+) -> Option<Vec<Event<'a>>> {
+    let processor = match processor {
+        Some(processor) => processor,
+        None => return Some(vec![evt]),
+    };
+    match evt {
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+            if state.is_none() && (&**lang == FIGURE_BLOCK || &**lang == GALLERY_BLOCK) =>
+        {
+            *state = Some((&**lang == GALLERY_BLOCK, String::new()));
+            Some(vec![])
+        }
+        Event::Text(txt) if state.is_some() => {
+            if let Some((_, content)) = state {
+                content.push_str(&txt);
+            }
+            Some(vec![])
+        }
+        Event::End(Tag::CodeBlock(kind)) if state.is_some() => {
+            let (gallery, content) = state.take()?;
+            match render_shortcode(gallery, &content, inline, processor) {
+                Some(html) => Some(vec![Event::Html(html.into())]),
+                None => Some(vec![
+                    Event::Start(Tag::CodeBlock(kind.clone())),
+                    Event::Text(content.into()),
+                    Event::End(Tag::CodeBlock(kind)),
+                ]),
+            }
+        }
+        e => Some(vec![e]),
+    }
+}
+
+/// Lists the ids of the media used in some Markdown, as images or in figure and gallery blocks.
+pub fn referenced_media(md: &str) -> Vec<i32> {
+    let mut ids = vec![];
+    let mut in_shortcode = false;
+    for evt in Parser::new_ext(md, Options::all()) {
+        let new_ids: Vec<i32> = match evt {
+            Event::Start(Tag::Image(_, ref url, _)) => {
+                url.parse::<i32>().ok().into_iter().collect()
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang))) => {
+                in_shortcode = &**lang == FIGURE_BLOCK || &**lang == GALLERY_BLOCK;
+                vec![]
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                in_shortcode = false;
+                vec![]
+            }
+            Event::Text(ref txt) if in_shortcode => {
+                shortcode_items(txt).map(|(id, _)| id).collect()
+            }
+            _ => vec![],
+        };
+        for id in new_ids {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    ids
+}
 
 fn process_image<'a, 'b>(
     evt: Event<'a>,
@@ -201,7 +378,12 @@ fn process_image<'a, 'b>(
     if let Some(ref processor) = *processor {
         match evt {
             Event::Start(Tag::Image(typ, id, title)) => {
-                if let Some((url, cw)) = id.parse::<i32>().ok().and_then(processor.as_ref()) {
+                if let Some(ProcessedMedia {
+                    url,
+                    content_warning: cw,
+                    ..
+                }) = id.parse::<i32>().ok().and_then(processor.as_ref())
+                {
                     if let (Some(cw), false) = (cw, inline) {
                         // there is a cw, and where are not inline
                         Event::Html(CowStr::Boxed(
@@ -227,7 +409,12 @@ fn process_image<'a, 'b>(
                 }
             }
             Event::End(Tag::Image(typ, id, title)) => {
-                if let Some((url, cw)) = id.parse::<i32>().ok().and_then(processor.as_ref()) {
+                if let Some(ProcessedMedia {
+                    url,
+                    content_warning: cw,
+                    ..
+                }) = id.parse::<i32>().ok().and_then(processor.as_ref())
+                {
                     if inline || cw.is_none() {
                         Event::End(Tag::Image(typ, CowStr::Boxed(url.into()), title))
                     } else {
@@ -248,6 +435,429 @@ fn process_image<'a, 'b>(
     }
 }
 
+/// Returns the URL of the image of a custom emoji, given its shortcode (without colons)
+pub type EmojiProcessor<'a> = Box<dyn 'a + Fn(&str) -> Option<String>>;
+
+fn is_emoji_character(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Splits some text around its `:shortcode:`s, returning `(text, is_shortcode)` pairs.
+/// Shortcodes are returned without their colons.
+fn split_emoji(text: &str) -> Vec<(&str, bool)> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut search = 0;
+    while let Some(open) = text[search..].find(':').map(|i| i + search) {
+        let name_len = text[open + 1..]
+            .find(|c| !is_emoji_character(c))
+            .unwrap_or(text.len() - open - 1);
+        let close = open + 1 + name_len;
+        if name_len >= 2 && text[close..].starts_with(':') {
+            if open > start {
+                parts.push((&text[start..open], false));
+            }
+            parts.push((&text[open + 1..close], true));
+            start = close + 1;
+            search = start;
+        } else {
+            search = close;
+        }
+    }
+    if start < text.len() {
+        parts.push((&text[start..], false));
+    }
+    parts
+}
+
+/// Lists the shortcodes (without colons) of the custom emoji that may be used in some text.
+pub fn emoji_shortcodes(text: &str) -> HashSet<String> {
+    split_emoji(text)
+        .into_iter()
+        .filter(|(_, is_shortcode)| *is_shortcode)
+        .map(|(shortcode, _)| shortcode.to_owned())
+        .collect()
+}
+
+fn emoji_html(shortcode: &str, url: &str) -> String {
+    format!(
+        r#"<img class="emoji" src="{url}" alt=":{name}:" title=":{name}:">"#,
+        url = escape(url),
+        name = shortcode
+    )
+}
+
+/// Replaces the `:shortcode:`s of a text with the corresponding custom emoji,
+/// leaving unknown shortcodes as they are.
+fn render_emoji(text: &str, processor: &EmojiProcessor<'_>) -> String {
+    split_emoji(text)
+        .into_iter()
+        .map(|(part, is_shortcode)| {
+            if !is_shortcode {
+                part.to_owned()
+            } else if let Some(url) = processor(part) {
+                emoji_html(part, &url)
+            } else {
+                format!(":{}:", part)
+            }
+        })
+        .collect()
+}
+
+/// Renders the custom emoji of some HTML, outside of its tags.
+///
+/// The HTML should be sanitized afterwards, as it is not parsed.
+pub fn render_emoji_in_html(html: &str, processor: &EmojiProcessor<'_>) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+    while !rest.is_empty() {
+        let text_end = rest.find('<').unwrap_or(rest.len());
+        let tag_end = rest[text_end..]
+            .find('>')
+            .map_or(rest.len(), |i| text_end + i + 1);
+        result.push_str(&render_emoji(&rest[..text_end], processor));
+        result.push_str(&rest[text_end..tag_end]);
+        rest = &rest[tag_end..];
+    }
+    result
+}
+
+/// Renders the custom emoji found in text, except in code blocks and image descriptions.
+fn process_emoji<'a>(
+    skip: &mut bool,
+    evt: Event<'a>,
+    processor: &Option<EmojiProcessor<'_>>,
+) -> Option<Vec<Event<'a>>> {
+    let processor = match processor {
+        Some(processor) => processor,
+        None => return Some(vec![evt]),
+    };
+    match evt {
+        Event::Start(Tag::CodeBlock(_)) | Event::Start(Tag::Image(_, _, _)) => {
+            *skip = true;
+            Some(vec![evt])
+        }
+        Event::End(Tag::CodeBlock(_)) | Event::End(Tag::Image(_, _, _)) => {
+            *skip = false;
+            Some(vec![evt])
+        }
+        Event::Text(txt) if !*skip && txt.contains(':') => Some(
+            split_emoji(&txt)
+                .into_iter()
+                .map(|(part, is_shortcode)| {
+                    if !is_shortcode {
+                        Event::Text(part.to_owned().into())
+                    } else if let Some(url) = processor(part) {
+                        Event::Html(emoji_html(part, &url).into())
+                    } else {
+                        Event::Text(format!(":{}:", part).into())
+                    }
+                })
+                .collect(),
+        ),
+        e => Some(vec![e]),
+    }
+}
+
+/// Surrounds the index of a formula in the Markdown source while it is being parsed.
+/// It is in a private use area, so it should never be found in actual text.
+const MATH_MARK: char = '\u{E000}';
+
+/// Renders TeX to MathML, keeping the source as the alternative text.
+fn tex_to_mathml(tex: &str, display: bool) -> Option<String> {
+    let style = if display {
+        DisplayStyle::Block
+    } else {
+        DisplayStyle::Inline
+    };
+    let mathml = latex_to_mathml(tex.trim(), style).ok()?;
+    Some(mathml.replacen(
+        "<math",
+        &format!(r#"<math alttext="{}""#, escape(tex.trim())),
+        1,
+    ))
+}
+
+/// Finds the end of a formula that starts at the beginning of `md`, and returns its length.
+///
+/// Inline formulas can't start or end with a space, can't be followed by a digit
+/// (so that "$5 or $10" is left alone) and can't span multiple paragraphs.
+fn find_math_end(md: &str, display: bool) -> Option<usize> {
+    if display {
+        return md.find("$$").filter(|len| *len > 0);
+    }
+    if md.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut escaped = false;
+    for (i, c) in md.char_indices() {
+        match c {
+            '\\' => escaped = !escaped,
+            '$' if !escaped && i > 0 => {
+                let before = md[..i].chars().last();
+                let after = md[i + 1..].chars().next();
+                if !before.map_or(false, char::is_whitespace)
+                    && !after.map_or(false, |c| c.is_ascii_digit())
+                {
+                    return Some(i);
+                }
+                return None;
+            }
+            '\n' if md[i + 1..]
+                .trim_start_matches(&[' ', '\t'][..])
+                .starts_with('\n') =>
+            {
+                return None
+            }
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Replaces the formulas (between `$`, or `$$` for display math) that are not in code
+/// with markers, and returns the MathML the markers stand for.
+fn extract_math(md: &str) -> (Cow<'_, str>, Vec<String>) {
+    if !md.contains('$') {
+        return (Cow::Borrowed(md), vec![]);
+    }
+    let code: Vec<Range<usize>> = Parser::new_ext(md, Options::all())
+        .into_offset_iter()
+        .filter_map(|(evt, range)| match evt {
+            Event::Code(_) | Event::Start(Tag::CodeBlock(_)) | Event::Html(_) => Some(range),
+            _ => None,
+        })
+        .collect();
+    let mut result = String::with_capacity(md.len());
+    let mut formulas = vec![];
+    let mut copied = 0;
+    let mut i = 0;
+    while i < md.len() {
+        if let Some(range) = code.iter().find(|r| r.contains(&i)) {
+            i = range.end;
+            continue;
+        }
+        match md.as_bytes()[i] {
+            b'\\' => i += 2,
+            b'$' => {
+                let display = md[i..].starts_with("$$");
+                let delimiter = if display { 2 } else { 1 };
+                let start = i + delimiter;
+                let mathml = find_math_end(&md[start..], display)
+                    .and_then(|len| Some((len, tex_to_mathml(&md[start..start + len], display)?)));
+                if let Some((len, mathml)) = mathml {
+                    result.push_str(&md[copied..i]);
+                    result.push(MATH_MARK);
+                    result.push_str(&formulas.len().to_string());
+                    result.push(MATH_MARK);
+                    formulas.push(mathml);
+                    i = start + len + delimiter;
+                    copied = i;
+                } else {
+                    i = start;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    if formulas.is_empty() {
+        return (Cow::Borrowed(md), formulas);
+    }
+    result.push_str(&md[copied..]);
+    (Cow::Owned(result), formulas)
+}
+
+/// Puts back the MathML of the formulas that were replaced by `extract_math`.
+fn expand_math<'a>(formulas: &[String], evt: Event<'a>) -> Vec<Event<'a>> {
+    match evt {
+        Event::Text(txt) if !formulas.is_empty() && txt.contains(MATH_MARK) => txt
+            .split(MATH_MARK)
+            .enumerate()
+            .filter(|(_, part)| !part.is_empty())
+            .map(
+                |(i, part)| match formulas.get(part.parse::<usize>().unwrap_or(usize::MAX)) {
+                    Some(mathml) if i % 2 == 1 => Event::Html(mathml.clone().into()),
+                    _ => Event::Text(part.to_owned().into()),
+                },
+            )
+            .collect(),
+        e => vec![e],
+    }
+}
+
+/// The paragraph authors can write to get a table of contents at its place
+const TOC_MARKER: &str = "[TOC]";
+
+/// Turns the text of a heading into something that can be used as an HTML id.
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-' || c == '_')
+            && !slug.is_empty()
+            && !slug.ends_with('-')
+        {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "section".to_owned()
+    } else {
+        slug.to_owned()
+    }
+}
+
+fn unique_slug(used: &mut HashSet<String>, text: &str) -> String {
+    let base = slugify(text);
+    let mut slug = base.clone();
+    let mut n = 0;
+    while used.contains(&slug) {
+        n += 1;
+        slug = format!("{}-{}", base, n);
+    }
+    used.insert(slug.clone());
+    slug
+}
+
+/// Renders a table of contents, as nested lists following the levels of the headings.
+fn table_of_contents(headings: &[(u32, String, String)]) -> String {
+    let mut toc = String::from(r#"<nav class="toc" dir="auto">"#);
+    let mut levels: Vec<u32> = vec![];
+    for (level, slug, title) in headings {
+        while levels.last().map_or(false, |last| last > level) {
+            toc.push_str("</li></ol>");
+            levels.pop();
+        }
+        if levels.last() == Some(level) {
+            toc.push_str("</li><li>");
+        } else {
+            toc.push_str("<ol><li>");
+            levels.push(*level);
+        }
+        toc.push_str(&format!(r##"<a href="#{}">{}</a>"##, slug, escape(title)));
+    }
+    for _ in levels {
+        toc.push_str("</li></ol>");
+    }
+    toc.push_str("</nav>\n");
+    toc
+}
+
+/// Gives an id to every heading, replaces the `[TOC]` paragraphs with a table of contents,
+/// and makes footnote references and definitions link to each other.
+fn add_navigation(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut used_slugs = HashSet::new();
+    let mut headings = vec![];
+    let mut heading_title: Option<String> = None;
+    let mut toc_paragraphs = HashMap::new();
+    let mut paragraph: Option<(usize, String)> = None;
+    for (i, evt) in events.iter().enumerate() {
+        match evt {
+            Event::Start(Tag::Heading(_)) => heading_title = Some(String::new()),
+            Event::End(Tag::Heading(level)) => {
+                if let Some(title) = heading_title.take() {
+                    let slug = unique_slug(&mut used_slugs, &title);
+                    headings.push((*level, slug, title));
+                }
+            }
+            Event::Start(Tag::Paragraph) => paragraph = Some((i, String::new())),
+            Event::End(Tag::Paragraph) => {
+                if let Some((start, text)) = paragraph.take() {
+                    if text.trim().eq_ignore_ascii_case(TOC_MARKER) {
+                        toc_paragraphs.insert(start, i);
+                    }
+                }
+            }
+            Event::Text(txt) => {
+                if let Some(ref mut title) = heading_title {
+                    title.push_str(txt);
+                }
+                if let Some((_, ref mut text)) = paragraph {
+                    text.push_str(txt);
+                }
+            }
+            Event::Code(txt) => {
+                if let Some(ref mut title) = heading_title {
+                    title.push_str(txt);
+                }
+                paragraph = None;
+            }
+            _ => paragraph = None,
+        }
+    }
+
+    let toc = if headings.is_empty() {
+        None
+    } else {
+        Some(table_of_contents(&headings))
+    };
+    let mut headings = headings.into_iter();
+    // footnote name -> (number, how many times it was referenced)
+    let mut footnotes: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut skip_until = None;
+    let mut result = Vec::with_capacity(events.len());
+    for (i, evt) in events.into_iter().enumerate() {
+        if let Some(end) = skip_until {
+            if i == end {
+                skip_until = None;
+            }
+            continue;
+        }
+        if let Some(end) = toc_paragraphs.get(&i) {
+            skip_until = Some(*end);
+            if let Some(ref toc) = toc {
+                result.push(Event::Html(toc.clone().into()));
+            }
+            continue;
+        }
+        let footnote_count = footnotes.len();
+        let evt = match evt {
+            Event::Start(Tag::Heading(level)) => {
+                let slug = headings.next().map(|(_, slug, _)| slug).unwrap_or_default();
+                Event::Html(format!(r#"<h{} dir="auto" id="{}">"#, level, slug).into())
+            }
+            Event::End(Tag::Heading(level)) => Event::Html(format!("</h{}>\n", level).into()),
+            Event::FootnoteReference(name) => {
+                let (number, references) = footnotes
+                    .entry(name.into_string())
+                    .or_insert((footnote_count + 1, 0));
+                *references += 1;
+                let id = if *references == 1 {
+                    format!("fnref-{}", number)
+                } else {
+                    format!("fnref-{}-{}", number, references)
+                };
+                Event::Html(
+                    format!(
+                        r##"<sup class="footnote-reference" id="{id}"><a href="#fn-{n}">{n}</a></sup>"##,
+                        id = id,
+                        n = number
+                    )
+                    .into(),
+                )
+            }
+            Event::Start(Tag::FootnoteDefinition(name)) => {
+                let (number, _) = footnotes
+                    .entry(name.into_string())
+                    .or_insert((footnote_count + 1, 0));
+                Event::Html(
+                    format!(
+                        r##"<div class="footnote-definition" id="fn-{n}"><sup class="footnote-definition-label"><a href="#fnref-{n}">{n}</a></sup>"##,
+                        n = number
+                    )
+                    .into(),
+                )
+            }
+            Event::End(Tag::FootnoteDefinition(_)) => Event::Html("</div>\n".into()),
+            e => e,
+        };
+        result.push(evt);
+    }
+    result
+}
+
 #[derive(Default, Debug)]
 struct DocumentContext {
     in_code: bool,
@@ -260,23 +870,34 @@ pub fn md_to_html<'a>(
     base_url: Option<&str>,
     inline: bool,
     media_processor: Option<MediaProcessor<'a>>,
+    emoji_processor: Option<EmojiProcessor<'a>>,
 ) -> (String, HashSet<String>, HashSet<String>) {
     let base_url = if let Some(base_url) = base_url {
         format!("https://{}/", base_url)
     } else {
         "/".to_owned()
     };
-    let parser = Parser::new_ext(md, Options::all());
+    let (md, formulas) = extract_math(md);
+    let parser = Parser::new_ext(&md, Options::all());
 
     let (parser, mentions, hashtags): (Vec<Event<'_>>, Vec<String>, Vec<String>) = parser
         // Flatten text because pulldown_cmark break #hashtag in two individual text elements
         .scan(None, flatten_text)
         .flatten()
+        .flat_map(|evt| expand_math(&formulas, evt))
+        .scan(None, |state, evt| {
+            process_shortcodes(state, evt, inline, &media_processor)
+        })
+        .flatten()
         .scan(None, highlight_code)
         .flatten()
         .map(|evt| process_image(evt, inline, &media_processor))
         // Ignore headings, images, and tables if inline = true
         .scan((vec![], inline), inline_tags)
+        .scan(false, |skip, evt| {
+            process_emoji(skip, evt, &emoji_processor)
+        })
+        .flatten()
         .scan(&mut DocumentContext::default(), |ctx, evt| match evt {
             Event::Start(Tag::CodeBlock(_)) => {
                 ctx.in_code = true;
@@ -430,6 +1051,12 @@ pub fn md_to_html<'a>(
                 (parser, mention, hashtag)
             },
         );
+    // Inline content has no headings, and is too short to need navigation
+    let parser = if inline {
+        parser
+    } else {
+        add_navigation(parser)
+    };
     let parser = parser.into_iter();
     let mentions = mentions.into_iter().map(|m| String::from(m.trim()));
     let hashtags = hashtags.into_iter().map(|h| String::from(h.trim()));
@@ -441,6 +1068,354 @@ pub fn md_to_html<'a>(
     (buf, mentions.collect(), hashtags.collect())
 }
 
+/// How many words an average reader reads in a minute
+const WORDS_PER_MINUTE: usize = 230;
+/// How many Chinese, Japanese or Korean characters an average reader reads in a minute
+const CJK_CHARACTERS_PER_MINUTE: usize = 500;
+
+/// Chinese and Japanese don't separate their words with spaces, so each of their characters is counted.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' // Hiragana and Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Unified Ideographs Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{FF66}'..='\u{FF9F}' // Halfwidth Katakana
+        | '\u{20000}'..='\u{2FA1F}' // Supplementary ideographs
+    )
+}
+
+/// Counts the words of some Markdown, ignoring its syntax, and estimates how many minutes it takes to read it.
+///
+/// Each Chinese or Japanese character counts as a word, and is read faster than a word in other languages.
+pub fn reading_stats(md: &str) -> (usize, usize) {
+    let mut words = 0;
+    let mut cjk = 0;
+    for evt in Parser::new_ext(md, Options::all()) {
+        if let Event::Text(txt) | Event::Code(txt) = evt {
+            let mut in_word = false;
+            for c in txt.chars() {
+                if is_cjk(c) {
+                    cjk += 1;
+                    in_word = false;
+                } else if c.is_alphanumeric() {
+                    if !in_word {
+                        words += 1;
+                    }
+                    in_word = true;
+                } else if !(in_word && (c == '\'' || c == '’')) {
+                    in_word = false;
+                }
+            }
+        }
+    }
+    // words / WORDS_PER_MINUTE + cjk / CJK_CHARACTERS_PER_MINUTE, rounded up
+    let per_minute = WORDS_PER_MINUTE * CJK_CHARACTERS_PER_MINUTE;
+    let reading_time =
+        (words * CJK_CHARACTERS_PER_MINUTE + cjk * WORDS_PER_MINUTE + per_minute - 1) / per_minute;
+    (words + cjk, reading_time)
+}
+
+/// Writes the text of a gemtext block, followed by the links it contained, as gemtext has no inline links.
+fn push_gemtext_block(
+    out: &mut String,
+    first_prefix: &str,
+    prefix: &str,
+    text: &mut String,
+    links: &mut Vec<(String, String)>,
+) {
+    for (i, line) in text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .enumerate()
+    {
+        out.push_str(if i == 0 { first_prefix } else { prefix });
+        out.push_str(line);
+        out.push('\n');
+    }
+    text.clear();
+    for (url, label) in links.drain(..) {
+        let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+        if label.is_empty() || label == url {
+            out.push_str(&format!("=> {}\n", url));
+        } else {
+            out.push_str(&format!("=> {} {}\n", url, label));
+        }
+    }
+}
+
+/// Separates gemtext blocks with an empty line.
+fn end_gemtext_block(out: &mut String) {
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+}
+
+/// Converts Markdown to gemtext, the markup language of Gemini.
+///
+/// Gemtext has no inline formatting: links, images and media are listed after the block they are in,
+/// and tables are preformatted. Relative URLs are made absolute using `base_url`.
+pub fn md_to_gemtext(
+    md: &str,
+    base_url: Option<&str>,
+    media_processor: Option<MediaProcessor<'_>>,
+) -> String {
+    let absolute = |url: &str| match base_url {
+        Some(base_url) if url.starts_with('/') && !url.starts_with("//") => {
+            format!("https://{}{}", base_url, url)
+        }
+        _ => url.to_owned(),
+    };
+    let media = |id: i32| media_processor.as_ref().and_then(|processor| processor(id));
+
+    let mut out = String::new();
+    let mut text = String::new();
+    let mut links: Vec<(String, String)> = vec![];
+    // The URL of the current link or image, and where its label starts in `text`
+    let mut link_start: Option<(String, usize)> = None;
+    let mut quote_depth = 0;
+    // The next number of each ordered list, or `None` for bullet lists
+    let mut lists: Vec<Option<u64>> = vec![];
+    let mut item_prefix = String::new();
+    let mut in_code = false;
+    let mut shortcode: Option<String> = None;
+    let mut table: Vec<Vec<String>> = vec![];
+    let mut row: Vec<String> = vec![];
+
+    for evt in Parser::new_ext(md, Options::all()) {
+        let quote = if quote_depth > 0 { "> " } else { "" };
+        match evt {
+            Event::Start(Tag::Heading(_)) | Event::Start(Tag::Table(_)) => {
+                push_gemtext_block(&mut out, quote, quote, &mut text, &mut links);
+            }
+            Event::End(Tag::Heading(level)) => {
+                let prefix = format!("{} ", "#".repeat(level.min(3) as usize));
+                text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                push_gemtext_block(&mut out, &prefix, &prefix, &mut text, &mut links);
+                end_gemtext_block(&mut out);
+            }
+            Event::End(Tag::Paragraph) => {
+                if lists.is_empty() {
+                    push_gemtext_block(&mut out, quote, quote, &mut text, &mut links);
+                    end_gemtext_block(&mut out);
+                } else {
+                    text.push('\n');
+                }
+            }
+            Event::Start(Tag::BlockQuote) => {
+                push_gemtext_block(&mut out, quote, quote, &mut text, &mut links);
+                quote_depth += 1;
+            }
+            Event::End(Tag::BlockQuote) => {
+                quote_depth -= 1;
+                end_gemtext_block(&mut out);
+            }
+            Event::Start(Tag::List(first)) => {
+                push_gemtext_block(&mut out, &item_prefix, "", &mut text, &mut links);
+                item_prefix.clear();
+                lists.push(first);
+            }
+            Event::End(Tag::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    end_gemtext_block(&mut out);
+                }
+            }
+            Event::Start(Tag::Item) => {
+                item_prefix = match lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "* ".to_owned(),
+                };
+            }
+            Event::End(Tag::Item) => {
+                push_gemtext_block(&mut out, &item_prefix, "", &mut text, &mut links);
+                item_prefix.clear();
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+                if media_processor.is_some()
+                    && (&**lang == FIGURE_BLOCK || &**lang == GALLERY_BLOCK) =>
+            {
+                push_gemtext_block(&mut out, quote, quote, &mut text, &mut links);
+                shortcode = Some(String::new());
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                push_gemtext_block(&mut out, quote, quote, &mut text, &mut links);
+                match kind {
+                    CodeBlockKind::Fenced(lang) => out.push_str(&format!("```{}\n", lang)),
+                    CodeBlockKind::Indented => out.push_str("```\n"),
+                }
+                in_code = true;
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some(content) = shortcode.take() {
+                    for (id, caption) in shortcode_items(&content) {
+                        if let Some(media) = media(id) {
+                            let label = if caption.is_empty() {
+                                media.alt_text
+                            } else {
+                                caption.to_owned()
+                            };
+                            links.push((absolute(&media.url), label));
+                        }
+                    }
+                    if links.is_empty() {
+                        out.push_str(&format!("```\n{}```\n", content));
+                    }
+                    push_gemtext_block(&mut out, quote, quote, &mut text, &mut links);
+                } else {
+                    if !out.ends_with('\n') {
+                        out.push('\n');
+                    }
+                    out.push_str("```\n");
+                    in_code = false;
+                }
+                end_gemtext_block(&mut out);
+            }
+            Event::Start(Tag::Link(_, url, _)) | Event::Start(Tag::Image(_, url, _)) => {
+                link_start = Some((url.to_string(), text.len()));
+            }
+            Event::End(Tag::Link(_, _, _)) => {
+                if let Some((url, start)) = link_start.take() {
+                    links.push((absolute(&url), text[start..].to_owned()));
+                }
+            }
+            Event::End(Tag::Image(_, _, _)) => {
+                if let Some((url, start)) = link_start.take() {
+                    // Images are not displayed inline, so their description is only kept as a label
+                    let alt = text.split_off(start);
+                    match url.parse().ok().and_then(media) {
+                        Some(media) if alt.trim().is_empty() => {
+                            links.push((absolute(&media.url), media.alt_text))
+                        }
+                        Some(media) => links.push((absolute(&media.url), alt)),
+                        None => links.push((absolute(&url), alt)),
+                    }
+                }
+            }
+            Event::End(Tag::TableCell) => {
+                row.push(text.split_whitespace().collect::<Vec<_>>().join(" "));
+                text.clear();
+            }
+            Event::End(Tag::TableHead) | Event::End(Tag::TableRow) => {
+                table.push(std::mem::take(&mut row));
+            }
+            Event::End(Tag::Table(_)) => {
+                out.push_str("```\n");
+                for row in table.drain(..) {
+                    out.push_str(&row.join(" | "));
+                    out.push('\n');
+                }
+                out.push_str("```\n");
+                push_gemtext_block(&mut out, quote, quote, &mut text, &mut links);
+                end_gemtext_block(&mut out);
+            }
+            Event::Start(Tag::FootnoteDefinition(name)) => {
+                text.push_str(&format!("[{}] ", name));
+            }
+            Event::Text(txt) => {
+                if let Some(ref mut content) = shortcode {
+                    content.push_str(&txt);
+                } else if in_code {
+                    out.push_str(&txt);
+                } else {
+                    text.push_str(&txt);
+                }
+            }
+            Event::Code(txt) => text.push_str(&txt),
+            Event::FootnoteReference(name) => text.push_str(&format!("[{}]", name)),
+            Event::TaskListMarker(checked) => text.push_str(if checked { "[x] " } else { "[ ] " }),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            Event::Rule => {
+                push_gemtext_block(&mut out, quote, quote, &mut text, &mut links);
+                out.push_str("---\n\n");
+            }
+            // Emphasis and raw HTML have no equivalent in gemtext
+            _ => {}
+        }
+    }
+    push_gemtext_block(&mut out, "", "", &mut text, &mut links);
+    out.truncate(out.trim_end().len());
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// A line of a diff between two texts, see `diff_lines`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+/// Computes a line-based diff between two texts, using their longest common subsequence.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // Only run the quadratic part of the algorithm on what changed
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j] is the length of the longest common subsequence of old_changed[i..] and new_changed[j..]
+    let mut lcs = vec![vec![0usize; new_changed.len() + 1]; old_changed.len() + 1];
+    for i in (0..old_changed.len()).rev() {
+        for j in (0..new_changed.len()).rev() {
+            lcs[i][j] = if old_changed[i] == new_changed[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = old[..prefix]
+        .iter()
+        .copied()
+        .map(DiffLine::Same)
+        .collect::<Vec<_>>();
+    let (mut i, mut j) = (0, 0);
+    while i < old_changed.len() && j < new_changed.len() {
+        if old_changed[i] == new_changed[j] {
+            diff.push(DiffLine::Same(old_changed[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(old_changed[i]));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new_changed[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old_changed[i..].iter().copied().map(DiffLine::Removed));
+    diff.extend(new_changed[j..].iter().copied().map(DiffLine::Added));
+    diff.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .copied()
+            .map(DiffLine::Same),
+    );
+    diff
+}
+
 pub fn escape(string: &str) -> askama_escape::Escaped<askama_escape::Html> {
     askama_escape::escape(string, askama_escape::Html)
 }
@@ -470,7 +1445,7 @@ mod tests {
 
         for (md, mentions) in tests {
             assert_eq!(
-                md_to_html(md, None, false, None).1,
+                md_to_html(md, None, false, None, None).1,
                 mentions
                     .into_iter()
                     .map(|s| s.to_string())
@@ -498,7 +1473,7 @@ mod tests {
 
         for (md, mentions) in tests {
             assert_eq!(
-                md_to_html(md, None, false, None).2,
+                md_to_html(md, None, false, None, None).2,
                 mentions
                     .into_iter()
                     .map(|s| s.to_string())
@@ -521,15 +1496,298 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_diff_lines() {
+        use DiffLine::*;
+
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nb\nc"),
+            vec![Same("a"), Same("b"), Same("c")]
+        );
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nd\nc\ne"),
+            vec![Same("a"), Removed("b"), Added("d"), Same("c"), Added("e")]
+        );
+        assert_eq!(
+            diff_lines("", "new\nlines"),
+            vec![Added("new"), Added("lines")]
+        );
+        assert_eq!(
+            diff_lines("x\ny\nz", "y"),
+            vec![Removed("x"), Same("y"), Removed("z")]
+        );
+    }
+
     #[test]
     fn test_inline() {
         assert_eq!(
-            md_to_html("# Hello", None, false, None).0,
-            String::from("<h1 dir=\"auto\">Hello</h1>\n")
+            md_to_html("# Hello", None, false, None, None).0,
+            String::from("<h1 dir=\"auto\" id=\"hello\">Hello</h1>\n")
         );
         assert_eq!(
-            md_to_html("# Hello", None, true, None).0,
+            md_to_html("# Hello", None, true, None, None).0,
             String::from("<p dir=\"auto\">Hello</p>\n")
         );
     }
+
+    #[test]
+    fn test_heading_anchors() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Ça marche -- bien "), "ça-marche-bien");
+        assert_eq!(slugify("???"), "section");
+        assert_eq!(
+            md_to_html(
+                "# Intro\n\n## Intro\n\n## `code` here",
+                None,
+                false,
+                None,
+                None
+            )
+            .0,
+            String::from(
+                "<h1 dir=\"auto\" id=\"intro\">Intro</h1>\n\
+                 <h2 dir=\"auto\" id=\"intro-1\">Intro</h2>\n\
+                 <h2 dir=\"auto\" id=\"code-here\"><code>code</code> here</h2>\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_table_of_contents() {
+        let html = md_to_html(
+            "[TOC]\n\n# One\n\n## Two\n\n### Three\n\n# Four & more",
+            None,
+            false,
+            None,
+            None,
+        )
+        .0;
+        assert!(html.starts_with(
+            "<nav class=\"toc\" dir=\"auto\"><ol>\
+             <li><a href=\"#one\">One</a><ol>\
+             <li><a href=\"#two\">Two</a><ol>\
+             <li><a href=\"#three\">Three</a></li></ol></li></ol></li>\
+             <li><a href=\"#four-more\">Four &amp; more</a></li></ol></nav>\n"
+        ));
+        assert!(!html.contains("[TOC]"));
+
+        // Without the marker, there is no table of contents
+        assert!(!md_to_html("# One", None, false, None, None)
+            .0
+            .contains("<nav"));
+    }
+
+    #[test]
+    fn test_footnotes() {
+        let html = md_to_html(
+            "A[^note] and B[^other], and A again[^note].\n\n[^note]: The note.\n\n[^other]: The other.",
+            None,
+            false,
+            None,
+            None,
+        )
+        .0;
+        assert!(html.contains(
+            r##"<sup class="footnote-reference" id="fnref-1"><a href="#fn-1">1</a></sup>"##
+        ));
+        assert!(html.contains(
+            r##"<sup class="footnote-reference" id="fnref-2"><a href="#fn-2">2</a></sup>"##
+        ));
+        assert!(html.contains(
+            r##"<sup class="footnote-reference" id="fnref-1-2"><a href="#fn-1">1</a></sup>"##
+        ));
+        assert!(html.contains(
+            r##"<div class="footnote-definition" id="fn-1"><sup class="footnote-definition-label"><a href="#fnref-1">1</a></sup>"##
+        ));
+        assert!(html.contains(
+            r##"<div class="footnote-definition" id="fn-2"><sup class="footnote-definition-label"><a href="#fnref-2">2</a></sup>"##
+        ));
+    }
+
+    #[test]
+    fn test_math() {
+        let (html, _, hashtags) = md_to_html(
+            "Einstein said $E = mc^2$, and #physics agrees.\n\n$$\\sum_{i=1}^n i$$",
+            None,
+            false,
+            None,
+            None,
+        );
+        assert!(html.contains(r#"<math alttext="E = mc^2""#));
+        assert!(html.contains(r#"display="inline""#));
+        assert!(html.contains(r#"<math alttext="\sum_{i=1}^n i""#));
+        assert!(html.contains(r#"display="block""#));
+        assert!(!html.contains('$'));
+        assert!(!html.contains(MATH_MARK));
+        assert_eq!(hashtags, vec!["physics".to_owned()].into_iter().collect());
+
+        // Prices, code and escaped dollars are not math
+        for md in &["It costs $5 or $10.", "`$x$`", "\\$x$", "$ x $"] {
+            assert!(!md_to_html(md, None, false, None, None).0.contains("<math"));
+        }
+        assert!(md_to_html("```\n$x$\n```", None, false, None, None)
+            .0
+            .contains("$x$"));
+    }
+
+    fn test_processor<'a>() -> Option<MediaProcessor<'a>> {
+        Some(Box::new(|id| match id {
+            1 => Some(ProcessedMedia {
+                url: "/static/media/1.png".to_owned(),
+                alt_text: "A cat".to_owned(),
+                content_warning: None,
+                category: "image".to_owned(),
+            }),
+            2 => Some(ProcessedMedia {
+                url: "/static/media/2.ogg".to_owned(),
+                alt_text: "Purring".to_owned(),
+                content_warning: None,
+                category: "audio".to_owned(),
+            }),
+            _ => None,
+        }))
+    }
+
+    #[test]
+    fn test_shortcodes() {
+        assert_eq!(
+            md_to_html(
+                "```figure\n1 My cat\n```",
+                None,
+                false,
+                test_processor(),
+                None
+            )
+            .0,
+            "<figure><img src=\"/static/media/1.png\" alt=\"A cat\" title=\"A cat\">\
+             <figcaption dir=\"auto\">My cat</figcaption></figure>\n"
+        );
+        assert_eq!(
+            md_to_html(
+                "```gallery\n1\n2 Listen\n3 Missing\n```",
+                None,
+                false,
+                test_processor(),
+                None
+            )
+            .0,
+            "<div class=\"media-gallery\">\
+             <figure><img src=\"/static/media/1.png\" alt=\"A cat\" title=\"A cat\"></figure>\
+             <figure><audio src=\"/static/media/2.ogg\" title=\"Purring\" controls></audio>\
+             <figcaption dir=\"auto\">Listen</figcaption></figure></div>\n"
+        );
+        assert_eq!(
+            md_to_html(
+                "```gallery\n1\n2 Listen\n```",
+                None,
+                true,
+                test_processor(),
+                None
+            )
+            .0,
+            "<a href=\"/static/media/1.png\">A cat</a> <a href=\"/static/media/2.ogg\">Listen</a>"
+        );
+
+        // Without media, the block stays as code
+        assert!(
+            md_to_html("```figure\n3\n```", None, false, test_processor(), None)
+                .0
+                .contains("<code")
+        );
+        assert!(md_to_html("```figure\n1\n```", None, false, None, None)
+            .0
+            .contains("<code"));
+    }
+
+    #[test]
+    fn test_referenced_media() {
+        assert_eq!(
+            referenced_media(
+                "![](4) and ![](https://example.com/a.png)\n\n```gallery\n5 Five\n4\n```\n\n```rust\n6\n```"
+            ),
+            vec![4, 5]
+        );
+    }
+
+    #[test]
+    fn test_gemtext() {
+        assert_eq!(
+            md_to_gemtext(
+                "# Title\n\nSome [link](https://example.com) and ![a cat](1).\n\n* one\n* two\n\n> quote\n\n```rust\nfn main() {}\n```",
+                Some("plu.me"),
+                test_processor()
+            ),
+            "# Title\n\nSome link and .\n=> https://example.com link\n=> https://plu.me/static/media/1.png a cat\n\n\
+             * one\n* two\n\n> quote\n\n```rust\nfn main() {}\n```\n"
+        );
+        assert_eq!(
+            md_to_gemtext(
+                "```gallery\n1\n2 Listen\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |",
+                None,
+                test_processor()
+            ),
+            "=> /static/media/1.png A cat\n=> /static/media/2.ogg Listen\n\n```\na | b\n1 | 2\n```\n"
+        );
+        assert_eq!(
+            md_to_gemtext("Text[^1].\n\n[^1]: Note.", None, None),
+            "Text[1].\n\n[1] Note.\n"
+        );
+    }
+
+    #[test]
+    fn test_reading_stats() {
+        assert_eq!(reading_stats(""), (0, 0));
+        assert_eq!(
+            reading_stats("# Title\n\nIt's *only* [a link](https://example.com/many/words)."),
+            (5, 1)
+        );
+        assert_eq!(reading_stats("日本語の文章です"), (8, 1));
+        assert_eq!(reading_stats(&"word ".repeat(460)), (460, 2));
+        assert_eq!(reading_stats(&"字".repeat(1001)), (1001, 3));
+    }
+
+    #[test]
+    fn test_emoji() {
+        let processor = || -> Option<EmojiProcessor<'static>> {
+            Some(Box::new(|shortcode| {
+                if shortcode == "blobcat" {
+                    Some(format!("https://plu.me/static/media/{}.png", shortcode))
+                } else {
+                    None
+                }
+            }))
+        };
+        let img = r#"<img class="emoji" src="https://plu.me/static/media/blobcat.png" alt=":blobcat:" title=":blobcat:">"#;
+        assert_eq!(
+            md_to_html(
+                "Hi :blobcat:, :unknown: at 10:30:00 `:blobcat:`",
+                None,
+                true,
+                None,
+                processor()
+            )
+            .0,
+            format!(
+                "<p dir=\"auto\">Hi {}, :unknown: at 10:30:00 <code>:blobcat:</code></p>\n",
+                img
+            )
+        );
+        assert!(
+            !md_to_html("```\n:blobcat:\n```", None, false, None, processor())
+                .0
+                .contains("<img")
+        );
+        assert_eq!(
+            render_emoji_in_html(
+                r#"<p title=":blobcat:">:blobcat::blobcat:</p>"#,
+                &processor().unwrap()
+            ),
+            format!(r#"<p title=":blobcat:">{0}{0}</p>"#, img)
+        );
+        let mut shortcodes = emoji_shortcodes("::a: :blob_cat: :x:y:10:30:")
+            .into_iter()
+            .collect::<Vec<_>>();
+        shortcodes.sort();
+        assert_eq!(shortcodes, vec!["10", "blob_cat"]);
+    }
 }
@@ -518,6 +518,17 @@ fn init_popup(
     let license = get_elt_value("license");
     make_input(&i18n!(CATALOG, "Tags"), "popup-tags", &popup).set_value(&tags.join(", "));
     make_input(&i18n!(CATALOG, "License"), "popup-license", &popup).set_value(&license);
+    make_input(&i18n!(CATALOG, "Series"), "popup-series", &popup)
+        .set_value(&get_elt_value("series"));
+    if document.get_element_by_id("publish_at").is_some() {
+        let publish_at = make_input(
+            &i18n!(CATALOG, "Publication date"),
+            "popup-publish-at",
+            &popup,
+        );
+        publish_at.set_type("datetime-local");
+        publish_at.set_value(&get_elt_value("publish_at"));
+    }
 
     let cover_label = document
         .create_element("label")
@@ -543,6 +554,30 @@ fn init_popup(
         .append_child(&cover)
         .map_err(|_| EditorError::DOMError)?;
 
+    let visibility_label = document
+        .create_element("label")
+        .map_err(|_| EditorError::DOMError)?;
+    visibility_label
+        .append_child(&document.create_text_node(&i18n!(CATALOG, "Visibility")))
+        .map_err(|_| EditorError::DOMError)?;
+    visibility_label
+        .set_attribute("for", "visibility")
+        .map_err(|_| EditorError::DOMError)?;
+    let visibility = document
+        .get_element_by_id("visibility")
+        .ok_or(EditorError::NoneError)?;
+    visibility
+        .parent_element()
+        .ok_or(EditorError::NoneError)?
+        .remove_child(&visibility)
+        .ok();
+    popup
+        .append_child(&visibility_label)
+        .map_err(|_| EditorError::DOMError)?;
+    popup
+        .append_child(&visibility)
+        .map_err(|_| EditorError::DOMError)?;
+
     if let Some(draft_checkbox) = document.get_element_by_id("draft") {
         let draft_checkbox = draft_checkbox.dyn_ref::<HtmlInputElement>().unwrap();
         let draft_label = document
@@ -616,7 +651,14 @@ fn init_popup(
         let cover = document.get_element_by_id("cover").unwrap();
         cover.parent_element().unwrap().remove_child(&cover).ok();
         old_ed.append_child(&cover).unwrap();
+        let visibility = document.get_element_by_id("visibility").unwrap();
+        visibility.parent_element().unwrap().remove_child(&visibility).ok();
+        old_ed.append_child(&visibility).unwrap();
         set_value("license", get_elt_value("popup-license"));
+        set_value("series", get_elt_value("popup-series"));
+        if document.get_element_by_id("popup-publish-at").is_some() {
+            set_value("publish_at", get_elt_value("popup-publish-at"));
+        }
         clear_autosave();
         let old_ed = old_ed.dyn_ref::<HtmlFormElement>().unwrap();
         old_ed.submit().unwrap();
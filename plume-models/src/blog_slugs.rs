@@ -0,0 +1,54 @@
+use crate::{blogs::Blog, schema::blog_slugs, Connection, Error, Result};
+use chrono::NaiveDateTime;
+use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
+
+/// A name a local blog had before being renamed, that still leads to it.
+#[derive(Clone, Queryable, Identifiable, Associations)]
+#[belongs_to(Blog)]
+pub struct BlogSlug {
+    pub id: i32,
+    pub blog_id: i32,
+    pub slug: String,
+    pub creation_date: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "blog_slugs"]
+pub struct NewBlogSlug {
+    pub blog_id: i32,
+    pub slug: String,
+}
+
+impl BlogSlug {
+    insert!(blog_slugs, NewBlogSlug);
+    find_by!(blog_slugs, find_by_slug, slug as &str);
+    list_by!(blog_slugs, list_for_blog, blog_id as i32);
+
+    /// Finds the blog that was previously named `slug`.
+    pub fn find_blog(conn: &Connection, slug: &str) -> Result<Blog> {
+        Self::find_by_slug(conn, slug).and_then(|s| Blog::get(conn, s.blog_id))
+    }
+
+    /// Remembers the previous name of a blog, that is about to be renamed.
+    pub(crate) fn record(conn: &Connection, blog: &Blog) -> Result<Self> {
+        Self::insert(
+            conn,
+            NewBlogSlug {
+                blog_id: blog.id,
+                slug: blog.actor_id.clone(),
+            },
+        )
+    }
+
+    /// Forgets a previous name of a blog, once it uses it again.
+    pub(crate) fn release(conn: &Connection, blog_id: i32, slug: &str) -> Result<()> {
+        diesel::delete(
+            blog_slugs::table
+                .filter(blog_slugs::blog_id.eq(blog_id))
+                .filter(blog_slugs::slug.eq(slug)),
+        )
+        .execute(conn)
+        .map(|_| ())
+        .map_err(Error::from)
+    }
+}
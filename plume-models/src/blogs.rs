@@ -1,5 +1,11 @@
 use crate::{
-    instance::*, medias::Media, posts::Post, safe_string::SafeString, schema::blogs, users::User,
+    blog_slugs::BlogSlug,
+    instance::*,
+    medias::Media,
+    posts::{FeaturedOwner, Post},
+    safe_string::SafeString,
+    schema::blogs,
+    users::User,
     Connection, Error, PlumeRocket, Result, CONFIG, ITEMS_PER_PAGE,
 };
 use activitystreams::{
@@ -21,11 +27,12 @@ use openssl::{
 use plume_common::{
     activity_pub::{
         inbox::{AsActor, FromId},
-        sign, ActivityStream, ApSignature, CustomGroup, Id, IntoId, PublicKey, Source,
-        SourceProperty, ToAsString, ToAsUri,
+        sign, ActivityStream, ApSignature, CustomGroup, FeaturedProperty, Id, IntoId, PublicKey,
+        Source, SourceProperty, ToAsString, ToAsUri,
     },
     utils::iri_percent_encode_seg,
 };
+use std::sync::Arc;
 use webfinger::*;
 
 #[derive(Queryable, Identifiable, Clone, AsChangeset, Debug)]
@@ -142,6 +149,15 @@ impl Blog {
             .map_err(Error::from)
     }
 
+    /// Lists the blogs of this instance, sorted by title.
+    pub fn list_local(conn: &Connection) -> Result<Vec<Blog>> {
+        blogs::table
+            .filter(blogs::instance_id.eq(Instance::get_local()?.id))
+            .order(blogs::title.asc())
+            .load::<Blog>(conn)
+            .map_err(Error::from)
+    }
+
     pub fn find_by_fqn(conn: &Connection, fqn: &str) -> Result<Blog> {
         let from_db = blogs::table
             .filter(blogs::fqn.eq(fqn))
@@ -229,7 +245,34 @@ impl Blog {
             public_key: pub_key,
         };
 
-        Ok(CustomGroup::new(blog, ap_signature, source))
+        let featured = FeaturedProperty {
+            featured: Some(self.featured_url().parse()?),
+        };
+
+        Ok(CustomGroup::new(blog, ap_signature, source, featured))
+    }
+
+    /// The URL of the collection of the pinned posts of this blog
+    pub fn featured_url(&self) -> String {
+        format!("{}/featured", self.ap_url.trim_end_matches('/'))
+    }
+
+    pub fn featured(&self, conn: &Connection) -> Result<ActivityStream<OrderedCollection>> {
+        self.featured_collection(conn).map(ActivityStream::new)
+    }
+    pub fn featured_collection(&self, conn: &Connection) -> Result<OrderedCollection> {
+        let posts = Post::list_pinned_for_blog(conn, self)?
+            .into_iter()
+            .map(|post| {
+                let article = serde_json::to_value(post.to_activity(conn)?)?;
+                Ok(AnyBase::from_arbitrary_json(article)?)
+            })
+            .collect::<Result<Vec<AnyBase>>>()?;
+        let mut coll = OrderedCollection::new();
+        coll.set_id(self.featured_url().parse::<IriString>()?);
+        coll.set_total_items(posts.len() as u64);
+        coll.set_many_ordered_items(posts);
+        Ok(coll)
     }
 
     pub fn outbox(&self, conn: &Connection) -> Result<ActivityStream<OrderedCollection>> {
@@ -355,6 +398,37 @@ impl Blog {
             .and_then(|c| c.url().ok())
     }
 
+    /// Changes the name of a local blog, used in its address.
+    ///
+    /// Its ActivityPub URLs don't change, and its previous name keeps leading to it.
+    pub fn rename(&mut self, conn: &Connection, slug: &str) -> Result<()> {
+        if !self.is_local() {
+            return Err(Error::InvalidValue);
+        }
+        if slug == self.actor_id {
+            return Ok(());
+        }
+        let taken = blogs::table
+            .filter(blogs::fqn.eq(slug))
+            .first::<Blog>(conn)
+            .optional()?
+            .is_some()
+            || BlogSlug::find_blog(conn, slug)
+                .map(|blog| blog.id != self.id)
+                .unwrap_or(false);
+        if taken {
+            return Err(Error::InvalidValue);
+        }
+
+        BlogSlug::release(conn, self.id, slug)?;
+        BlogSlug::record(conn, self)?;
+        self.actor_id = slug.to_owned();
+        self.fqn = slug.to_owned();
+        self.save_changes::<Blog>(conn)?;
+        Post::forget_blog_fqn(self.id);
+        Ok(())
+    }
+
     pub fn delete(&self, conn: &Connection) -> Result<()> {
         for post in Post::get_for_blog(conn, self)? {
             post.delete(conn)?;
@@ -408,6 +482,7 @@ impl FromId<Connection> for Blog {
             ..NewBlog::default()
         };
 
+        let featured = acct.ext_three.featured.map(|url| url.to_string());
         let object = ApObject::new(acct.inner);
         new_blog.title = object
             .name()
@@ -484,7 +559,11 @@ impl FromId<Connection> for Blog {
         })?;
         new_blog.instance_id = instance.id;
 
-        Blog::insert(conn, new_blog)
+        let blog = Blog::insert(conn, new_blog)?;
+        if let Some(featured) = featured {
+            FeaturedOwner::Blog(Arc::new(blog.clone())).featured_found(featured);
+        }
+        Ok(blog)
     }
 
     fn get_sender() -> &'static dyn sign::Signer {
@@ -700,6 +779,23 @@ pub(crate) mod tests {
         })
     }
 
+    #[test]
+    fn list_local() {
+        let conn = &db();
+        conn.test_transaction::<_, (), _>(|| {
+            let (_, blogs) = fill_database(conn);
+            assert_eq!(
+                Blog::list_local(conn)
+                    .unwrap()
+                    .into_iter()
+                    .map(|b| b.id)
+                    .collect::<Vec<_>>(),
+                vec![blogs[0].id, blogs[1].id, blogs[2].id]
+            );
+            Ok(())
+        })
+    }
+
     #[test]
     fn authors() {
         let conn = &db();
@@ -859,6 +955,34 @@ pub(crate) mod tests {
         })
     }
 
+    #[test]
+    fn rename() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (_, blogs) = fill_database(conn);
+            let mut blog = blogs[0].clone();
+            let old_name = blog.actor_id.clone();
+            let ap_url = blog.ap_url.clone();
+
+            blog.rename(conn, "renamed")?;
+            assert_eq!(Blog::find_by_fqn(conn, "renamed")?.id, blog.id);
+            assert_eq!(BlogSlug::find_blog(conn, &old_name)?.id, blog.id);
+            assert_eq!(Blog::get(conn, blog.id)?.ap_url, ap_url);
+
+            // the previous name of a blog can't be taken by another one
+            let mut other = blogs[1].clone();
+            assert!(other.rename(conn, &old_name).is_err());
+            assert!(other.rename(conn, "renamed").is_err());
+
+            // but the blog itself can use it again
+            blog.rename(conn, &old_name)?;
+            assert_eq!(Blog::find_by_fqn(conn, &old_name)?.id, blog.id);
+            assert!(BlogSlug::find_blog(conn, &old_name).is_err());
+            assert_eq!(BlogSlug::find_blog(conn, "renamed")?.id, blog.id);
+            Ok(())
+        })
+    }
+
     #[test]
     fn delete_via_user() {
         let conn = &db();
@@ -1010,6 +1134,7 @@ pub(crate) mod tests {
                 "inbox": "https://plu.me/~/BlogName/inbox",
                 "name": "Blog name",
                 "outbox": "https://plu.me/~/BlogName/outbox",
+                "featured": "https://plu.me/~/BlogName/featured",
                 "preferredUsername": "BlogName",
                 "publicKey": {
                     "id": "https://plu.me/~/BlogName/#main-key",
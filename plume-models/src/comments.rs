@@ -1,5 +1,6 @@
 use crate::{
     comment_seers::{CommentSeers, NewCommentSeers},
+    emojis::Emoji,
     instance::Instance,
     medias::Media,
     mentions::Mention,
@@ -7,6 +8,7 @@ use crate::{
     posts::Post,
     safe_string::SafeString,
     schema::comments,
+    user_blocks::UserBlock,
     users::User,
     Connection, Error, Result, CONFIG,
 };
@@ -111,6 +113,15 @@ impl Comment {
                 .unwrap_or(false)
     }
 
+    /// Whether `user` blocked the author of this comment.
+    pub fn is_hidden_for(&self, conn: &Connection, user: Option<&User>) -> bool {
+        user.map_or(false, |u| {
+            self.get_author(conn)
+                .and_then(|author| UserBlock::is_blocked(conn, u, &author))
+                .unwrap_or(false)
+        })
+    }
+
     pub fn to_activity(&self, conn: &Connection) -> Result<Note> {
         let author = User::get(conn, self.author_id)?;
         let (html, mentions, _hashtags) = utils::md_to_html(
@@ -118,6 +129,7 @@ impl Comment {
             Some(&Instance::get_local()?.public_domain),
             true,
             Some(Media::get_media_processor(conn, vec![&author])),
+            None,
         );
 
         let mut note = Note::new();
@@ -141,11 +153,21 @@ impl Comment {
         );
         note.set_attributed_to(author.into_id().parse::<IriString>()?);
         note.set_many_tos(to);
-        note.set_many_tags(mentions.into_iter().filter_map(|m| {
-            Mention::build_activity(conn, &m)
-                .map(|mention| mention.into_any_base().expect("Can convert"))
-                .ok()
-        }));
+        let emojis = Emoji::list_used_in(conn, self.content.get())?;
+        note.set_many_tags(
+            mentions
+                .into_iter()
+                .filter_map(|m| {
+                    Mention::build_activity(conn, &m)
+                        .map(|mention| mention.into_any_base().expect("Can convert"))
+                        .ok()
+                })
+                .chain(
+                    emojis
+                        .into_iter()
+                        .filter_map(|emoji| emoji.to_activity(conn).ok()?.into_any_base().ok()),
+                ),
+        );
         Ok(note)
     }
 
@@ -177,11 +199,13 @@ impl Comment {
     }
 
     pub fn notify(&self, conn: &Connection) -> Result<()> {
+        let commenter = self.get_author(conn)?;
         for author in self.get_post(conn)?.get_authors(conn)? {
             if Mention::list_for_comment(conn, self.id)?
                 .iter()
                 .all(|m| m.get_mentioned(conn).map(|u| u != author).unwrap_or(true))
                 && author.is_local()
+                && !UserBlock::is_blocked(conn, &author, &commenter)?
             {
                 Notification::insert(
                     conn,
@@ -252,16 +276,30 @@ impl FromId<Connection> for Comment {
 
             let summary = note.summary().and_then(|summary| summary.to_as_string());
             let sensitive = summary.is_some();
+            let author = User::from_id(
+                conn,
+                &note
+                    .attributed_to()
+                    .ok_or(Error::MissingApProperty)?
+                    .to_as_uri()
+                    .ok_or(Error::MissingApProperty)?,
+                None,
+                CONFIG.proxy(),
+            )
+            .map_err(|(_, e)| e)?;
+            let emojis = Emoji::from_tags(conn, note.tag(), &author);
             let comm = Comment::insert(
                 conn,
                 NewComment {
-                    content: SafeString::new(
+                    content: SafeString::new(&Emoji::render(
+                        conn,
                         &note
                             .content()
                             .ok_or(Error::MissingApProperty)?
                             .to_as_string()
                             .ok_or(Error::InvalidValue)?,
-                    ),
+                        &emojis,
+                    )),
                     spoiler_text: summary.unwrap_or_default(),
                     ap_url: Some(
                         note.id_unchecked()
@@ -272,18 +310,7 @@ impl FromId<Connection> for Comment {
                     post_id: previous_comment.map(|c| c.post_id).or_else(|_| {
                         Ok(Post::find_by_ap_url(conn, previous_url.as_str())?.id) as Result<i32>
                     })?,
-                    author_id: User::from_id(
-                        conn,
-                        &note
-                            .attributed_to()
-                            .ok_or(Error::MissingApProperty)?
-                            .to_as_uri()
-                            .ok_or(Error::MissingApProperty)?,
-                        None,
-                        CONFIG.proxy(),
-                    )
-                    .map_err(|(_, e)| e)?
-                    .id,
+                    author_id: author.id,
                     sensitive,
                     public_visibility,
                 },
@@ -293,11 +320,12 @@ impl FromId<Connection> for Comment {
             if let Some(tags) = note.tag() {
                 let author_url = &Post::get(conn, comm.post_id)?.get_authors(conn)?[0].ap_url;
                 for tag in tags.iter() {
-                    let m = tag.clone().extend::<link::Mention, MentionType>()?; // FIXME: Don't clone
-                    if m.is_none() {
-                        continue;
-                    }
-                    let m = m.unwrap();
+                    // FIXME: Don't clone
+                    let m = match tag.clone().extend::<link::Mention, MentionType>() {
+                        Ok(Some(m)) => m,
+                        // Other tags, like hashtags or emoji
+                        _ => continue,
+                    };
                     let not_author = m.href().ok_or(Error::MissingApProperty)? != author_url;
                     let _ = Mention::from_activity(conn, &m, comm.id, false, not_author);
                 }
@@ -403,7 +431,7 @@ impl CommentTree {
         Ok(Comment::list_by_post(conn, p.id)?
             .into_iter()
             .filter(|c| c.in_response_to_id.is_none())
-            .filter(|c| c.can_see(conn, user))
+            .filter(|c| c.can_see(conn, user) && !c.is_hidden_for(conn, user))
             .filter_map(|c| Self::from_comment(conn, c, user).ok())
             .collect())
     }
@@ -412,7 +440,7 @@ impl CommentTree {
         let responses = comment
             .get_responses(conn)?
             .into_iter()
-            .filter(|c| c.can_see(conn, user))
+            .filter(|c| c.can_see(conn, user) && !c.is_hidden_for(conn, user))
             .filter_map(|c| Self::from_comment(conn, c, user).ok())
             .collect();
         Ok(CommentTree { comment, responses })
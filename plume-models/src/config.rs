@@ -1,6 +1,7 @@
 use crate::search::TokenizerKind as SearchTokenizer;
 use crate::signups::Strategy as SignupStrategy;
 use crate::smtp::{SMTP_PORT, SUBMISSIONS_PORT, SUBMISSION_PORT};
+use chrono::Duration;
 use rocket::config::Limits;
 use rocket::Config as RocketConfig;
 use std::collections::HashSet;
@@ -31,6 +32,9 @@ pub struct Config {
     pub ldap: Option<LdapConfig>,
     pub proxy: Option<ProxyConfig>,
     pub s3: Option<S3Config>,
+    pub gemini: Option<GeminiConfig>,
+    /// How long deliveries to an instance can fail before it is considered unreachable
+    pub unreachable_after: Duration,
 }
 
 impl Config {
@@ -456,6 +460,57 @@ fn get_s3_config() -> Option<S3Config> {
     }
 }
 
+pub struct GeminiConfig {
+    pub address: String,
+    pub port: u16,
+    // hostname used in gemini:// links. If not set, default to the instance domain
+    pub hostname: Option<String>,
+    // PEM encoded certificate chain and PKCS#8 private key
+    pub tls_certificate: String,
+    pub tls_key: String,
+}
+
+impl GeminiConfig {
+    pub fn base_url(&self, domain: &str) -> String {
+        let hostname = self.hostname.as_deref().unwrap_or(domain);
+        if self.port == GEMINI_PORT {
+            format!("gemini://{}", hostname)
+        } else {
+            format!("gemini://{}:{}", hostname, self.port)
+        }
+    }
+}
+
+pub const GEMINI_PORT: u16 = 1965;
+
+fn get_gemini_config() -> Option<GeminiConfig> {
+    let enabled = var("GEMINI_ENABLED").unwrap_or_else(|_| "false".to_owned());
+    if !string_to_bool(&enabled, "GEMINI_ENABLED") {
+        return None;
+    }
+
+    #[cfg(not(feature = "gemini"))]
+    {
+        tracing::warn!("GEMINI_ENABLED is ignored: Gemini support is not enabled in this build");
+        None
+    }
+
+    #[cfg(feature = "gemini")]
+    {
+        Some(GeminiConfig {
+            address: var("GEMINI_ADDRESS").unwrap_or_else(|_| "localhost".to_owned()),
+            port: var("GEMINI_PORT").map_or(GEMINI_PORT, |port| {
+                port.parse().expect("GEMINI_PORT must be an integer")
+            }),
+            hostname: var("GEMINI_HOSTNAME").ok(),
+            tls_certificate: var("GEMINI_TLS_CERTIFICATE")
+                .expect("Invalid Gemini configuration: GEMINI_TLS_CERTIFICATE must be set"),
+            tls_key: var("GEMINI_TLS_KEY")
+                .expect("Invalid Gemini configuration: GEMINI_TLS_KEY must be set"),
+        })
+    }
+}
+
 lazy_static! {
     pub static ref CONFIG: Config = Config {
         base_url: var("BASE_URL").unwrap_or_else(|_| format!(
@@ -488,5 +543,10 @@ lazy_static! {
         ldap: get_ldap_config(),
         proxy: get_proxy_config(),
         s3: get_s3_config(),
+        gemini: get_gemini_config(),
+        unreachable_after: Duration::days(var("UNREACHABLE_AFTER_DAYS").map_or(7, |days| {
+            days.parse()
+                .expect("UNREACHABLE_AFTER_DAYS must be a number of days")
+        })),
     };
 }
@@ -0,0 +1,420 @@
+use crate::{
+    blogs::Blog, instance::Instance, schema::deliveries, users::User, Connection, Error, Result,
+    CONFIG,
+};
+use activitystreams::markers::Activity;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
+use once_cell::sync::Lazy;
+use plume_common::{
+    activity_pub::{deliver, inbox::AsActor, inboxes, sign::Signer, sign_activity},
+    utils::random_hex,
+};
+use reqwest::Url;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, TryLockError},
+};
+use tracing::warn;
+
+/// Deliveries that failed this many times are not retried anymore, until an admin asks for it.
+pub const MAX_ATTEMPTS: i32 = 12;
+
+/// How many deliveries are sent at each run of the queue.
+const BATCH_SIZE: i64 = 100;
+
+/// Makes sure only one thread sends the queued deliveries at a time.
+static PROCESSING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// An activity waiting to be posted to a remote inbox.
+///
+/// When a delivery fails, it is retried later, waiting twice as long after each failure.
+/// After `MAX_ATTEMPTS` failures, it stays in the queue until an admin retries or purges it.
+#[derive(Clone, Queryable, Identifiable, AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+#[table_name = "deliveries"]
+pub struct Delivery {
+    pub id: i32,
+    pub activity_id: String,
+    pub inbox: String,
+    /// The AP URL of the user or blog signing the activity
+    pub sender: String,
+    pub body: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub creation_date: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "deliveries"]
+pub struct NewDelivery {
+    pub activity_id: String,
+    pub inbox: String,
+    pub sender: String,
+    pub body: String,
+    pub next_attempt_at: NaiveDateTime,
+}
+
+impl Delivery {
+    insert!(deliveries, NewDelivery);
+    get!(deliveries);
+    find_by!(
+        deliveries,
+        find_by_activity,
+        activity_id as &str,
+        inbox as &str
+    );
+
+    /// Queues an activity for the inboxes of `to`.
+    ///
+    /// Each inbox is only queued once, even when it is shared by many actors or when
+    /// the same activity is broadcasted again before it was sent. Unreachable instances
+    /// are skipped.
+    pub fn broadcast<S, A, T, C>(conn: &Connection, sender: &S, act: A, to: Vec<T>) -> Result<()>
+    where
+        S: Signer,
+        A: Activity + Serialize,
+        T: AsActor<C>,
+    {
+        let unreachable = Instance::unreachable_domains(conn)?;
+        let inboxes = inboxes(to)
+            .into_iter()
+            .filter(|inbox| inbox_domain(inbox).map_or(true, |d| !unreachable.contains(&d)))
+            .collect::<Vec<_>>();
+        if inboxes.is_empty() {
+            return Ok(());
+        }
+
+        let act = sign_activity(sender, act);
+        let activity_id = act["id"]
+            .as_str()
+            .map(String::from)
+            .unwrap_or_else(random_hex);
+        let sender = sender
+            .get_key_id()
+            .split('#')
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        let body = act.to_string();
+        for inbox in inboxes {
+            if Delivery::find_by_activity(conn, &activity_id, &inbox).is_ok() {
+                continue;
+            }
+            Delivery::insert(
+                conn,
+                NewDelivery {
+                    activity_id: activity_id.clone(),
+                    inbox,
+                    sender: sender.clone(),
+                    body: body.clone(),
+                    next_attempt_at: Utc::now().naive_utc(),
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn page(conn: &Connection, (min, max): (i32, i32)) -> Result<Vec<Delivery>> {
+        deliveries::table
+            .order(deliveries::next_attempt_at.asc())
+            .offset(min.into())
+            .limit((max - min).into())
+            .load::<Delivery>(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn count(conn: &Connection) -> Result<i64> {
+        deliveries::table
+            .count()
+            .get_result(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn count_failed(conn: &Connection) -> Result<i64> {
+        deliveries::table
+            .filter(deliveries::attempts.ge(MAX_ATTEMPTS))
+            .count()
+            .get_result(conn)
+            .map_err(Error::from)
+    }
+
+    /// Lists the deliveries that should be tried now.
+    pub fn list_due(conn: &Connection) -> Result<Vec<Delivery>> {
+        deliveries::table
+            .filter(deliveries::attempts.lt(MAX_ATTEMPTS))
+            .filter(deliveries::next_attempt_at.le(Utc::now().naive_utc()))
+            .order(deliveries::next_attempt_at.asc())
+            .limit(BATCH_SIZE)
+            .load::<Delivery>(conn)
+            .map_err(Error::from)
+    }
+
+    /// Whether this delivery gave up, and waits for an admin.
+    pub fn is_failed(&self) -> bool {
+        self.attempts >= MAX_ATTEMPTS
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<Self> {
+        diesel::update(self).set(self).execute(conn)?;
+        Self::get(conn, self.id)
+    }
+
+    pub fn delete(&self, conn: &Connection) -> Result<()> {
+        diesel::delete(self)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    /// Schedules this delivery to be tried again as soon as possible.
+    pub fn retry(&mut self, conn: &Connection) -> Result<Self> {
+        self.attempts = 0;
+        self.next_attempt_at = Utc::now().naive_utc();
+        self.update(conn)
+    }
+
+    /// Schedules all the failed deliveries to be tried again, and returns how many there were.
+    pub fn retry_failed(conn: &Connection) -> Result<usize> {
+        diesel::update(deliveries::table.filter(deliveries::attempts.ge(MAX_ATTEMPTS)))
+            .set((
+                deliveries::attempts.eq(0),
+                deliveries::next_attempt_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(conn)
+            .map_err(Error::from)
+    }
+
+    /// Deletes all the failed deliveries, and returns how many there were.
+    pub fn purge_failed(conn: &Connection) -> Result<usize> {
+        diesel::delete(deliveries::table.filter(deliveries::attempts.ge(MAX_ATTEMPTS)))
+            .execute(conn)
+            .map_err(Error::from)
+    }
+
+    /// Records a failed attempt, and schedules the next one.
+    pub fn fail(&mut self, conn: &Connection, error: String) -> Result<Self> {
+        // Wait 1 minute after the first failure, then 2, 4, 8, …
+        let delay = Duration::minutes(1 << self.attempts.clamp(0, MAX_ATTEMPTS));
+        self.attempts += 1;
+        self.next_attempt_at = Utc::now().naive_utc() + delay;
+        self.last_error = Some(error);
+        self.update(conn)
+    }
+
+    /// Schedules this delivery later, without counting it as a failed attempt.
+    pub fn postpone(&mut self, conn: &Connection, delay: Duration, reason: String) -> Result<Self> {
+        self.next_attempt_at = Utc::now().naive_utc() + delay;
+        self.last_error = Some(reason);
+        self.update(conn)
+    }
+
+    fn find_sender(conn: &Connection, ap_url: &str) -> Result<Box<dyn Signer>> {
+        User::find_by_ap_url(conn, ap_url)
+            .map(|user| Box::new(user) as Box<dyn Signer>)
+            .or_else(|_| {
+                Blog::find_by_ap_url(conn, ap_url).map(|blog| Box::new(blog) as Box<dyn Signer>)
+            })
+    }
+
+    /// Sends the deliveries that are due, and returns how many of them succeeded.
+    pub fn process_due(conn: &Connection) -> Result<usize> {
+        let _lock = match PROCESSING.try_lock() {
+            Ok(lock) => lock,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            // Another thread is already sending them
+            Err(TryLockError::WouldBlock) => return Ok(0),
+        };
+
+        let unreachable = Instance::unreachable_domains(conn)?;
+        let mut senders = HashMap::new();
+        let mut due = vec![];
+        for mut delivery in Delivery::list_due(conn)? {
+            if inbox_domain(&delivery.inbox).map_or(false, |d| unreachable.contains(&d)) {
+                // It will be sent once the instance answers again, that is checked every hour
+                delivery.postpone(
+                    conn,
+                    Duration::hours(1),
+                    "The instance is unreachable".into(),
+                )?;
+                continue;
+            }
+            let sender = senders
+                .entry(delivery.sender.clone())
+                .or_insert_with(|| Delivery::find_sender(conn, &delivery.sender).ok());
+            if sender.is_some() {
+                due.push(delivery);
+            } else {
+                // Nobody can sign it anymore, there is no point in trying again
+                delivery.attempts = MAX_ATTEMPTS;
+                delivery.last_error = Some("The sender doesn't exist anymore".into());
+                delivery.update(conn)?;
+            }
+        }
+        if due.is_empty() {
+            return Ok(0);
+        }
+
+        let results = deliver(
+            due.iter()
+                .filter_map(|delivery| {
+                    let sender = senders.get(&delivery.sender)?.as_deref()?;
+                    Some((sender, delivery.inbox.clone(), delivery.body.clone()))
+                })
+                .collect(),
+            CONFIG.proxy().cloned(),
+        );
+
+        let mut instances = HashMap::new();
+        let mut sent = 0;
+        for (mut delivery, result) in due.into_iter().zip(results) {
+            if let Some(domain) = inbox_domain(&delivery.inbox) {
+                let instance = instances
+                    .entry(domain)
+                    .or_insert_with_key(|domain| Instance::find_by_domain(conn, domain).ok());
+                if let Some(instance) = instance {
+                    *instance = instance.record_delivery(conn, result.is_ok())?;
+                }
+            }
+            match result {
+                Ok(()) => {
+                    delivery.delete(conn)?;
+                    sent += 1;
+                }
+                Err(e) => {
+                    warn!("Delivery to {} failed: {}", delivery.inbox, e);
+                    delivery.fail(conn, e)?;
+                }
+            }
+        }
+        Ok(sent)
+    }
+}
+
+/// The domain of an inbox, as the `public_domain` of its instance.
+fn inbox_domain(inbox: &str) -> Option<String> {
+    let url = Url::parse(inbox).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{schema::instances, tests::db, users::tests as user_tests};
+    use activitystreams::{activity::Like, iri_string::types::IriString, prelude::*};
+    use diesel::Connection;
+
+    fn like(id: &str) -> Like {
+        let mut act = Like::new(
+            "https://plu.me/@/admin/".parse::<IriString>().unwrap(),
+            "https://remote.example/posts/1"
+                .parse::<IriString>()
+                .unwrap(),
+        );
+        act.set_id(id.parse::<IriString>().unwrap());
+        act
+    }
+
+    #[test]
+    fn broadcast_and_retry() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let users = user_tests::fill_database(conn);
+            let remote = Instance::get_remotes(conn)?[0].id;
+            let targets = ["alice", "bob"]
+                .iter()
+                .map(|name| {
+                    let mut user = users[1].clone();
+                    user.instance_id = remote;
+                    user.inbox_url = format!("https://remote.example/@/{}/inbox", name);
+                    user.shared_inbox_url = Some("https://remote.example/inbox".to_owned());
+                    user
+                })
+                .collect::<Vec<_>>();
+
+            Delivery::broadcast(
+                conn,
+                &users[0],
+                like("https://plu.me/like/1"),
+                targets.clone(),
+            )?;
+            // Broadcasting it again doesn't queue it twice
+            Delivery::broadcast(
+                conn,
+                &users[0],
+                like("https://plu.me/like/1"),
+                targets.clone(),
+            )?;
+            // Local users are not queued
+            Delivery::broadcast(
+                conn,
+                &users[0],
+                like("https://plu.me/like/2"),
+                vec![users[1].clone()],
+            )?;
+            assert_eq!(Delivery::count(conn)?, 1);
+
+            let mut delivery = Delivery::list_due(conn)?.remove(0);
+            assert_eq!(delivery.inbox, "https://remote.example/inbox");
+            assert_eq!(delivery.sender, users[0].ap_url);
+            assert!(delivery.body.contains("signature"));
+
+            let mut delivery = delivery.fail(conn, "Timeout".to_owned())?;
+            assert_eq!(delivery.attempts, 1);
+            assert!(delivery.next_attempt_at > Utc::now().naive_utc());
+            assert!(Delivery::list_due(conn)?.is_empty());
+
+            delivery.attempts = MAX_ATTEMPTS - 1;
+            let delivery = delivery.fail(conn, "Timeout".to_owned())?;
+            assert!(delivery.is_failed());
+            assert_eq!(Delivery::count_failed(conn)?, 1);
+
+            assert_eq!(Delivery::retry_failed(conn)?, 1);
+            assert_eq!(Delivery::list_due(conn)?.len(), 1);
+            assert_eq!(Delivery::purge_failed(conn)?, 0);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn skips_unreachable() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let users = user_tests::fill_database(conn);
+            let instance = Instance::get_remotes(conn)?.remove(0);
+            diesel::update(&instance)
+                .set(instances::unreachable.eq(true))
+                .execute(conn)?;
+            let mut target = users[1].clone();
+            target.instance_id = instance.id;
+            target.inbox_url = format!("https://{}/inbox", instance.public_domain);
+            target.shared_inbox_url = None;
+
+            Delivery::broadcast(conn, &users[0], like("https://plu.me/like/3"), vec![target])?;
+            assert_eq!(Delivery::count(conn)?, 0);
+
+            // Deliveries queued before it became unreachable wait for it to come back
+            Delivery::insert(
+                conn,
+                NewDelivery {
+                    activity_id: "https://plu.me/like/4".to_owned(),
+                    inbox: format!("https://{}/inbox", instance.public_domain),
+                    sender: users[0].ap_url.clone(),
+                    body: "{}".to_owned(),
+                    next_attempt_at: Utc::now().naive_utc(),
+                },
+            )?;
+            assert_eq!(Delivery::process_due(conn)?, 0);
+            let delivery = Delivery::page(conn, (0, 10))?.remove(0);
+            assert_eq!(delivery.attempts, 0);
+            assert!(delivery.next_attempt_at > Utc::now().naive_utc());
+            Ok(())
+        });
+    }
+}
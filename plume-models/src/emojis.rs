@@ -0,0 +1,419 @@
+use crate::{
+    ap_url,
+    instance::Instance,
+    medias::Media,
+    schema::emojis,
+    users::{User, UserEvent::*},
+    Connection, Error, Result, USER_CHAN,
+};
+use activitystreams::{
+    base::AnyBase,
+    iri_string::types::IriString,
+    object::{kind::ImageType, Image},
+    prelude::*,
+    primitives::OneOrMany,
+};
+use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
+use plume_common::{
+    activity_pub::{Emoji as EmojiObject, EmojiType, ToAsString, ToAsUri},
+    utils::{emoji_shortcodes, render_emoji_in_html, EmojiProcessor},
+};
+use riker::actors::{Publish, Tell};
+use std::sync::Arc;
+
+/// The most emoji that are saved for a single post or comment of another instance.
+const MAX_EMOJI_PER_OBJECT: usize = 20;
+
+/// A custom emoji, that replaces its `:shortcode:` in posts, comments and profiles.
+///
+/// Local emoji are managed by the admins, remote ones are received with the content using them.
+#[derive(Clone, Identifiable, Queryable, AsChangeset)]
+pub struct Emoji {
+    pub id: i32,
+    pub shortcode: String,
+    pub media_id: i32,
+    pub instance_id: i32,
+    pub ap_url: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "emojis"]
+pub struct NewEmoji {
+    pub shortcode: String,
+    pub media_id: i32,
+    pub instance_id: i32,
+    pub ap_url: String,
+}
+
+impl Emoji {
+    insert!(emojis, NewEmoji);
+    get!(emojis);
+    find_by!(emojis, find_by_ap_url, ap_url as &str);
+    find_by!(
+        emojis,
+        find_by_shortcode,
+        instance_id as i32,
+        shortcode as &str
+    );
+
+    /// Shortcodes are made of at least two letters, digits or underscores.
+    pub fn is_valid_shortcode(shortcode: &str) -> bool {
+        shortcode.len() >= 2
+            && shortcode
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    /// Adds a custom emoji to this instance, using a picture of the media gallery.
+    pub fn new_local(conn: &Connection, shortcode: &str, media: &Media) -> Result<Emoji> {
+        if !Emoji::is_valid_shortcode(shortcode) {
+            return Err(Error::InvalidValue);
+        }
+        let instance = Instance::get_local()?;
+        Emoji::insert(
+            conn,
+            NewEmoji {
+                shortcode: shortcode.to_owned(),
+                media_id: media.id,
+                instance_id: instance.id,
+                ap_url: ap_url(&format!("{}/emojis/{}", instance.public_domain, shortcode)),
+            },
+        )
+    }
+
+    pub fn list_local(conn: &Connection) -> Result<Vec<Emoji>> {
+        emojis::table
+            .filter(emojis::instance_id.eq(Instance::get_local()?.id))
+            .order(emojis::shortcode.asc())
+            .load::<Emoji>(conn)
+            .map_err(Error::from)
+    }
+
+    /// Lists the local emoji used in some Markdown or HTML.
+    pub fn list_used_in(conn: &Connection, content: &str) -> Result<Vec<Emoji>> {
+        let shortcodes = emoji_shortcodes(content);
+        if shortcodes.is_empty() {
+            return Ok(vec![]);
+        }
+        emojis::table
+            .filter(emojis::instance_id.eq(Instance::get_local()?.id))
+            .filter(emojis::shortcode.eq_any(shortcodes))
+            .load::<Emoji>(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<Self> {
+        diesel::update(self).set(self).execute(conn)?;
+        Self::get(conn, self.id)
+    }
+
+    pub fn delete(&self, conn: &Connection) -> Result<()> {
+        diesel::delete(self)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    pub fn url(&self, conn: &Connection) -> Result<String> {
+        Media::get(conn, self.media_id)?.url()
+    }
+
+    /// Renders the emoji of this instance, for local content.
+    pub fn get_emoji_processor(conn: &Connection) -> EmojiProcessor<'_> {
+        Box::new(move |shortcode| {
+            let local = Instance::get_local().ok()?;
+            Emoji::find_by_shortcode(conn, local.id, shortcode)
+                .and_then(|emoji| emoji.url(conn))
+                .ok()
+        })
+    }
+
+    /// Renders some remote emoji in HTML.
+    ///
+    /// The result should be sanitized afterwards.
+    pub fn render(conn: &Connection, html: &str, emojis: &[Emoji]) -> String {
+        if emojis.is_empty() {
+            return html.to_owned();
+        }
+        let processor: EmojiProcessor<'_> = Box::new(|shortcode| {
+            emojis
+                .iter()
+                .find(|emoji| emoji.shortcode == shortcode)
+                .and_then(|emoji| emoji.url(conn).ok())
+        });
+        render_emoji_in_html(html, &processor)
+    }
+
+    pub fn to_activity(&self, conn: &Connection) -> Result<EmojiObject> {
+        let mut icon = Image::new();
+        icon.set_url(self.url(conn)?.parse::<IriString>()?);
+
+        let mut emoji = EmojiObject::new();
+        emoji.set_id(self.ap_url.parse::<IriString>()?);
+        emoji.set_name(format!(":{}:", self.shortcode));
+        emoji.set_icon(icon.into_any_base()?);
+        Ok(emoji)
+    }
+
+    /// Saves or updates a remote emoji.
+    ///
+    /// Its image is downloaded in the background to a media owned by `author`,
+    /// and is used from the other instance until then.
+    pub fn from_activity(conn: &Connection, emoji: &EmojiObject, author: &User) -> Result<Emoji> {
+        let id = emoji.id_unchecked().ok_or(Error::MissingApProperty)?;
+        let ap_url = id.to_string();
+        let shortcode = emoji
+            .name()
+            .and_then(|name| name.to_as_string())
+            .ok_or(Error::MissingApProperty)?
+            .trim_matches(':')
+            .to_owned();
+        if !Emoji::is_valid_shortcode(&shortcode) {
+            return Err(Error::InvalidValue);
+        }
+        let url = emoji
+            .icon()
+            .and_then(|icon| icon.iter().next())
+            .and_then(|icon| icon.clone().extend::<Image, ImageType>().ok())
+            .and_then(|image| image)
+            .and_then(|image| image.url().and_then(|url| url.to_as_uri()))
+            .ok_or(Error::MissingApProperty)?;
+
+        let existing = Emoji::find_by_ap_url(conn, &ap_url);
+        if let Ok(ref emoji) = existing {
+            if emoji.instance_id != author.instance_id {
+                // It comes from another instance, like our own emoji used in a reply
+                return existing;
+            }
+        }
+        // Only the instance of the author can define the emoji it uses
+        let host = id
+            .authority_components()
+            .ok_or(Error::Url)?
+            .host()
+            .to_owned();
+        if host != author.get_instance(conn)?.public_domain {
+            return Err(Error::Unauthorized);
+        }
+        let existing =
+            existing.or_else(|_| Emoji::find_by_shortcode(conn, author.instance_id, &shortcode));
+        match existing {
+            Ok(mut existing) => {
+                let old_media = Media::get(conn, existing.media_id)?;
+                if existing.ap_url == ap_url
+                    && existing.shortcode == shortcode
+                    && old_media.remote_url.as_ref() == Some(&url)
+                {
+                    return Ok(existing);
+                }
+                existing.ap_url = ap_url;
+                existing.shortcode = shortcode;
+                if old_media.remote_url.as_ref() != Some(&url) {
+                    existing.media_id = Media::save_remote(conn, url, author)?.id;
+                    let emoji = existing.update(conn)?;
+                    // The emoji doesn't use this media anymore, it can be deleted without cascading
+                    old_media.delete(conn)?;
+                    emoji.download_later(author);
+                    Ok(emoji)
+                } else {
+                    existing.update(conn)
+                }
+            }
+            Err(_) => {
+                let emoji = Emoji::insert(
+                    conn,
+                    NewEmoji {
+                        media_id: Media::save_remote(conn, url, author)?.id,
+                        shortcode,
+                        instance_id: author.instance_id,
+                        ap_url,
+                    },
+                )?;
+                emoji.download_later(author);
+                Ok(emoji)
+            }
+        }
+    }
+
+    fn download_later(&self, author: &User) {
+        USER_CHAN.tell(
+            Publish {
+                msg: RemoteEmojiFound(self.id, Arc::new(author.clone())),
+                topic: "user.remote_emoji_found".into(),
+            },
+            None,
+        )
+    }
+
+    /// Replaces the remote image of this emoji with a copy owned by `author`.
+    pub fn download(&self, conn: &Connection, author: &User) -> Result<Emoji> {
+        let old_media = Media::get(conn, self.media_id)?;
+        if !old_media.is_remote {
+            return Ok(self.clone());
+        }
+        let url = old_media.remote_url.clone().ok_or(Error::NotFound)?;
+        let mut emoji = self.clone();
+        emoji.media_id = Media::save_download(conn, url, self.shortcode.clone(), author)?.id;
+        let emoji = emoji.update(conn)?;
+        old_media.delete(conn)?;
+        Ok(emoji)
+    }
+
+    /// Saves the emoji found in the tags of a remote object, ignoring invalid ones.
+    ///
+    /// Only the first `MAX_EMOJI_PER_OBJECT` are saved.
+    pub fn from_tags(
+        conn: &Connection,
+        tags: Option<&OneOrMany<AnyBase>>,
+        author: &User,
+    ) -> Vec<Emoji> {
+        tags.map(|tags| {
+            tags.iter()
+                .filter_map(|tag| tag.clone().extend::<EmojiObject, EmojiType>().ok()?)
+                .take(MAX_EMOJI_PER_OBJECT)
+                .filter_map(|emoji| Emoji::from_activity(conn, &emoji, author).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{medias::NewMedia, tests::db, users::tests as user_tests, Connection as Conn};
+    use assert_json_diff::assert_json_eq;
+    use diesel::Connection;
+    use plume_common::utils::md_to_html;
+    use serde_json::to_value;
+
+    pub(crate) fn fill_database(conn: &Conn) -> (Vec<User>, Vec<Emoji>) {
+        let users = user_tests::fill_database(conn);
+        let media = Media::insert(
+            conn,
+            NewMedia {
+                file_path: "static/media/blobcat.png".to_owned(),
+                alt_text: "A cat".to_owned(),
+                is_remote: false,
+                remote_url: None,
+                sensitive: false,
+                content_warning: None,
+                owner_id: users[0].id,
+            },
+        )
+        .unwrap();
+        let emoji = Emoji::new_local(conn, "blobcat", &media).unwrap();
+        (users, vec![emoji])
+    }
+
+    #[test]
+    fn shortcodes() {
+        assert!(Emoji::is_valid_shortcode("blob_cat2"));
+        assert!(!Emoji::is_valid_shortcode("a"));
+        assert!(!Emoji::is_valid_shortcode("blob cat"));
+        assert!(!Emoji::is_valid_shortcode("blob:cat"));
+    }
+
+    #[test]
+    fn render_local() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (_, emojis) = fill_database(&conn);
+            let (html, _, _) = md_to_html(
+                ":blobcat: and :unknown:, but not `:blobcat:`",
+                None,
+                true,
+                None,
+                Some(Emoji::get_emoji_processor(&conn)),
+            );
+            assert_eq!(
+                html,
+                format!(
+                    r#"<p dir="auto"><img class="emoji" src="{}" alt=":blobcat:" title=":blobcat:"> and :unknown:, but not <code>:blobcat:</code></p>
+"#,
+                    emojis[0].url(&conn)?
+                )
+            );
+
+            let used = Emoji::list_used_in(&conn, "Hello :blobcat: :unknown:")?;
+            assert_eq!(used.len(), 1);
+            assert_eq!(used[0].id, emojis[0].id);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn activity() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (users, emojis) = fill_database(&conn);
+            let mut act = emojis[0].to_activity(&conn)?;
+            let expected = json!({
+                "icon": {
+                    "type": "Image",
+                    "url": emojis[0].url(&conn)?,
+                },
+                "id": "https://plu.me/emojis/blobcat",
+                "name": ":blobcat:",
+                "type": "Emoji",
+            });
+            assert_json_eq!(to_value(&act)?, expected);
+
+            // The same emoji, as sent by another instance
+            let mut author = users[1].clone();
+            author.instance_id = Instance::get_remotes(&conn)?[0].id;
+            // Our own emoji, used by someone else, are left as they are
+            assert_eq!(Emoji::from_activity(&conn, &act, &author)?.id, emojis[0].id);
+            // Emoji can't be defined for another instance
+            act.set_id("https://remote.example/emojis/42".parse::<IriString>()?);
+            assert!(Emoji::from_activity(&conn, &act, &author).is_err());
+            // The image is used from the other instance until it is downloaded
+            let domain = author.get_instance(&conn)?.public_domain;
+            act.set_id(format!("https://{}/emojis/42", domain).parse::<IriString>()?);
+            let mut icon = Image::new();
+            icon.set_url("http://127.0.0.1/blobcat.png".parse::<IriString>()?);
+            act.set_icon(icon.into_any_base()?);
+            let remote = Emoji::from_activity(&conn, &act, &author)?;
+            assert_eq!(remote.url(&conn)?, "http://127.0.0.1/blobcat.png");
+            // Images on private addresses are not downloaded
+            assert!(remote.download(&conn, &author).is_err());
+
+            // Receiving it again doesn't create a new one
+            let again = Emoji::from_activity(&conn, &act, &author)?;
+            assert_eq!(again.id, remote.id);
+            assert_eq!(again.media_id, remote.media_id);
+
+            let rendered = Emoji::render(&conn, r#"<p title=":blobcat:">:blobcat:</p>"#, &[again]);
+            assert!(rendered.starts_with(r#"<p title=":blobcat:"><img class="emoji""#));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn from_tags() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (users, _) = fill_database(&conn);
+            let mut author = users[1].clone();
+            author.instance_id = Instance::get_remotes(&conn)?[0].id;
+            let domain = author.get_instance(&conn)?.public_domain;
+
+            let tags = (0..MAX_EMOJI_PER_OBJECT + 5)
+                .map(|i| -> Result<AnyBase> {
+                    let mut icon = Image::new();
+                    icon.set_url(
+                        format!("https://{}/emoji{}.png", domain, i).parse::<IriString>()?,
+                    );
+                    let mut emoji = EmojiObject::new();
+                    emoji.set_id(format!("https://{}/emojis/{}", domain, i).parse::<IriString>()?);
+                    emoji.set_name(format!(":emoji{}:", i));
+                    emoji.set_icon(icon.into_any_base()?);
+                    Ok(AnyBase::from_extended(emoji)?)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let emojis = Emoji::from_tags(&conn, Some(&OneOrMany::from_many(tags)), &author);
+            assert_eq!(emojis.len(), MAX_EMOJI_PER_OBJECT);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,580 @@
+//! Builds EPUB books from posts, to read them offline.
+//!
+//! A book has a chapter for each of its posts, with their rendered content, the local
+//! media they use, their authors and their license. It can be made of a single post,
+//! of the posts of a blog, or of the parts of a series.
+
+use crate::{
+    blogs::Blog,
+    export::media_file_name,
+    import::rewrite_html_images,
+    instance::Instance,
+    medias::{Media, MediaCategory},
+    posts::Post,
+    series::Series,
+    users::User,
+    Connection, Error, Result, CONFIG,
+};
+use chrono::NaiveDateTime;
+use plume_common::utils::escape;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Seek, Write},
+};
+use tracing::warn;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+/// The stylesheet of books, also used for the printable versions of posts.
+pub const STYLESHEET: &str = include_str!("print.css");
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// The most media data a book can contain. The media that don't fit are linked to instead.
+const MAX_MEDIA_SIZE: usize = 50 * 1024 * 1024;
+
+/// Elements that can't have content, and that must be closed in XHTML.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "br", "col", "embed", "hr", "img", "input", "source", "track", "wbr",
+];
+
+pub struct Book {
+    pub title: String,
+    pub description: String,
+    /// The URL of what the book was made from, that identifies it.
+    pub identifier: String,
+    pub cover: Option<Media>,
+    pub posts: Vec<Post>,
+}
+
+impl Book {
+    pub fn for_post(conn: &Connection, post: Post) -> Book {
+        Book {
+            title: post.title.clone(),
+            description: post.subtitle.clone(),
+            identifier: post.ap_url.clone(),
+            cover: post.cover_id.and_then(|id| Media::get(conn, id).ok()),
+            posts: vec![post],
+        }
+    }
+
+    /// `posts` are the posts of the blog to include, that are sorted from the oldest one.
+    pub fn for_blog(conn: &Connection, blog: &Blog, mut posts: Vec<Post>) -> Book {
+        posts.sort_by_key(|post| post.creation_date);
+        Book {
+            title: blog.title.clone(),
+            description: blog.summary.clone(),
+            identifier: blog.ap_url.clone(),
+            cover: blog
+                .banner_id
+                .or(blog.icon_id)
+                .and_then(|id| Media::get(conn, id).ok()),
+            posts,
+        }
+    }
+
+    /// `posts` are the parts of the series to include, in order.
+    pub fn for_series(conn: &Connection, series: &Series, posts: Vec<Post>) -> Result<Book> {
+        let blog = Blog::get(conn, series.blog_id)?;
+        Ok(Book {
+            title: series.title.clone(),
+            description: series.description.clone(),
+            identifier: series.ap_url.clone(),
+            cover: blog.banner_id.and_then(|id| Media::get(conn, id).ok()),
+            posts,
+        })
+    }
+
+    /// The language of the first post that has one, or "und" if it is unknown.
+    pub fn language(&self) -> &str {
+        self.posts
+            .iter()
+            .find_map(|post| post.language.as_deref())
+            .unwrap_or("und")
+    }
+
+    /// Writes the book to `writer`, and gives it back.
+    pub fn write<W: Write + Seek>(&self, conn: &Connection, writer: W) -> Result<W> {
+        if self.posts.is_empty() {
+            return Err(Error::NotFound);
+        }
+        let mut zip = ZipWriter::new(writer);
+        // the MIME type must be the first file, and not be compressed
+        zip.start_file(
+            "mimetype",
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        zip.write_all(b"application/epub+zip")?;
+        append(&mut zip, "META-INF/container.xml", CONTAINER.as_bytes())?;
+        append(&mut zip, "OEBPS/style.css", STYLESHEET.as_bytes())?;
+
+        let mut authors: Vec<Vec<User>> = vec![];
+        let mut creators: Vec<String> = vec![];
+        let mut licenses: Vec<&str> = vec![];
+        // the relative URLs of the local media used in posts
+        let mut media_urls = HashSet::new();
+        for post in &self.posts {
+            let post_authors = post.get_authors(conn)?;
+            for author in &post_authors {
+                if !creators.contains(&author.display_name) {
+                    creators.push(author.display_name.clone());
+                }
+            }
+            if !licenses.contains(&post.license.as_str()) {
+                licenses.push(&post.license);
+            }
+            authors.push(post_authors);
+            rewrite_html_images(post.content.get(), |url, _| {
+                if let Some(start) = url.find("static/media/") {
+                    media_urls.insert(url[start..].to_owned());
+                }
+                None
+            });
+        }
+
+        let cover = self
+            .cover
+            .as_ref()
+            .filter(|cover| cover.category() == MediaCategory::Image);
+        let author_ids = authors.iter().flatten().map(|a| a.id).collect::<Vec<_>>();
+        let media_urls = media_urls.into_iter().collect::<Vec<_>>();
+        let medias = cover
+            .cloned()
+            .into_iter()
+            .chain(Media::find_by_relative_urls(
+                conn,
+                &media_urls,
+                &author_ids,
+            )?);
+        // the media added to the book with their content, by relative URL
+        let mut included_medias = HashMap::new();
+        let mut size = 0;
+        for media in medias {
+            let url = match media.relative_url() {
+                Some(url) if !included_medias.contains_key(&url) => url,
+                _ => continue,
+            };
+            match media.read_file() {
+                Ok(data) if size + data.len() <= MAX_MEDIA_SIZE => {
+                    size += data.len();
+                    included_medias.insert(url, (media, data));
+                }
+                Ok(_) => warn!("Media {} doesn't fit in a book anymore", media.id),
+                Err(e) => warn!("Couldn't add media {} to a book: {:?}", media.id, e),
+            }
+        }
+
+        // file name and manifest properties of each chapter
+        let mut chapters = vec![];
+        for (i, (post, post_authors)) in self.posts.iter().zip(&authors).enumerate() {
+            let content = rewrite_html_images(post.content.get(), |url, _| {
+                let key = url.find("static/media/").map(|start| &url[start..])?;
+                let (media, _) = included_medias.get(key)?;
+                Some(format!("media/{}", media_file_name(media)))
+            });
+            let content = to_xhtml(&absolute_links(&content));
+            let mut properties = vec![];
+            if content.contains("<math") {
+                properties.push("mathml");
+            }
+            if content.contains("src=\"http") {
+                properties.push("remote-resources");
+            }
+            let file = format!("chapter-{}.xhtml", i + 1);
+            append(
+                &mut zip,
+                &format!("OEBPS/{}", file),
+                chapter(post, post_authors, &content).as_bytes(),
+            )?;
+            chapters.push((file, properties.join(" ")));
+        }
+
+        let mut manifest = vec![];
+        for (media, data) in included_medias.values() {
+            let file = format!("media/{}", media_file_name(media));
+            append(&mut zip, &format!("OEBPS/{}", file), data)?;
+            let properties = if cover.map(|c| c.id) == Some(media.id) {
+                r#" properties="cover-image""#
+            } else {
+                ""
+            };
+            manifest.push(format!(
+                r#"<item id="media-{}" href="{}" media-type="{}"{}/>"#,
+                media.id,
+                escape(&file),
+                media_type(&file),
+                properties
+            ));
+        }
+
+        append(&mut zip, "OEBPS/nav.xhtml", self.nav().as_bytes())?;
+        let mut metadata = vec![
+            format!(
+                r#"<dc:identifier id="id">{}</dc:identifier>"#,
+                escape(&self.identifier)
+            ),
+            format!("<dc:title>{}</dc:title>", escape(&self.title)),
+            format!("<dc:language>{}</dc:language>", escape(self.language())),
+        ];
+        for creator in creators {
+            metadata.push(format!("<dc:creator>{}</dc:creator>", escape(&creator)));
+        }
+        if !self.description.is_empty() {
+            metadata.push(format!(
+                "<dc:description>{}</dc:description>",
+                escape(&self.description)
+            ));
+        }
+        if let [license] = licenses.as_slice() {
+            if !license.is_empty() {
+                metadata.push(format!("<dc:rights>{}</dc:rights>", escape(license)));
+            }
+        }
+        if let Ok(instance) = Instance::get_local() {
+            metadata.push(format!(
+                "<dc:publisher>{}</dc:publisher>",
+                escape(&instance.name)
+            ));
+        }
+        let dates = self.posts.iter().map(|post| post.creation_date);
+        if let Some(published) = dates.clone().min() {
+            metadata.push(format!("<dc:date>{}</dc:date>", date(published)));
+        }
+        if let Some(modified) = dates.max() {
+            metadata.push(format!(
+                r#"<meta property="dcterms:modified">{}</meta>"#,
+                date(modified)
+            ));
+        }
+        let mut spine = vec![];
+        for (i, (file, properties)) in chapters.iter().enumerate() {
+            let properties = if properties.is_empty() {
+                String::new()
+            } else {
+                format!(r#" properties="{}""#, properties)
+            };
+            manifest.push(format!(
+                r#"<item id="chapter-{}" href="{}" media-type="application/xhtml+xml"{}/>"#,
+                i + 1,
+                file,
+                properties
+            ));
+            spine.push(format!(r#"<itemref idref="chapter-{}"/>"#, i + 1));
+        }
+        let package = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id" xml:lang="{lang}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    {metadata}
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="style" href="style.css" media-type="text/css"/>
+    {manifest}
+  </manifest>
+  <spine>
+    {spine}
+  </spine>
+</package>
+"#,
+            lang = escape(self.language()),
+            metadata = metadata.join("\n    "),
+            manifest = manifest.join("\n    "),
+            spine = spine.join("\n    "),
+        );
+        append(&mut zip, "OEBPS/content.opf", package.as_bytes())?;
+
+        Ok(zip.finish()?)
+    }
+
+    /// The table of contents of the book.
+    fn nav(&self) -> String {
+        let entries = self
+            .posts
+            .iter()
+            .enumerate()
+            .map(|(i, post)| {
+                format!(
+                    r#"<li><a href="chapter-{}.xhtml">{}</a></li>"#,
+                    i + 1,
+                    escape(&post.title)
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+<title>{title}</title>
+<link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+<nav epub:type="toc" id="toc">
+<h1>{title}</h1>
+<ol>
+{entries}
+</ol>
+</nav>
+</body>
+</html>
+"#,
+            title = escape(&self.title),
+            entries = entries.join("\n"),
+        )
+    }
+}
+
+fn chapter(post: &Post, authors: &[User], content: &str) -> String {
+    let lang = escape(post.language.as_deref().unwrap_or("und")).to_string();
+    let subtitle = if post.subtitle.is_empty() {
+        String::new()
+    } else {
+        format!("<h2>{}</h2>\n", escape(&post.subtitle))
+    };
+    let warning = match post.content_warning {
+        Some(ref warning) if post.sensitive => {
+            format!(r#"<p class="content-warning">{}</p>"#, escape(warning)) + "\n"
+        }
+        _ => String::new(),
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{lang}" xml:lang="{lang}">
+<head>
+<title>{title}</title>
+<link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+<article>
+<header>
+<h1>{title}</h1>
+{subtitle}<p class="byline">{authors} — {date}</p>
+</header>
+{warning}{content}
+<footer>
+<p class="license">{license}</p>
+<p class="source"><a href="{url}">{url}</a></p>
+</footer>
+</article>
+</body>
+</html>
+"#,
+        lang = lang,
+        title = escape(&post.title),
+        subtitle = subtitle,
+        authors = escape(
+            &authors
+                .iter()
+                .map(|a| a.display_name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        date = post.creation_date.format("%Y-%m-%d"),
+        warning = warning,
+        content = content,
+        license = escape(&post.license),
+        url = escape(&post.ap_url),
+    )
+}
+
+/// Links to this instance are relative in posts, but not in books.
+fn absolute_links(html: &str) -> String {
+    html.replace("href=\"/", &format!("href=\"https://{}/", CONFIG.base_url))
+        .replace("src=\"/", &format!("src=\"https://{}/", CONFIG.base_url))
+}
+
+/// Turns the HTML produced by the sanitizer into XHTML, that EPUB readers expect.
+fn to_xhtml(html: &str) -> String {
+    // the only named entity the sanitizer outputs that XML doesn't know
+    let html = html.replace("&nbsp;", "&#160;");
+    let mut res = String::with_capacity(html.len());
+    let mut rest = html.as_str();
+    while let Some(start) = rest.find('<') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        // attributes may contain a >
+        let mut quote = None;
+        let end = rest.char_indices().skip(1).find(|&(_, c)| match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+                false
+            }
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                false
+            }
+            None => c == '>',
+        });
+        let end = match end {
+            Some((end, _)) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        let name = tag[1..]
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        res.push_str(tag);
+        if name == "math" && !tag.contains("xmlns") {
+            res.push_str(r#" xmlns="http://www.w3.org/1998/Math/MathML""#);
+        }
+        if VOID_ELEMENTS.contains(&name.as_str()) && !tag.ends_with('/') {
+            res.push_str(" /");
+        }
+        res.push('>');
+        rest = &rest[end + 1..];
+    }
+    res.push_str(rest);
+    res
+}
+
+fn media_type(file: &str) -> &'static str {
+    match &*file
+        .rsplit_once('.')
+        .map(|x| x.1)
+        .unwrap_or("")
+        .to_lowercase()
+    {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn date(date: NaiveDateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn append<W: Write + Seek>(zip: &mut ZipWriter<W>, path: &str, data: &[u8]) -> Result<()> {
+    zip.start_file(path, FileOptions::default())?;
+    zip.write_all(data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        inbox::tests::fill_database, medias::NewMedia, safe_string::SafeString, tests::db,
+    };
+    use diesel::Connection;
+    use std::{
+        env::{current_dir, set_current_dir},
+        fs,
+        io::{Cursor, Read},
+    };
+    use zip::ZipArchive;
+
+    #[test]
+    fn xhtml() {
+        assert_eq!(
+            to_xhtml(r#"<p>a&nbsp;b<br><img src="a.png" alt="1 > 0"></p><math><mi>x</mi></math>"#),
+            r#"<p>a&#160;b<br /><img src="a.png" alt="1 > 0" /></p><math xmlns="http://www.w3.org/1998/Math/MathML"><mi>x</mi></math>"#
+        );
+        assert_eq!(to_xhtml("<hr/>"), "<hr/>");
+    }
+
+    #[test]
+    fn post_book() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, _) = fill_database(conn);
+            let book = Book::for_post(conn, posts[0].clone());
+            let data = book.write(conn, Cursor::new(vec![]))?.into_inner();
+
+            let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+            assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+            let mut package = String::new();
+            archive
+                .by_name("OEBPS/content.opf")
+                .unwrap()
+                .read_to_string(&mut package)?;
+            assert!(package.contains("<dc:title>Testing</dc:title>"));
+            assert!(package.contains("<dc:rights>WTFPL</dc:rights>"));
+            assert!(package.contains(&format!(
+                "<dc:creator>{}</dc:creator>",
+                users[0].display_name
+            )));
+            let mut chapter = String::new();
+            archive
+                .by_name("OEBPS/chapter-1.xhtml")
+                .unwrap()
+                .read_to_string(&mut chapter)?;
+            assert!(chapter.contains("<h2>Bye</h2>"));
+            assert!(chapter.contains("Hello"));
+
+            let empty = Book::for_blog(conn, &Blog::get(conn, posts[0].blog_id)?, vec![]);
+            assert!(empty.write(conn, Cursor::new(vec![])).is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn only_used_media() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, _) = fill_database(conn);
+            let mut wd = current_dir()?;
+            while wd.pop() {
+                if wd.join(".git").exists() {
+                    set_current_dir(wd)?;
+                    break;
+                }
+            }
+            let mut medias = vec![];
+            for file in &["static/media/book-used.png", "static/media/book-unused.png"] {
+                fs::write(file, b"A cat")?;
+                medias.push(Media::insert(
+                    conn,
+                    NewMedia {
+                        file_path: file.to_string(),
+                        alt_text: String::new(),
+                        is_remote: false,
+                        remote_url: None,
+                        sensitive: false,
+                        content_warning: None,
+                        owner_id: users[0].id,
+                    },
+                )?);
+            }
+            let mut post = posts[0].clone();
+            post.content = SafeString::new(r#"<p><img src="/static/media/book-used.png"></p>"#);
+            let data = Book::for_post(conn, post)
+                .write(conn, Cursor::new(vec![]))?
+                .into_inner();
+
+            let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+            let media_files = (0..archive.len())
+                .map(|i| archive.by_index(i).unwrap().name().to_owned())
+                .filter(|name| name.starts_with("OEBPS/media/"))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                media_files,
+                vec![format!("OEBPS/media/{}", media_file_name(&medias[0]))]
+            );
+
+            for media in medias {
+                media.delete(conn)?;
+            }
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,299 @@
+//! Exports a blog to an archive that can be read back by the importer, on this
+//! instance or on another one.
+//!
+//! The archive is a gzipped tarball containing:
+//!
+//! - `blog.json`, the ActivityPub representation of the blog;
+//! - `posts/<slug>.md`, the source of each post, drafts included, with a YAML front matter;
+//! - `media/`, the files of the media these posts use;
+//! - `comments.json`, the comments of each post, by slug.
+
+use crate::{
+    blogs::Blog,
+    comments::Comment,
+    import::{rewrite_html_images, rewrite_markdown_images},
+    medias::Media,
+    posts::Post,
+    schema::posts,
+    series::Series,
+    tags::Tag,
+    Connection, Result,
+};
+use chrono::{NaiveDateTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use flate2::{write::GzEncoder, Compression};
+use plume_common::activity_pub::context;
+use serde_json::json;
+use std::{collections::HashMap, io::Write};
+use tar::{Builder, Header};
+use tracing::warn;
+
+/// Writes the archive of `blog` to `writer`, and gives it back.
+pub fn export_blog<W: Write>(conn: &Connection, blog: &Blog, writer: W) -> Result<W> {
+    let mut archive = Builder::new(GzEncoder::new(writer, Compression::default()));
+    let now = Utc::now().timestamp() as u64;
+
+    let mut blog_json = serde_json::to_value(blog.to_activity(conn)?)?;
+    blog_json["@context"] = context();
+    append(
+        &mut archive,
+        "blog.json",
+        &serde_json::to_vec_pretty(&blog_json)?,
+        now,
+    )?;
+
+    // the local media of the authors, by relative URL, to find the ones used in HTML
+    let mut local_medias = HashMap::new();
+    for author in blog.list_authors(conn)? {
+        for media in Media::for_user(conn, author.id)? {
+            if let Some(url) = media.relative_url() {
+                local_medias.insert(url, media);
+            }
+        }
+    }
+    let mut used_medias = HashMap::new();
+
+    let posts = posts::table
+        .filter(posts::blog_id.eq(blog.id))
+        .order(posts::creation_date.asc())
+        .load::<Post>(conn)?;
+    let mut comments = serde_json::Map::new();
+    for post in posts {
+        let mut media_link = |media: Media| {
+            if media.is_remote {
+                media.remote_url
+            } else {
+                let link = format!("../media/{}", media_file_name(&media));
+                used_medias.insert(media.id, media);
+                Some(link)
+            }
+        };
+        let source = rewrite_markdown_images(&post.source, |url, _| {
+            url.parse::<i32>()
+                .ok()
+                .and_then(|id| Media::get(conn, id).ok())
+                .and_then(&mut media_link)
+        });
+        let source = rewrite_html_images(&source, |url, _| {
+            let key = url.find("static/media/").map(|start| &url[start..])?;
+            local_medias.get(key).cloned().and_then(&mut media_link)
+        });
+        let cover = post
+            .cover_id
+            .and_then(|id| Media::get(conn, id).ok())
+            .and_then(&mut media_link);
+
+        let mut front_matter = vec![
+            ("title", quote(&post.title)),
+            ("subtitle", quote(&post.subtitle)),
+            ("slug", quote(&post.slug)),
+            ("date", date(post.creation_date)),
+            (
+                "authors",
+                serde_json::to_string(
+                    &post
+                        .get_authors(conn)?
+                        .into_iter()
+                        .map(|a| a.fqn)
+                        .collect::<Vec<_>>(),
+                )?,
+            ),
+            (
+                "tags",
+                serde_json::to_string(
+                    &Tag::for_post(conn, post.id)?
+                        .into_iter()
+                        .filter(|t| !t.is_hashtag)
+                        .map(|t| t.tag)
+                        .collect::<Vec<_>>(),
+                )?,
+            ),
+            ("license", quote(&post.license)),
+            ("draft", (!post.published).to_string()),
+            ("visibility", quote(post.visibility().as_str())),
+        ];
+        if let Some(ref language) = post.language {
+            front_matter.push(("lang", quote(language)));
+        }
+        if let Some(ref canonical_url) = post.canonical_url {
+            front_matter.push(("canonical_url", quote(canonical_url)));
+        }
+        if post.sensitive {
+            front_matter.push(("sensitive", "true".to_owned()));
+        }
+        if let Some(ref warning) = post.content_warning {
+            front_matter.push(("content_warning", quote(warning)));
+        }
+        if let Some(publish_at) = post.publish_at {
+            front_matter.push(("publish_at", date(publish_at)));
+        }
+        if let Some(series) = Series::find_for_post(conn, post.id)? {
+            front_matter.push(("series", quote(&series.title)));
+        }
+        if let Some(cover) = cover {
+            front_matter.push(("cover", quote(&cover)));
+        }
+        front_matter.push(("ap_url", quote(&post.ap_url)));
+
+        let mut file = String::from("---\n");
+        for (key, value) in front_matter {
+            file.push_str(&format!("{}: {}\n", key, value));
+        }
+        file.push_str("---\n\n");
+        file.push_str(&source);
+        file.push('\n');
+        append(
+            &mut archive,
+            &format!("posts/{}.md", post.slug.replace(&['/', '\\'][..], "-")),
+            file.as_bytes(),
+            post.creation_date.timestamp() as u64,
+        )?;
+
+        let post_comments = Comment::list_by_post(conn, post.id)?
+            .into_iter()
+            .map(|comment| {
+                let author = comment.get_author(conn)?;
+                Ok(json!({
+                    "id": comment.id,
+                    "in_response_to": comment.in_response_to_id,
+                    "author": author.fqn,
+                    "author_url": author.ap_url,
+                    "date": date(comment.creation_date),
+                    "ap_url": comment.ap_url,
+                    "sensitive": comment.sensitive,
+                    "spoiler_text": comment.spoiler_text,
+                    "content": comment.content.get(),
+                    "public": comment.public_visibility,
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if !post_comments.is_empty() {
+            comments.insert(post.slug, post_comments.into());
+        }
+    }
+    append(
+        &mut archive,
+        "comments.json",
+        &serde_json::to_vec_pretty(&comments)?,
+        now,
+    )?;
+
+    for media in used_medias.values() {
+        match media.read_file() {
+            Ok(data) => append(
+                &mut archive,
+                &format!("media/{}", media_file_name(media)),
+                &data,
+                now,
+            )?,
+            Err(e) => warn!("Couldn't export media {}: {:?}", media.id, e),
+        }
+    }
+
+    Ok(archive.into_inner()?.finish()?)
+}
+
+/// Media are prefixed with their id, as mirrored remote files may have the same name.
+pub(crate) fn media_file_name(media: &Media) -> String {
+    let name = media
+        .file_path
+        .rsplit(&['/', '\\'][..])
+        .next()
+        .unwrap_or_default();
+    format!("{}-{}", media.id, name)
+}
+
+/// JSON strings are valid YAML strings, and can contain any character.
+fn quote(s: &str) -> String {
+    serde_json::Value::from(s).to_string()
+}
+
+fn date(date: NaiveDateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn append<W: Write>(archive: &mut Builder<W>, path: &str, data: &[u8], mtime: u64) -> Result<()> {
+    let mut header = Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    archive.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        import::{import_posts, ImportFormat, ImportOptions},
+        inbox::tests::fill_database,
+        posts::Visibility,
+        tags::NewTag,
+        tests::db,
+        Error,
+    };
+    use chrono::Timelike;
+    use diesel::Connection;
+
+    #[test]
+    fn export_and_import() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, blogs) = fill_database(conn);
+            Tag::insert(
+                conn,
+                NewTag {
+                    tag: "Archive".to_owned(),
+                    is_hashtag: false,
+                    post_id: posts[0].id,
+                },
+            )?;
+            Series::set_for_post(conn, &posts[0], "Exports")?;
+            let mut post = posts[0].clone();
+            post.visibility = Visibility::Unlisted.into();
+            post.content_warning = Some("Spoilers".to_owned());
+            post.sensitive = true;
+            post.update(conn)?;
+
+            let data = export_blog(conn, &blogs[0], vec![])?;
+            let exported = ImportFormat::Archive.parse(&data)?;
+            assert_eq!(exported.len(), 1);
+            let exported = &exported[0];
+            assert_eq!(exported.slug.as_deref(), Some("testing"));
+            assert_eq!(exported.title, "Testing");
+            assert_eq!(exported.subtitle, "Bye");
+            assert_eq!(exported.source, "Hello");
+            assert_eq!(exported.tags, vec!["Archive".to_owned()]);
+            assert_eq!(exported.series.as_deref(), Some("Exports"));
+            assert_eq!(exported.visibility, Visibility::Unlisted);
+            assert_eq!(exported.content_warning.as_deref(), Some("Spoilers"));
+            assert!(exported.published);
+
+            let options = ImportOptions {
+                default_license: "CC-0".to_owned(),
+                site_url: None,
+                index: false,
+                federate: false,
+            };
+            let report =
+                import_posts(conn, &blogs[1], &users[1], vec![exported.clone()], &options)?;
+            assert_eq!(report.imported.len(), 1);
+            let imported = &report.imported[0];
+            assert_eq!(imported.blog_id, blogs[1].id);
+            assert_eq!(imported.slug, posts[0].slug);
+            assert_eq!(imported.license, posts[0].license);
+            assert_eq!(
+                imported.creation_date,
+                posts[0].creation_date.with_nanosecond(0).unwrap()
+            );
+            assert_eq!(imported.visibility(), Visibility::Unlisted);
+            assert!(imported.sensitive);
+            assert_eq!(
+                Series::find_for_post(conn, imported.id)?.map(|s| s.title),
+                Some("Exports".to_owned())
+            );
+
+            Ok(())
+        });
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
-    ap_url, instance::Instance, notifications::*, schema::follows, users::User, Connection, Error,
-    Result, CONFIG,
+    ap_url, deliveries::Delivery, instance::Instance, notifications::*, schema::follows,
+    user_blocks::UserBlock, users::User, Connection, Error, Result, CONFIG,
 };
 use activitystreams::{
     activity::{Accept, ActorAndObjectRef, Follow as FollowAct, Undo},
@@ -10,7 +10,6 @@ use activitystreams::{
 };
 use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl, SaveChangesDsl};
 use plume_common::activity_pub::{
-    broadcast,
     inbox::{AsActor, AsObject, FromId},
     sign::Signer,
     Id, IntoId, PUBLIC_VISIBILITY,
@@ -69,7 +68,10 @@ impl Follow {
     }
 
     pub fn notify(&self, conn: &Connection) -> Result<()> {
-        if User::get(conn, self.following_id)?.is_local() {
+        let following = User::get(conn, self.following_id)?;
+        if following.is_local()
+            && !UserBlock::is_blocked(conn, &following, &User::get(conn, self.follower_id)?)?
+        {
             Notification::insert(
                 conn,
                 NewNotification {
@@ -92,6 +94,13 @@ impl Follow {
         from_id: i32,
         target_id: i32,
     ) -> Result<Follow> {
+        if UserBlock::is_blocked(
+            conn,
+            &User::get(conn, target_id)?,
+            &User::get(conn, from_id)?,
+        )? {
+            return Err(Error::Unauthorized);
+        }
         let res = Follow::insert(
             conn,
             NewFollow {
@@ -107,7 +116,7 @@ impl Follow {
         res.notify(conn)?;
 
         let accept = res.build_accept(from, target, follow)?;
-        broadcast(target, accept, vec![from.clone()], CONFIG.proxy().cloned());
+        Delivery::broadcast(conn, target, accept, vec![from.clone()])?;
         Ok(res)
     }
 
@@ -148,6 +157,37 @@ impl Follow {
 
         Ok(undo)
     }
+
+    /// Makes the local followers of `from` follow `to` instead, when `from` moved to `to`.
+    ///
+    /// A `Follow` is sent to `to` for each of them if it is a remote account. Followers that
+    /// blocked `to`, or that are blocked by it, keep following `from`.
+    pub fn move_followers(conn: &Connection, from: &User, to: &User) -> Result<()> {
+        for follower in from.get_followers(conn)? {
+            if !follower.is_local()
+                || UserBlock::is_blocked(conn, &follower, to)?
+                || UserBlock::is_blocked(conn, to, &follower)?
+            {
+                continue;
+            }
+            let mut follow = Follow::find(conn, follower.id, from.id)?;
+            if Follow::find(conn, follower.id, to.id).is_ok() {
+                diesel::delete(&follow).execute(conn)?;
+                continue;
+            }
+
+            // `to` must not receive a `Follow` that `from` already knows under the same id
+            follow.following_id = to.id;
+            follow.ap_url = ap_url(&format!(
+                "{}/follows/{}/move/{}",
+                CONFIG.base_url, follow.id, to.id
+            ));
+            let follow = follow.save_changes::<Follow>(conn)?;
+            follow.notify(conn)?;
+            Delivery::broadcast(conn, &follower, follow.to_activity(conn)?, vec![to.clone()])?;
+        }
+        Ok(())
+    }
 }
 
 impl AsObject<User, FollowAct, &Connection> for User {
@@ -0,0 +1,98 @@
+//! Reads the archives made by [`export_blog`](crate::export::export_blog).
+//!
+//! They are gzipped tarballs, with a Markdown file per post, and the media they use.
+
+use super::{markdown, normalize, ImportedPost};
+use crate::{Error, Result};
+use flate2::read::GzDecoder;
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tar::Archive;
+
+/// The most files an archive can contain.
+const MAX_ENTRIES: usize = 10_000;
+/// The biggest file an archive can contain, once decompressed.
+const MAX_ENTRY_SIZE: u64 = 50 * 1024 * 1024;
+/// The biggest an archive can be, once decompressed.
+const MAX_TOTAL_SIZE: u64 = 200 * 1024 * 1024;
+
+pub fn parse(data: &[u8]) -> Result<Vec<ImportedPost>> {
+    let mut archive = Archive::new(GzDecoder::new(data));
+    let mut posts = vec![];
+    let mut files = HashMap::new();
+    let mut total_size = 0;
+    for (i, entry) in archive.entries()?.enumerate() {
+        if i >= MAX_ENTRIES {
+            return Err(Error::InvalidValue);
+        }
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = normalize(&entry.path()?);
+        let mut content = vec![];
+        entry.take(MAX_ENTRY_SIZE + 1).read_to_end(&mut content)?;
+        total_size += content.len() as u64;
+        if content.len() as u64 > MAX_ENTRY_SIZE || total_size > MAX_TOTAL_SIZE {
+            return Err(Error::InvalidValue);
+        }
+        if path.extension().and_then(|ext| ext.to_str()) == Some("md") {
+            posts.push((path, content));
+        } else {
+            files.insert(path, content);
+        }
+    }
+    if posts.is_empty() && files.is_empty() {
+        return Err(Error::InvalidValue);
+    }
+    posts.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let files = Arc::new(files);
+    posts
+        .into_iter()
+        .map(|(path, content)| {
+            let mut post = markdown::parse(&String::from_utf8_lossy(&content))?;
+            post.base_dir = Some(
+                path.parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(PathBuf::new),
+            );
+            post.files = Some(files.clone());
+            Ok(post)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io;
+    use tar::{Builder, Header};
+
+    fn archive(files: impl Iterator<Item = (String, u64)>) -> Vec<u8> {
+        let mut builder = Builder::new(GzEncoder::new(vec![], Compression::fast()));
+        for (path, size) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(size);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, path, io::repeat(b'a').take(size))
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn limits() {
+        let big_file = std::iter::once(("media/1-big.png".to_owned(), MAX_ENTRY_SIZE + 1));
+        assert!(parse(&archive(big_file)).is_err());
+
+        let many_files = (0..=MAX_ENTRIES).map(|i| (format!("media/{}.png", i), 0));
+        assert!(parse(&archive(many_files)).is_err());
+    }
+}
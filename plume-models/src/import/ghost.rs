@@ -0,0 +1,200 @@
+//! Reads the JSON files produced by the Ghost exporter.
+
+use super::{parse_date, ImportedPost};
+use crate::{Error, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+
+fn string<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+/// Ghost ids are strings in recent versions, and numbers in older ones.
+fn id(value: &Value, key: &str) -> Option<String> {
+    match value.get(key)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Older exports don't have rendered HTML, but keep the Markdown in a mobiledoc card.
+fn mobiledoc_markdown(post: &Value) -> Option<String> {
+    let doc = serde_json::from_str::<Value>(string(post, "mobiledoc")?).ok()?;
+    let markdown = doc
+        .get("cards")?
+        .as_array()?
+        .iter()
+        .filter(|card| card.get(0).and_then(Value::as_str) == Some("markdown"))
+        .filter_map(|card| string(card.get(1)?, "markdown"))
+        .collect::<Vec<_>>();
+    if markdown.is_empty() {
+        None
+    } else {
+        Some(markdown.join("\n\n"))
+    }
+}
+
+pub fn parse(data: &str) -> Result<Vec<ImportedPost>> {
+    let export = serde_json::from_str::<Value>(data)?;
+    let db = match export.get("db") {
+        Some(Value::Array(dbs)) => dbs.get(0).ok_or(Error::InvalidValue)?,
+        Some(db) => db,
+        None => &export,
+    };
+    let data = db.get("data").ok_or(Error::InvalidValue)?;
+
+    let empty = vec![];
+    let tags = data
+        .get("tags")
+        .and_then(Value::as_array)
+        .unwrap_or(&empty)
+        .iter()
+        .filter_map(|tag| Some((id(tag, "id")?, string(tag, "name")?.to_owned())))
+        .collect::<HashMap<_, _>>();
+    let mut post_tags: HashMap<String, Vec<(i64, String)>> = HashMap::new();
+    for link in data
+        .get("posts_tags")
+        .and_then(Value::as_array)
+        .unwrap_or(&empty)
+    {
+        if let (Some(post), Some(tag)) = (
+            id(link, "post_id"),
+            id(link, "tag_id").and_then(|t| tags.get(&t)),
+        ) {
+            let order = link.get("sort_order").and_then(Value::as_i64).unwrap_or(0);
+            post_tags
+                .entry(post)
+                .or_default()
+                .push((order, tag.clone()));
+        }
+    }
+
+    let posts = data
+        .get("posts")
+        .and_then(Value::as_array)
+        .ok_or(Error::InvalidValue)?
+        .iter()
+        // pages are not articles, and can't be imported as such
+        .filter(|post| string(post, "type").unwrap_or("post") == "post")
+        .map(|post| {
+            let mut tags = id(post, "id")
+                .and_then(|id| post_tags.remove(&id))
+                .unwrap_or_default();
+            tags.sort();
+            ImportedPost {
+                title: string(post, "title").unwrap_or_default().to_owned(),
+                subtitle: string(post, "custom_excerpt")
+                    .unwrap_or_default()
+                    .to_owned(),
+                slug: string(post, "slug").map(ToOwned::to_owned),
+                source: string(post, "html")
+                    .map(ToOwned::to_owned)
+                    .or_else(|| mobiledoc_markdown(post))
+                    .or_else(|| string(post, "plaintext").map(ToOwned::to_owned))
+                    .unwrap_or_default(),
+                creation_date: string(post, "published_at")
+                    .or_else(|| string(post, "created_at"))
+                    .and_then(parse_date),
+                published: string(post, "status") == Some("published"),
+                tags: tags.into_iter().map(|(_, tag)| tag).collect(),
+                license: None,
+                cover: string(post, "feature_image").map(ToOwned::to_owned),
+                canonical_url: string(post, "canonical_url").map(ToOwned::to_owned),
+                base_dir: None,
+                ..ImportedPost::default()
+            }
+        })
+        .collect();
+    Ok(posts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const EXPORT: &str = r##"{
+        "db": [{
+            "meta": { "exported_on": 1560000000000, "version": "2.25.0" },
+            "data": {
+                "posts": [
+                    {
+                        "id": "5c0",
+                        "title": "Moving to Plume",
+                        "slug": "moving-to-plume",
+                        "html": "<p>Hello</p><img src=\"__GHOST_URL__/content/images/a.png\">",
+                        "feature_image": "__GHOST_URL__/content/images/cover.png",
+                        "custom_excerpt": "Why and how",
+                        "status": "published",
+                        "type": "post",
+                        "created_at": "2019-05-01T08:00:00.000Z",
+                        "published_at": "2019-05-02T09:30:00.000Z"
+                    },
+                    {
+                        "id": "5c1",
+                        "title": "About",
+                        "slug": "about",
+                        "html": "<p>Me</p>",
+                        "status": "published",
+                        "type": "page"
+                    },
+                    {
+                        "id": 12,
+                        "title": "Old draft",
+                        "slug": "old-draft",
+                        "html": null,
+                        "mobiledoc": "{\"version\":\"0.3.1\",\"cards\":[[\"markdown\",{\"markdown\":\"# Old\"}]]}",
+                        "status": "draft",
+                        "created_at": "2017-01-01T00:00:00.000Z"
+                    }
+                ],
+                "tags": [
+                    { "id": "t1", "name": "Meta" },
+                    { "id": "t2", "name": "Fediverse" }
+                ],
+                "posts_tags": [
+                    { "post_id": "5c0", "tag_id": "t2", "sort_order": 1 },
+                    { "post_id": "5c0", "tag_id": "t1", "sort_order": 0 }
+                ]
+            }
+        }]
+    }"##;
+
+    #[test]
+    fn parse_export() {
+        let posts = parse(EXPORT).unwrap();
+        assert_eq!(posts.len(), 2);
+
+        let post = &posts[0];
+        assert_eq!(post.title, "Moving to Plume");
+        assert_eq!(post.subtitle, "Why and how");
+        assert_eq!(post.slug.as_deref(), Some("moving-to-plume"));
+        assert!(post.source.starts_with("<p>Hello</p>"));
+        assert!(post.published);
+        assert_eq!(
+            post.creation_date,
+            NaiveDate::from_ymd_opt(2019, 5, 2).and_then(|d| d.and_hms_opt(9, 30, 0))
+        );
+        assert_eq!(post.tags, vec!["Meta".to_owned(), "Fediverse".to_owned()]);
+        assert_eq!(
+            post.cover.as_deref(),
+            Some("__GHOST_URL__/content/images/cover.png")
+        );
+
+        let draft = &posts[1];
+        assert_eq!(draft.source, "# Old");
+        assert!(!draft.published);
+        assert!(draft.tags.is_empty());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse("{}").is_err());
+        assert!(parse("<rss></rss>").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Reads Markdown files with a YAML front matter, as used by most static site generators.
+
+use super::{parse_date, ImportedPost};
+use crate::{Error, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Splits a file into its front matter and its body.
+fn split_front_matter(data: &str) -> (&str, &str) {
+    let data = data.trim_start_matches('\u{feff}');
+    let rest = match data
+        .strip_prefix("---\n")
+        .or_else(|| data.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return ("", data),
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" || line.trim_end() == "..." {
+            return (&rest[..offset], &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    ("", data)
+}
+
+fn string(front_matter: &Yaml, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .map(|key| &front_matter[*key])
+        .find_map(|value| match value {
+            Yaml::String(s) => Some(s.trim().to_owned()),
+            Yaml::Integer(i) => Some(i.to_string()),
+            Yaml::Real(r) => Some(r.clone()),
+            _ => None,
+        })
+        .filter(|s| !s.is_empty())
+}
+
+/// Tags can either be a list, or a comma separated string.
+fn list(front_matter: &Yaml, key: &str) -> Vec<String> {
+    match front_matter[key] {
+        Yaml::Array(ref items) => items
+            .iter()
+            .filter_map(Yaml::as_str)
+            .map(ToOwned::to_owned)
+            .collect(),
+        Yaml::String(ref tags) => tags.split(',').map(ToOwned::to_owned).collect(),
+        _ => vec![],
+    }
+    .into_iter()
+    .map(|tag| tag.trim().to_owned())
+    .filter(|tag| !tag.is_empty())
+    .collect()
+}
+
+/// Parses a single Markdown file.
+///
+/// Posts without a title in their front matter use their first heading, that is then
+/// removed from the body.
+pub fn parse(data: &str) -> Result<ImportedPost> {
+    let (front_matter, body) = split_front_matter(data);
+    let front_matter = YamlLoader::load_from_str(front_matter)?
+        .into_iter()
+        .next()
+        .unwrap_or(Yaml::Null);
+
+    let mut body = body.trim().to_owned();
+    let title = match string(&front_matter, &["title"]) {
+        Some(title) => title,
+        None => {
+            let heading = body
+                .lines()
+                .next()
+                .and_then(|l| l.strip_prefix("# "))
+                .map(|h| h.trim().to_owned())
+                .ok_or(Error::InvalidValue)?;
+            body = body
+                .split_once('\n')
+                .map(|(_, rest)| rest.trim().to_owned())
+                .unwrap_or_default();
+            heading
+        }
+    };
+
+    let mut tags = list(&front_matter, "tags");
+    tags.extend(list(&front_matter, "categories"));
+    let published = match (
+        front_matter["draft"].as_bool(),
+        front_matter["published"].as_bool(),
+    ) {
+        (Some(draft), _) => !draft,
+        (None, Some(published)) => published,
+        (None, None) => true,
+    };
+    Ok(ImportedPost {
+        title,
+        subtitle: string(&front_matter, &["subtitle", "description", "summary"])
+            .unwrap_or_default(),
+        slug: string(&front_matter, &["slug"]),
+        source: body,
+        creation_date: string(&front_matter, &["date"])
+            .as_deref()
+            .and_then(parse_date),
+        published,
+        publish_at: string(&front_matter, &["publish_at"])
+            .as_deref()
+            .and_then(parse_date),
+        visibility: string(&front_matter, &["visibility"])
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default(),
+        sensitive: front_matter["sensitive"].as_bool().unwrap_or(false),
+        content_warning: string(&front_matter, &["content_warning", "cw"]),
+        tags,
+        license: string(&front_matter, &["license"]),
+        series: string(&front_matter, &["series"]),
+        cover: string(&front_matter, &["cover", "image", "cover_image"]),
+        canonical_url: string(&front_matter, &["canonical_url", "canonical"]),
+        language: string(&front_matter, &["lang", "language"]),
+        base_dir: None,
+        files: None,
+    })
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+        .unwrap_or(false)
+}
+
+/// Reads a Markdown file, or all the Markdown files of a directory and its sub-directories.
+///
+/// Images with a relative path are looked for next to the file that uses them.
+pub fn read(path: &Path) -> Result<Vec<ImportedPost>> {
+    let mut files = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|path| path.is_file() && is_markdown(path))
+            .collect::<Vec<PathBuf>>()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|file| {
+            let mut post = parse(&fs::read_to_string(&file)?)?;
+            post.base_dir = file.parent().map(Path::to_path_buf);
+            Ok(post)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::posts::Visibility;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn parse_front_matter() {
+        let post = parse(
+            r#"---
+title: "Hello: world"
+subtitle: A first post
+slug: hello
+date: 2020-02-03 04:05
+tags:
+  - Rust
+  - Fediverse
+license: CC-0
+draft: false
+visibility: unlisted
+series: Getting started
+cover: images/cover.jpg
+---
+
+Some text with ![a cat](images/cat.png).
+"#,
+        )
+        .unwrap();
+        assert_eq!(post.title, "Hello: world");
+        assert_eq!(post.subtitle, "A first post");
+        assert_eq!(post.slug.as_deref(), Some("hello"));
+        assert_eq!(
+            post.creation_date,
+            NaiveDate::from_ymd_opt(2020, 2, 3).and_then(|d| d.and_hms_opt(4, 5, 0))
+        );
+        assert_eq!(post.tags, vec!["Rust".to_owned(), "Fediverse".to_owned()]);
+        assert_eq!(post.license.as_deref(), Some("CC-0"));
+        assert!(post.published);
+        assert_eq!(post.visibility, Visibility::Unlisted);
+        assert_eq!(post.series.as_deref(), Some("Getting started"));
+        assert_eq!(post.cover.as_deref(), Some("images/cover.jpg"));
+        assert_eq!(post.source, "Some text with ![a cat](images/cat.png).");
+    }
+
+    #[test]
+    fn parse_without_front_matter() {
+        let post = parse("# A title\n\nThe body\n---\n").unwrap();
+        assert_eq!(post.title, "A title");
+        assert_eq!(post.source, "The body\n---");
+        assert!(post.tags.is_empty());
+        assert!(post.published);
+
+        let draft = parse("---\ntags: a, b\ndraft: true\n---\n# Draft\n").unwrap();
+        assert_eq!(draft.title, "Draft");
+        assert_eq!(draft.tags, vec!["a".to_owned(), "b".to_owned()]);
+        assert!(!draft.published);
+
+        assert!(parse("No title").is_err());
+    }
+}
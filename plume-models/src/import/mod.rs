@@ -0,0 +1,806 @@
+//! Importing posts exported from other blogging platforms.
+
+use crate::{
+    blogs::Blog,
+    deliveries::Delivery,
+    emojis::Emoji,
+    instance::Instance,
+    medias::Media,
+    mentions::Mention,
+    post_authors::{NewPostAuthor, PostAuthor},
+    post_revisions::PostRevision,
+    posts::{is_valid_language, NewPost, Post, Visibility},
+    safe_string::SafeString,
+    series::Series,
+    tags::{NewTag, Tag},
+    timeline::{Kind, Timeline},
+    users::User,
+    Connection, Error, Result, CONFIG,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use diesel::Connection as _;
+use plume_common::utils::md_to_html;
+use reqwest::{
+    blocking::ClientBuilder,
+    header::{CONTENT_TYPE, LOCATION},
+    redirect::Policy,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Read,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tracing::warn;
+use url::{Host, Url};
+
+mod archive;
+mod ghost;
+mod markdown;
+mod wordpress;
+
+/// The formats posts can be imported from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// A WXR file, as produced by the WordPress exporter
+    WordPress,
+    /// A JSON file, as produced by the Ghost exporter
+    Ghost,
+    /// Markdown files with a YAML front matter
+    Markdown,
+    /// An archive made by [`export_blog`](crate::export::export_blog)
+    Archive,
+}
+
+impl FromStr for ImportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "wordpress" | "wxr" => Ok(ImportFormat::WordPress),
+            "ghost" => Ok(ImportFormat::Ghost),
+            "markdown" | "md" => Ok(ImportFormat::Markdown),
+            "archive" | "plume" => Ok(ImportFormat::Archive),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+}
+
+impl ImportFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImportFormat::WordPress => "wordpress",
+            ImportFormat::Ghost => "ghost",
+            ImportFormat::Markdown => "markdown",
+            ImportFormat::Archive => "archive",
+        }
+    }
+
+    /// Reads the posts contained in an uploaded file.
+    pub fn parse(self, data: &[u8]) -> Result<Vec<ImportedPost>> {
+        match self {
+            ImportFormat::WordPress => wordpress::parse(&String::from_utf8_lossy(data)),
+            ImportFormat::Ghost => ghost::parse(&String::from_utf8_lossy(data)),
+            ImportFormat::Markdown => {
+                markdown::parse(&String::from_utf8_lossy(data)).map(|post| vec![post])
+            }
+            ImportFormat::Archive => archive::parse(data),
+        }
+    }
+
+    /// Reads the posts of an export stored at `path`.
+    ///
+    /// For Markdown, `path` can also be a directory, that will be searched recursively.
+    pub fn read(self, path: &Path) -> Result<Vec<ImportedPost>> {
+        match self {
+            ImportFormat::Markdown => markdown::read(path),
+            ImportFormat::Archive => archive::parse(&fs::read(path)?),
+            _ => {
+                let mut posts = self.parse(&fs::read(path)?)?;
+                for post in posts.iter_mut() {
+                    post.base_dir = path.parent().map(Path::to_path_buf);
+                }
+                Ok(posts)
+            }
+        }
+    }
+}
+
+/// The files of an archive, by path, shared by all the posts it contains.
+pub type ArchiveFiles = Arc<HashMap<PathBuf, Vec<u8>>>;
+
+/// A post read from an export, before it is saved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportedPost {
+    pub title: String,
+    pub subtitle: String,
+    /// The slug the post had on its previous platform, if any
+    pub slug: Option<String>,
+    /// The content of the post, in Markdown or in HTML
+    pub source: String,
+    pub creation_date: Option<NaiveDateTime>,
+    pub published: bool,
+    /// When a draft should be published
+    pub publish_at: Option<NaiveDateTime>,
+    pub visibility: Visibility,
+    pub sensitive: bool,
+    pub content_warning: Option<String>,
+    pub tags: Vec<String>,
+    pub license: Option<String>,
+    /// The title of the series the post is part of
+    pub series: Option<String>,
+    /// The URL or path of the cover image
+    pub cover: Option<String>,
+    /// The address of the post on the site it was first published on
+    pub canonical_url: Option<String>,
+    /// The language code of the post, like "en"
+    pub language: Option<String>,
+    /// The directory relative image paths should be resolved against
+    pub base_dir: Option<PathBuf>,
+    /// For posts read from an archive, the other files it contains
+    pub files: Option<ArchiveFiles>,
+}
+
+pub struct ImportOptions {
+    /// The license of the posts that don't specify one
+    pub default_license: String,
+    /// The address of the imported site, to resolve relative image URLs
+    pub site_url: Option<String>,
+    /// Add the published posts to the search index
+    pub index: bool,
+    /// Send the published posts to the followers of their author, and add them to
+    /// timelines, like newly written ones
+    pub federate: bool,
+}
+
+#[derive(Default)]
+pub struct ImportReport {
+    pub imported: Vec<Post>,
+    /// The titles of the posts that were not imported, with the reason why
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Parses the dates found in exports, that are either RFC 3339 timestamps or
+/// "YYYY-MM-DD HH:MM:SS" in UTC, with optional seconds or time.
+pub(crate) fn parse_date(date: &str) -> Option<NaiveDateTime> {
+    let date = date.trim();
+    DateTime::parse_from_rfc3339(date)
+        .map(|d| d.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M"))
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// Saves `posts` in `blog`, written by `author`.
+///
+/// Posts whose slug is already used in the blog are skipped, so that an interrupted
+/// import can be run again.
+pub fn import_posts(
+    conn: &Connection,
+    blog: &Blog,
+    author: &User,
+    posts: Vec<ImportedPost>,
+    options: &ImportOptions,
+) -> Result<ImportReport> {
+    if !author.is_author_in(conn, blog)? {
+        return Err(Error::Unauthorized);
+    }
+
+    let mut medias = HashMap::new();
+    let mut report = ImportReport::default();
+    for imported in posts {
+        let title = imported.title.clone();
+        let result =
+            conn.transaction(|| import_post(conn, blog, author, imported, options, &mut medias));
+        match result {
+            Ok(Some(post)) => {
+                if post.published {
+                    if options.index {
+                        post.publish_published();
+                    }
+                    if options.federate {
+                        federate(conn, author, &post).unwrap_or_else(|e| {
+                            warn!("Failed to federate {}: {:?}", post.ap_url, e)
+                        });
+                    }
+                }
+                report.imported.push(post);
+            }
+            Ok(None) => report
+                .skipped
+                .push((title, "a post with the same slug already exists".to_owned())),
+            Err(e) => {
+                warn!("Failed to import {}: {:?}", title, e);
+                // the media saved with this post don't exist anymore
+                medias.retain(|_, media: &mut Media| Media::get(conn, media.id).is_ok());
+                report.skipped.push((title, format!("{:?}", e)));
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn import_post(
+    conn: &Connection,
+    blog: &Blog,
+    author: &User,
+    imported: ImportedPost,
+    options: &ImportOptions,
+    medias: &mut HashMap<String, Media>,
+) -> Result<Option<Post>> {
+    let title = imported.title.trim();
+    if title.is_empty() {
+        return Err(Error::InvalidValue);
+    }
+    // keep the original slug when Plume can route it
+    let slug = imported
+        .slug
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && *s != "new" && !s.contains('/'))
+        .unwrap_or_else(|| Post::slug(title))
+        .to_owned();
+    if Post::find_by_slug(conn, &slug, blog.id).is_ok() {
+        return Ok(None);
+    }
+
+    let base_dir = imported.base_dir.as_deref();
+    let files = imported.files.as_ref();
+    let mut import_media = |url: &str, alt: &str| -> Option<Media> {
+        if let Some(media) = medias.get(url) {
+            return Some(media.clone());
+        }
+        let source = media_source(url, base_dir, files, options)?;
+        let media = save_media(conn, author, source, alt)?;
+        medias.insert(url.to_owned(), media.clone());
+        Some(media)
+    };
+    let source = rewrite_markdown_images(&imported.source, |url, alt| {
+        import_media(url, alt).map(|m| m.id.to_string())
+    });
+    let source = rewrite_html_images(&source, |url, alt| {
+        import_media(url, alt).and_then(|m| m.url().ok())
+    });
+    let cover_id = imported
+        .cover
+        .as_deref()
+        .and_then(|cover| import_media(cover, ""))
+        .map(|m| m.id);
+
+    let (content, mentions, hashtags) = md_to_html(
+        &source,
+        Some(&Instance::get_local()?.public_domain),
+        false,
+        Some(Media::get_media_processor(conn, vec![author])),
+        Some(Emoji::get_emoji_processor(conn)),
+    );
+    let post = Post::insert_unindexed(
+        conn,
+        NewPost {
+            blog_id: blog.id,
+            slug,
+            title: title.to_owned(),
+            content: SafeString::new(&content),
+            published: imported.published,
+            license: imported
+                .license
+                .unwrap_or_else(|| options.default_license.clone()),
+            ap_url: String::new(),
+            creation_date: imported.creation_date,
+            subtitle: imported.subtitle.trim().to_owned(),
+            source,
+            cover_id,
+            publish_at: imported.publish_at.filter(|_| !imported.published),
+            visibility: imported.visibility.into(),
+            sensitive: imported.sensitive || imported.content_warning.is_some(),
+            content_warning: imported.content_warning,
+            canonical_url: imported.canonical_url,
+            language: imported.language.filter(|l| is_valid_language(l)),
+            translation_of: None,
+        },
+    )?;
+    PostAuthor::insert(
+        conn,
+        NewPostAuthor {
+            post_id: post.id,
+            author_id: author.id,
+        },
+    )?;
+    PostRevision::snapshot(conn, &post, Some(author))?;
+    if let Some(series) = imported.series {
+        Series::set_for_post(conn, &post, &series)?;
+    }
+
+    let tags = imported
+        .tags
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<HashSet<_>>();
+    for tag in tags {
+        Tag::insert(
+            conn,
+            NewTag {
+                tag: tag.to_owned(),
+                is_hashtag: false,
+                post_id: post.id,
+            },
+        )?;
+    }
+    for hashtag in hashtags {
+        Tag::insert(
+            conn,
+            NewTag {
+                tag: hashtag,
+                is_hashtag: true,
+                post_id: post.id,
+            },
+        )?;
+    }
+
+    // mentions notify people, so they are only saved for posts that are federated
+    if post.published && options.federate {
+        for m in mentions {
+            Mention::from_activity(
+                conn,
+                &Mention::build_activity(conn, &m)?,
+                post.id,
+                true,
+                true,
+            )?;
+        }
+    }
+    Ok(Some(post))
+}
+
+fn federate(conn: &Connection, author: &User, post: &Post) -> Result<()> {
+    Timeline::add_to_all_timelines(conn, post, Kind::Original)?;
+    let act = post.create_activity(conn)?;
+    let dest = post.get_receivers(conn)?;
+    Delivery::broadcast(conn, author, act, dest)?;
+    Ok(())
+}
+
+/// Where the file of an imported image can be found.
+enum MediaSource {
+    Remote(String),
+    File(PathBuf),
+    /// The content of a file of an archive, and its name
+    Data(Vec<u8>, String),
+}
+
+/// Resolves the `..` of a path inside an archive.
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => res.push(c),
+            Component::ParentDir => {
+                res.pop();
+            }
+            _ => {}
+        }
+    }
+    res
+}
+
+fn media_source(
+    url: &str,
+    base_dir: Option<&Path>,
+    files: Option<&ArchiveFiles>,
+    options: &ImportOptions,
+) -> Option<MediaSource> {
+    let url = url.trim().replace("&amp;", "&");
+    // data URIs are kept as they are, and numbers are already Plume media
+    if url.is_empty() || url.starts_with("data:") || url.parse::<i32>().is_ok() {
+        return None;
+    }
+
+    let site_url = options.site_url.as_deref().map(|s| s.trim_end_matches('/'));
+    let url = match site_url {
+        Some(site_url) => url.replace("__GHOST_URL__", site_url),
+        None => url,
+    };
+    if url.starts_with("https://") || url.starts_with("http://") {
+        if url.starts_with(&format!("https://{}/", CONFIG.base_url)) {
+            None
+        } else {
+            Some(MediaSource::Remote(url))
+        }
+    } else if let Some(url) = url.strip_prefix("//") {
+        Some(MediaSource::Remote(format!("https://{}", url)))
+    } else if url.starts_with('/') {
+        site_url.map(|site_url| MediaSource::Remote(format!("{}{}", site_url, url)))
+    } else if let Some(files) = files {
+        let path = normalize(&base_dir.unwrap_or_else(|| Path::new("")).join(&url));
+        files
+            .get(&path)
+            .map(|data| MediaSource::Data(data.clone(), url))
+    } else {
+        base_dir
+            .map(|dir| dir.join(&url))
+            .filter(|path| path.is_file())
+            .map(MediaSource::File)
+    }
+}
+
+/// The biggest image that can be downloaded.
+const MAX_MEDIA_SIZE: u64 = 20 * 1024 * 1024;
+
+/// The most redirections followed to download an image.
+const MAX_REDIRECTIONS: usize = 5;
+
+/// Whether `ip` can be reached from the Internet.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // shared address space, used by carrier-grade NATs
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            match ip.to_ipv4() {
+                Some(ipv4) if !ip.is_loopback() => is_public_ip(IpAddr::V4(ipv4)),
+                _ => {
+                    !(ip.is_loopback()
+                        || ip.is_unspecified()
+                        || ip.is_multicast()
+                        // unique local addresses
+                        || (first & 0xfe00) == 0xfc00
+                        // link-local addresses
+                        || (first & 0xffc0) == 0xfe80)
+                }
+            }
+        }
+    }
+}
+
+/// The addresses of the host of `url`, if they can all be reached from the Internet.
+///
+/// The URLs come from imported exports or from other instances, so they must
+/// not make the instance reach its private network.
+fn public_addresses(url: &Url) -> Option<Vec<SocketAddr>> {
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return None;
+    }
+    let port = url.port_or_known_default()?;
+    let addresses: Vec<SocketAddr> = match url.host()? {
+        Host::Ipv4(ip) => vec![SocketAddr::new(IpAddr::V4(ip), port)],
+        Host::Ipv6(ip) => vec![SocketAddr::new(IpAddr::V6(ip), port)],
+        Host::Domain(domain) => (domain, port).to_socket_addrs().ok()?.collect(),
+    };
+    if !addresses.is_empty() && addresses.iter().all(|address| is_public_ip(address.ip())) {
+        Some(addresses)
+    } else {
+        None
+    }
+}
+
+/// Downloads a remote image, if it is on a public address, and returns it with its content type.
+///
+/// The connection is made to the address that was checked, and not through the configured
+/// proxy, as resolving the domain again could give a private address.
+pub(crate) fn download(url: &str) -> Result<(Vec<u8>, Option<String>)> {
+    let mut url = Url::parse(url)?;
+    // redirections are followed by hand, to check each of them
+    for _ in 0..=MAX_REDIRECTIONS {
+        let addresses = public_addresses(&url).ok_or(Error::Unauthorized)?;
+        let mut client = ClientBuilder::new()
+            .no_proxy()
+            .redirect(Policy::none())
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(30));
+        if let Some(Host::Domain(domain)) = url.host() {
+            client = client.resolve(domain, addresses[0]);
+        }
+        let res = client.build()?.get(url.clone()).send()?;
+
+        if res.status().is_redirection() {
+            let location = res
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or(Error::NotFound)?;
+            url = url.join(location)?;
+            continue;
+        }
+        if !res.status().is_success() {
+            return Err(Error::NotFound);
+        }
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .map(str::to_owned);
+        let mut data = vec![];
+        res.take(MAX_MEDIA_SIZE + 1).read_to_end(&mut data)?;
+        if data.len() as u64 > MAX_MEDIA_SIZE {
+            return Err(Error::InvalidValue);
+        }
+        return Ok((data, content_type));
+    }
+    Err(Error::NotFound)
+}
+
+/// Copies an image used by an imported post to the media of `author`.
+///
+/// Remote images that can't be downloaded are saved as remote media.
+fn save_media(conn: &Connection, author: &User, source: MediaSource, alt: &str) -> Option<Media> {
+    let (data, name) = match source {
+        MediaSource::Remote(url) => {
+            let data = download(&url);
+            match data {
+                Ok((data, _)) => {
+                    let name = url
+                        .split(&['?', '#'][..])
+                        .next()
+                        .unwrap_or_default()
+                        .to_owned();
+                    (data, name)
+                }
+                Err(e) => {
+                    warn!("Failed to download {}: {:?}", url, e);
+                    return Media::save_remote(conn, url, author).ok();
+                }
+            }
+        }
+        MediaSource::File(path) => match fs::read(&path) {
+            Ok(data) => (data, path.to_string_lossy().into_owned()),
+            Err(e) => {
+                warn!("Failed to read {}: {:?}", path.display(), e);
+                return None;
+            }
+        },
+        MediaSource::Data(data, name) => (data, name),
+    };
+
+    Media::save_file(conn, &data, &name, alt.to_owned(), None, author)
+        .map_err(|e| warn!("Failed to save {}: {:?}", name, e))
+        .ok()
+}
+
+/// Replaces the target of each `![alt](target)` image of `md` with the result of `f`,
+/// that is given the target and the alternative text. `None` keeps the image as it is.
+pub(crate) fn rewrite_markdown_images(
+    md: &str,
+    mut f: impl FnMut(&str, &str) -> Option<String>,
+) -> String {
+    let mut res = String::with_capacity(md.len());
+    let mut rest = md;
+    while let Some(start) = rest.find("![") {
+        let after = &rest[start + 2..];
+        let image = after.find("](").and_then(|alt_end| {
+            let target_end = alt_end + 2 + after[alt_end + 2..].find(')')?;
+            Some((alt_end, target_end))
+        });
+        match image {
+            Some((alt_end, target_end)) if !after[..alt_end].contains('\n') => {
+                let alt = &after[..alt_end];
+                let target = after[alt_end + 2..target_end].trim();
+                // the target may be followed by a title
+                let (url, title) =
+                    target.split_at(target.find(char::is_whitespace).unwrap_or(target.len()));
+                let url = url.trim_start_matches('<').trim_end_matches('>');
+
+                res.push_str(&rest[..start]);
+                match f(url, alt) {
+                    Some(new) => res.push_str(&format!("![{}]({}{})", alt, new, title)),
+                    None => res.push_str(&rest[start..start + 2 + target_end + 1]),
+                }
+                rest = &rest[start + 2 + target_end + 1..];
+            }
+            _ => {
+                res.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Finds the value of an attribute in an HTML tag, and returns its position.
+fn html_attribute(tag: &str, name: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    while let Some(pos) = tag[offset..].find(name) {
+        let start = offset + pos;
+        offset = start + name.len();
+        let preceded_by_space = tag[..start].ends_with(char::is_whitespace);
+        let value = tag[offset..].trim_start();
+        if !preceded_by_space || !value.starts_with('=') {
+            continue;
+        }
+        let value = value[1..].trim_start();
+        let value_start = tag.len() - value.len();
+        let quote = value.chars().next()?;
+        return if quote == '"' || quote == '\'' {
+            let len = value[1..].find(quote)?;
+            Some((value_start + 1, value_start + 1 + len))
+        } else {
+            let len = value
+                .find(|c: char| c.is_whitespace() || c == '>')
+                .unwrap_or(value.len());
+            Some((value_start, value_start + len))
+        };
+    }
+    None
+}
+
+/// Replaces the `src` of each `<img>` of `html` with the result of `f`, that is given the
+/// current `src` and the alternative text. `None` keeps the image as it is.
+pub(crate) fn rewrite_html_images(
+    html: &str,
+    mut f: impl FnMut(&str, &str) -> Option<String>,
+) -> String {
+    let mut res = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<img") {
+        let end = match rest[start..].find('>') {
+            Some(len) => start + len + 1,
+            None => break,
+        };
+        res.push_str(&rest[..start]);
+        let tag = &rest[start..end];
+        let new_src = html_attribute(tag, "src").and_then(|(src_start, src_end)| {
+            let alt = html_attribute(tag, "alt")
+                .map(|(alt_start, alt_end)| &tag[alt_start..alt_end])
+                .unwrap_or_default();
+            f(&tag[src_start..src_end], alt).map(|src| (src_start, src_end, src))
+        });
+        match new_src {
+            Some((src_start, src_end, src)) => {
+                let tag = format!("{}{}{}", &tag[..src_start], src, &tag[src_end..]);
+                // the other sizes of the image are still on the old site
+                match html_attribute(&tag, "srcset") {
+                    Some((_, value_end)) => {
+                        let srcset_start = tag[..value_end].rfind("srcset").unwrap_or(value_end);
+                        let srcset_end = if tag[value_end..].starts_with(&['"', '\''][..]) {
+                            value_end + 1
+                        } else {
+                            value_end
+                        };
+                        res.push_str(tag[..srcset_start].trim_end());
+                        res.push_str(&tag[srcset_end..]);
+                    }
+                    None => res.push_str(&tag),
+                }
+            }
+            None => res.push_str(tag),
+        }
+        rest = &rest[end..];
+    }
+    res.push_str(rest);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inbox::tests::fill_database, tests::db};
+
+    #[test]
+    fn dates() {
+        let date = NaiveDate::from_ymd_opt(2021, 6, 7).and_then(|d| d.and_hms_opt(8, 9, 10));
+        assert_eq!(parse_date("2021-06-07T08:09:10Z"), date);
+        assert_eq!(parse_date("2021-06-07T10:09:10+02:00"), date);
+        assert_eq!(parse_date("2021-06-07 08:09:10"), date);
+        assert_eq!(
+            parse_date("2021-06-07"),
+            NaiveDate::from_ymd_opt(2021, 6, 7).and_then(|d| d.and_hms_opt(0, 0, 0))
+        );
+        assert_eq!(parse_date("0000-00-00 00:00:00"), None);
+    }
+
+    #[test]
+    fn rewrite_images() {
+        assert_eq!(
+            rewrite_markdown_images(
+                "A ![cat](img/cat.png \"Title\") and ![dog](<dog.png>), [not an image](a.png)",
+                |url, alt| Some(format!("{}-{}", alt, url))
+            ),
+            "A ![cat](cat-img/cat.png \"Title\") and ![dog](dog-dog.png), [not an image](a.png)"
+        );
+        assert_eq!(
+            rewrite_markdown_images("![unfinished", |_, _| None),
+            "![unfinished"
+        );
+
+        assert_eq!(
+            rewrite_html_images(
+                r#"<p><img class="big" src="https://old.example/cat.png" alt='A cat' srcset="https://old.example/cat-2x.png 2x"/></p><img src=x.png>"#,
+                |url, alt| if url.starts_with("https") {
+                    Some(format!("/{}", alt))
+                } else {
+                    None
+                }
+            ),
+            r#"<p><img class="big" src="/A cat" alt='A cat'/></p><img src=x.png>"#
+        );
+    }
+
+    #[test]
+    fn private_urls() {
+        for url in [
+            "http://127.0.0.1/cat.png",
+            "http://localhost:8080/cat.png",
+            "https://10.1.2.3/cat.png",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/cat.png",
+            "http://[fd12::1]/cat.png",
+            "http://[::ffff:192.168.0.1]/cat.png",
+            "file:///etc/passwd",
+        ] {
+            assert!(
+                public_addresses(&Url::parse(url).unwrap()).is_none(),
+                "{}",
+                url
+            );
+        }
+        assert_eq!(
+            public_addresses(&Url::parse("https://93.184.216.34/cat.png").unwrap()),
+            Some(vec!["93.184.216.34:443".parse().unwrap()])
+        );
+    }
+
+    #[test]
+    fn import() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (_, users, blogs) = fill_database(conn);
+            let posts = ImportFormat::Markdown.parse(
+                b"---\ntitle: Imported\nslug: imported-post\ndate: 2015-10-21\ntags: [Time, Travel]\n---\n\nHello ![local](a.png)",
+            )?;
+            let options = ImportOptions {
+                default_license: "CC-BY-SA".to_owned(),
+                site_url: None,
+                index: false,
+                federate: false,
+            };
+
+            let report = import_posts(conn, &blogs[0], &users[0], posts.clone(), &options)?;
+            assert_eq!(report.imported.len(), 1);
+            let post = &report.imported[0];
+            assert_eq!(post.slug, "imported-post");
+            assert_eq!(post.title, "Imported");
+            assert_eq!(post.license, "CC-BY-SA");
+            assert!(post.published);
+            assert_eq!(
+                post.creation_date,
+                NaiveDate::from_ymd_opt(2015, 10, 21).and_then(|d| d.and_hms_opt(0, 0, 0)).unwrap()
+            );
+            assert_eq!(post.get_authors(conn)?[0].id, users[0].id);
+            let mut tags = Tag::for_post(conn, post.id)?
+                .into_iter()
+                .map(|t| t.tag)
+                .collect::<Vec<_>>();
+            tags.sort();
+            assert_eq!(tags, vec!["Time".to_owned(), "Travel".to_owned()]);
+
+            // importing again doesn't create duplicates
+            let report = import_posts(conn, &blogs[0], &users[0], posts, &options)?;
+            assert!(report.imported.is_empty());
+            assert_eq!(report.skipped.len(), 1);
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,223 @@
+//! Reads the WXR files produced by the WordPress exporter.
+
+use super::{parse_date, ImportedPost};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use xml::{name::OwnedName, reader::XmlEvent, EventReader};
+
+/// The fields of an `<item>`, before we know if it is a post or an attachment.
+#[derive(Default)]
+struct Item {
+    fields: HashMap<String, String>,
+    tags: Vec<String>,
+    meta: HashMap<String, String>,
+}
+
+impl Item {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty())
+    }
+}
+
+fn qualified_name(name: &OwnedName) -> String {
+    match name.prefix {
+        Some(ref prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+pub fn parse(data: &str) -> Result<Vec<ImportedPost>> {
+    let mut items = vec![];
+    let mut current: Option<Item> = None;
+    // the element we are reading the text of, with the attributes we need
+    let mut element: Option<(String, Option<String>)> = None;
+    let mut text = String::new();
+    let mut meta_key = String::new();
+
+    for event in EventReader::from_str(data) {
+        match event? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let name = qualified_name(&name);
+                if name == "item" {
+                    current = Some(Item::default());
+                } else if current.is_some() {
+                    let domain = attributes
+                        .into_iter()
+                        .find(|a| a.name.local_name == "domain")
+                        .map(|a| a.value);
+                    element = Some((name, domain));
+                    text.clear();
+                }
+            }
+            XmlEvent::Characters(chars) | XmlEvent::CData(chars) | XmlEvent::Whitespace(chars) => {
+                if element.is_some() {
+                    text.push_str(&chars);
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                let name = qualified_name(&name);
+                if name == "item" {
+                    items.extend(current.take());
+                    continue;
+                }
+                let item = match current {
+                    Some(ref mut item) => item,
+                    None => continue,
+                };
+                match element.take() {
+                    Some((ref el, ref domain)) if *el == name => match name.as_str() {
+                        // categories are turned into tags too, as Plume has nothing else to sort posts
+                        "category"
+                            if domain.as_deref() == Some("post_tag")
+                                || domain.as_deref() == Some("category") =>
+                        {
+                            let tag = text.trim();
+                            if !tag.is_empty() && tag != "Uncategorized" {
+                                item.tags.push(tag.to_owned());
+                            }
+                        }
+                        "wp:meta_key" => meta_key = text.trim().to_owned(),
+                        "wp:meta_value" => {
+                            item.meta.insert(meta_key.clone(), text.trim().to_owned());
+                        }
+                        _ => {
+                            item.fields.insert(name.clone(), text.clone());
+                        }
+                    },
+                    _ => {}
+                }
+                text.clear();
+            }
+            _ => {}
+        }
+    }
+
+    let attachments = items
+        .iter()
+        .filter(|i| i.field("wp:post_type") == Some("attachment"))
+        .filter_map(|i| {
+            Some((
+                i.field("wp:post_id")?.to_owned(),
+                i.field("wp:attachment_url")?.to_owned(),
+            ))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let posts = items
+        .iter()
+        .filter(|i| i.field("wp:post_type") == Some("post"))
+        .filter(|i| i.field("wp:status") != Some("trash"))
+        .map(|item| ImportedPost {
+            title: item.field("title").unwrap_or_default().to_owned(),
+            subtitle: item.field("excerpt:encoded").unwrap_or_default().to_owned(),
+            slug: item.field("wp:post_name").map(ToOwned::to_owned),
+            source: item.field("content:encoded").unwrap_or_default().to_owned(),
+            creation_date: item
+                .field("wp:post_date_gmt")
+                .and_then(parse_date)
+                .or_else(|| item.field("wp:post_date").and_then(parse_date)),
+            published: item.field("wp:status") == Some("publish"),
+            tags: item.tags.clone(),
+            license: None,
+            cover: item
+                .meta
+                .get("_thumbnail_id")
+                .and_then(|id| attachments.get(id))
+                .cloned(),
+            base_dir: None,
+            ..ImportedPost::default()
+        })
+        .collect::<Vec<_>>();
+
+    // not a WordPress export
+    if items.is_empty() {
+        return Err(Error::InvalidValue);
+    }
+    Ok(posts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const EXPORT: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0"
+    xmlns:excerpt="http://wordpress.org/export/1.2/excerpt/"
+    xmlns:content="http://purl.org/rss/1.0/modules/content/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:wp="http://wordpress.org/export/1.2/">
+<channel>
+    <title>My blog</title>
+    <item>
+        <title>Hello world</title>
+        <dc:creator><![CDATA[admin]]></dc:creator>
+        <content:encoded><![CDATA[Some <em>text</em>.
+
+<img src="https://old.example/wp-content/uploads/cat.png" alt="A cat" />]]></content:encoded>
+        <excerpt:encoded><![CDATA[A first post]]></excerpt:encoded>
+        <wp:post_id>1</wp:post_id>
+        <wp:post_date><![CDATA[2019-03-04 11:12:13]]></wp:post_date>
+        <wp:post_date_gmt><![CDATA[2019-03-04 10:12:13]]></wp:post_date_gmt>
+        <wp:post_name><![CDATA[hello-world]]></wp:post_name>
+        <wp:status><![CDATA[publish]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+        <category domain="category" nicename="news"><![CDATA[News]]></category>
+        <category domain="post_tag" nicename="plume"><![CDATA[Plume]]></category>
+        <wp:postmeta>
+            <wp:meta_key><![CDATA[_thumbnail_id]]></wp:meta_key>
+            <wp:meta_value><![CDATA[2]]></wp:meta_value>
+        </wp:postmeta>
+    </item>
+    <item>
+        <title>cover</title>
+        <wp:post_id>2</wp:post_id>
+        <wp:post_type><![CDATA[attachment]]></wp:post_type>
+        <wp:attachment_url><![CDATA[https://old.example/wp-content/uploads/cover.jpg]]></wp:attachment_url>
+    </item>
+    <item>
+        <title>Not finished</title>
+        <content:encoded><![CDATA[Soon]]></content:encoded>
+        <wp:post_id>3</wp:post_id>
+        <wp:post_date_gmt><![CDATA[0000-00-00 00:00:00]]></wp:post_date_gmt>
+        <wp:post_name><![CDATA[]]></wp:post_name>
+        <wp:status><![CDATA[draft]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+    </item>
+</channel>
+</rss>"#;
+
+    #[test]
+    fn parse_export() {
+        let posts = parse(EXPORT).unwrap();
+        assert_eq!(posts.len(), 2);
+
+        let post = &posts[0];
+        assert_eq!(post.title, "Hello world");
+        assert_eq!(post.subtitle, "A first post");
+        assert_eq!(post.slug.as_deref(), Some("hello-world"));
+        assert!(post
+            .source
+            .contains(r#"<img src="https://old.example/wp-content/uploads/cat.png""#));
+        assert_eq!(
+            post.creation_date,
+            NaiveDate::from_ymd_opt(2019, 3, 4).and_then(|d| d.and_hms_opt(10, 12, 13))
+        );
+        assert!(post.published);
+        assert_eq!(post.tags, vec!["News".to_owned(), "Plume".to_owned()]);
+        assert_eq!(
+            post.cover.as_deref(),
+            Some("https://old.example/wp-content/uploads/cover.jpg")
+        );
+
+        let draft = &posts[1];
+        assert!(!draft.published);
+        assert_eq!(draft.slug, None);
+        assert_eq!(draft.creation_date, None);
+    }
+}
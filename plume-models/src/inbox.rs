@@ -1,10 +1,12 @@
-use activitystreams::activity::{Announce, Create, Delete, Follow, Like, Undo, Update};
+use activitystreams::activity::{Announce, Block, Create, Delete, Follow, Like, Undo, Update};
 
 use crate::{
     comments::Comment,
     follows, likes,
     posts::{Post, PostUpdate},
+    reports::Report,
     reshares::Reshare,
+    user_blocks::UserBlock,
     users::User,
     Connection, Error, CONFIG,
 };
@@ -28,6 +30,7 @@ pub enum InboxResult {
     Liked(likes::Like),
     Other,
     Post(Post),
+    Reported(Report),
     Reshared(Reshare),
 }
 
@@ -42,10 +45,21 @@ impl_into_inbox_result! {
     follows::Follow => Followed,
     likes::Like => Liked,
     Post => Post,
+    Report => Reported,
     Reshare => Reshared
 }
 
 pub fn inbox(conn: &Connection, act: serde_json::Value) -> Result<InboxResult, Error> {
+    // The objects of a Flag are often a list (the reported account and some of its posts),
+    // that `Inbox` can't handle
+    if act["type"] == "Flag" {
+        return Report::from_flag(conn, &act).map(InboxResult::from);
+    }
+    // `Inbox` handlers don't get the target of the activity, that tells where the account moved
+    if act["type"] == "Move" {
+        return User::from_move(conn, &act).map(InboxResult::from);
+    }
+
     Inbox::handle(conn, act)
         .with::<User, Announce, Post>(CONFIG.proxy())
         .with::<User, Create, Comment>(CONFIG.proxy())
@@ -59,6 +73,8 @@ pub fn inbox(conn: &Connection, act: serde_json::Value) -> Result<InboxResult, E
         .with::<User, Undo, follows::Follow>(CONFIG.proxy())
         .with::<User, Undo, likes::Like>(CONFIG.proxy())
         .with::<User, Update, PostUpdate>(CONFIG.proxy())
+        .with::<User, Block, User>(CONFIG.proxy())
+        .with::<User, Undo, UserBlock>(CONFIG.proxy())
         .done()
 }
 
@@ -96,6 +112,13 @@ pub(crate) mod tests {
                 subtitle: "Bye".to_string(),
                 source: "Hello".to_string(),
                 cover_id: None,
+                publish_at: None,
+                visibility: Visibility::Public.into(),
+                sensitive: false,
+                content_warning: None,
+                canonical_url: None,
+                language: None,
+                translation_of: None,
             },
         )
         .unwrap();
@@ -522,6 +545,32 @@ pub(crate) mod tests {
         });
     }
 
+    #[test]
+    fn flag_from_instance_actor() {
+        let conn = db();
+        conn.test_transaction::<_, (), _>(|| {
+            let (posts, users, _) = fill_database(&conn);
+
+            let act = json!({
+                "id": "https://remote.example/flag/1",
+                "actor": "https://remote.example/actor",
+                "object": [users[0].ap_url, posts[0].ap_url],
+                "content": "Spam",
+                "type": "Flag",
+            });
+            match super::inbox(&conn, act).unwrap() {
+                InboxResult::Reported(r) => {
+                    assert_eq!(r.reporter_id, None);
+                    assert_eq!(r.user_id, users[0].id);
+                    assert_eq!(r.post_id, Some(posts[0].id));
+                    assert_eq!(r.reason, "Spam".to_owned());
+                }
+                _ => panic!("Unexpected result"),
+            }
+            Ok(())
+        });
+    }
+
     #[test]
     fn like() {
         let conn = db();
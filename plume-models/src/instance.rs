@@ -1,16 +1,21 @@
 use crate::{
     ap_url,
+    emojis::Emoji,
     medias::Media,
     safe_string::SafeString,
     schema::{instances, users},
     users::{NewUser, Role, User},
-    Connection, Error, Result,
+    Connection, Error, Result, CONFIG,
 };
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{self, result::Error::NotFound, ExpressionMethods, QueryDsl, RunQueryDsl};
 use once_cell::sync::OnceCell;
-use plume_common::utils::{iri_percent_encode_seg, md_to_html};
-use std::sync::RwLock;
+use plume_common::{
+    activity_pub::request,
+    utils::{iri_percent_encode_seg, md_to_html},
+};
+use std::{collections::HashSet, sync::RwLock};
+use tracing::{info, warn};
 
 #[derive(Clone, Identifiable, Queryable)]
 pub struct Instance {
@@ -40,6 +45,14 @@ pub struct NewInstance {
     pub default_license: String,
     pub long_description_html: String,
     pub short_description_html: String,
+    /// The last time a delivery to this instance succeeded
+    pub last_success_at: Option<NaiveDateTime>,
+    /// When deliveries to this instance started to fail, if they still do
+    pub failing_since: Option<NaiveDateTime>,
+    /// The number of deliveries that failed since then
+    pub failures: i32,
+    /// Nothing is sent to unreachable instances, until they answer again
+    pub unreachable: bool,
 }
 
 lazy_static! {
@@ -151,6 +164,81 @@ impl Instance {
         Ok(false)
     }
 
+    /// Records whether a delivery to this instance succeeded.
+    ///
+    /// When deliveries keep failing for longer than `CONFIG.unreachable_after`,
+    /// the instance is marked as unreachable.
+    pub fn record_delivery(&self, conn: &Connection, success: bool) -> Result<Instance> {
+        let now = Utc::now().naive_utc();
+        if success {
+            diesel::update(self)
+                .set((
+                    instances::last_success_at.eq(Some(now)),
+                    instances::failing_since.eq(None::<NaiveDateTime>),
+                    instances::failures.eq(0),
+                    instances::unreachable.eq(false),
+                ))
+                .execute(conn)?;
+        } else {
+            let failing_since = self.failing_since.unwrap_or(now);
+            let unreachable = self.unreachable || now - failing_since >= CONFIG.unreachable_after;
+            if unreachable && !self.unreachable {
+                info!("{} is now considered unreachable", self.public_domain);
+            }
+            diesel::update(self)
+                .set((
+                    instances::failing_since.eq(Some(failing_since)),
+                    instances::failures.eq(self.failures + 1),
+                    instances::unreachable.eq(unreachable),
+                ))
+                .execute(conn)?;
+        }
+        Instance::get(conn, self.id)
+    }
+
+    pub fn list_unreachable(conn: &Connection) -> Result<Vec<Instance>> {
+        instances::table
+            .filter(instances::unreachable.eq(true))
+            .load::<Instance>(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn unreachable_domains(conn: &Connection) -> Result<HashSet<String>> {
+        instances::table
+            .filter(instances::unreachable.eq(true))
+            .select(instances::public_domain)
+            .load::<String>(conn)
+            .map(|domains| domains.into_iter().collect())
+            .map_err(Error::from)
+    }
+
+    /// Checks if an unreachable instance answers again, and brings it back if it does.
+    pub fn probe(&self, conn: &Connection) -> Result<bool> {
+        let sender = Instance::get_local_instance_user().ok_or(Error::NotFound)?;
+        let answered = request::get(
+            &format!("https://{}/.well-known/nodeinfo", self.public_domain),
+            sender,
+            CONFIG.proxy().cloned(),
+        )
+        .map(|res| res.status().is_success())
+        .unwrap_or(false);
+        if answered {
+            info!("{} is reachable again", self.public_domain);
+            self.record_delivery(conn, true)?;
+        }
+        Ok(answered)
+    }
+
+    /// Probes all the unreachable instances.
+    pub fn probe_unreachable(conn: &Connection) -> Result<()> {
+        for instance in Instance::list_unreachable(conn)? {
+            if let Err(e) = instance.probe(conn) {
+                warn!("Failed to probe {}: {:?}", instance.public_domain, e);
+            }
+        }
+        Ok(())
+    }
+
     pub fn has_admin(&self, conn: &Connection) -> Result<bool> {
         users::table
             .filter(users::instance_id.eq(self.id))
@@ -192,12 +280,14 @@ impl Instance {
             Some(&self.public_domain),
             true,
             Some(Media::get_media_processor(conn, vec![])),
+            Some(Emoji::get_emoji_processor(conn)),
         );
         let (ld, _, _) = md_to_html(
             long_description.as_ref(),
             Some(&self.public_domain),
             false,
             Some(Media::get_media_processor(conn, vec![])),
+            Some(Emoji::get_emoji_processor(conn)),
         );
         let res = diesel::update(self)
             .set((
@@ -284,6 +374,7 @@ impl Instance {
 pub(crate) mod tests {
     use super::*;
     use crate::{tests::db, Connection as Conn};
+    use chrono::Duration;
     use diesel::Connection;
 
     pub(crate) fn fill_database(conn: &Conn) -> Vec<(NewInstance, Instance)> {
@@ -444,6 +535,42 @@ pub(crate) mod tests {
         });
     }
 
+    #[test]
+    fn delivery_health() {
+        let conn = &db();
+        conn.test_transaction::<_, (), _>(|| {
+            let inst = fill_database(conn)[1].1.clone();
+            let inst = inst.record_delivery(conn, false).unwrap();
+            assert_eq!(inst.failures, 1);
+            assert!(inst.failing_since.is_some());
+            assert!(!inst.unreachable);
+
+            // Deliveries have been failing for longer than allowed
+            let long_ago = Utc::now().naive_utc() - CONFIG.unreachable_after - Duration::hours(1);
+            diesel::update(&inst)
+                .set(instances::failing_since.eq(Some(long_ago)))
+                .execute(conn)
+                .unwrap();
+            let inst = Instance::get(conn, inst.id)
+                .unwrap()
+                .record_delivery(conn, false)
+                .unwrap();
+            assert_eq!(inst.failures, 2);
+            assert!(inst.unreachable);
+            assert!(Instance::unreachable_domains(conn)
+                .unwrap()
+                .contains(&inst.public_domain));
+
+            let inst = inst.record_delivery(conn, true).unwrap();
+            assert!(!inst.unreachable);
+            assert_eq!(inst.failures, 0);
+            assert!(inst.failing_since.is_none());
+            assert!(inst.last_success_at.is_some());
+            assert!(Instance::list_unreachable(conn).unwrap().is_empty());
+            Ok(())
+        });
+    }
+
     #[test]
     fn blocked() {
         let conn = &db();
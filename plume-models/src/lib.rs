@@ -151,12 +151,30 @@ impl From<timeline::query::QueryError> for Error {
     }
 }
 
+impl From<xml::reader::Error> for Error {
+    fn from(_: xml::reader::Error) -> Self {
+        Error::SerDe
+    }
+}
+
+impl From<yaml_rust::ScanError> for Error {
+    fn from(_: yaml_rust::ScanError) -> Self {
+        Error::SerDe
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
         Error::Io(err)
     }
 }
 
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::Io(err.into())
+    }
+}
+
 impl From<InboxError<Error>> for Error {
     fn from(err: InboxError<Error>) -> Error {
         match err {
@@ -414,13 +432,19 @@ pub mod api_tokens;
 pub mod apps;
 pub mod blocklisted_emails;
 pub mod blog_authors;
+pub mod blog_slugs;
 pub mod blogs;
 pub mod comment_seers;
 pub mod comments;
 pub mod db_conn;
+pub mod deliveries;
 pub mod email_signups;
+pub mod emojis;
+pub mod epub;
+pub mod export;
 pub mod follows;
 pub mod headers;
+pub mod import;
 pub mod inbox;
 pub mod instance;
 pub mod likes;
@@ -432,15 +456,22 @@ pub mod notifications;
 pub mod password_reset_requests;
 pub mod plume_rocket;
 pub mod post_authors;
+pub mod post_previews;
+pub mod post_revisions;
+pub mod post_slugs;
+pub mod post_templates;
 pub mod posts;
 pub mod remote_fetch_actor;
+pub mod reports;
 pub mod reshares;
 pub mod safe_string;
 #[allow(unused_imports)]
 pub mod schema;
 pub mod search;
+pub mod series;
 pub mod signups;
 pub mod tags;
 pub mod timeline;
+pub mod user_blocks;
 pub mod users;
 pub use plume_rocket::PlumeRocket;
@@ -1,6 +1,6 @@
 use crate::{
-    instance::Instance, notifications::*, posts::Post, schema::likes, timeline::*, users::User,
-    Connection, Error, Result, CONFIG,
+    instance::Instance, notifications::*, posts::Post, schema::likes, timeline::*,
+    user_blocks::UserBlock, users::User, Connection, Error, Result, CONFIG,
 };
 use activitystreams::{
     activity::{ActorAndObjectRef, Like as LikeAct, Undo},
@@ -55,8 +55,9 @@ impl Like {
 
     pub fn notify(&self, conn: &Connection) -> Result<()> {
         let post = Post::get(conn, self.post_id)?;
+        let user = User::get(conn, self.user_id)?;
         for author in post.get_authors(conn)? {
-            if author.is_local() {
+            if author.is_local() && !UserBlock::is_blocked(conn, &author, &user)? {
                 Notification::insert(
                     conn,
                     NewNotification {
@@ -1,13 +1,17 @@
 use crate::{
-    ap_url, instance::Instance, safe_string::SafeString, schema::medias, users::User, Connection,
-    Error, Result, CONFIG,
+    ap_url, import::download, instance::Instance, safe_string::SafeString, schema::medias,
+    users::User, Connection, Error, Result, CONFIG,
+};
+use activitystreams::{
+    base::AnyBase,
+    object::{Audio, Document, Image, Video},
+    prelude::*,
 };
-use activitystreams::{object::Image, prelude::*};
 use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
 use guid_create::GUID;
 use plume_common::{
     activity_pub::{inbox::FromId, request, ToAsString, ToAsUri},
-    utils::{escape, MediaProcessor},
+    utils::{escape, MediaProcessor, ProcessedMedia},
 };
 use std::{
     fs::{self, DirBuilder},
@@ -21,6 +25,16 @@ use crate::config::S3Config;
 
 const REMOTE_MEDIA_DIRECTORY: &str = "remote";
 
+/// The types of images that can be downloaded from other instances, with their extension.
+///
+/// Anything else, like SVG or HTML, could run scripts once served from our domain.
+const DOWNLOADABLE_IMAGES: &[(&str, &str)] = &[
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+];
+
 #[derive(Clone, Identifiable, Queryable, AsChangeset)]
 pub struct Media {
     pub id: i32,
@@ -77,6 +91,36 @@ impl Media {
             .map_err(Error::from)
     }
 
+    /// Finds the media of `owners` that are stored on this instance at `relative_urls`.
+    pub fn find_by_relative_urls(
+        conn: &Connection,
+        relative_urls: &[String],
+        owners: &[i32],
+    ) -> Result<Vec<Media>> {
+        // files may be stored in the media directory, or under their relative URL
+        let file_paths = relative_urls
+            .iter()
+            .flat_map(|url| {
+                let file = url.trim_start_matches("static/media/");
+                vec![
+                    format!("{}/{}", CONFIG.media_directory, file),
+                    format!("static/media/{}", file),
+                ]
+            })
+            .collect::<Vec<_>>();
+        Ok(medias::table
+            .filter(medias::file_path.eq_any(file_paths))
+            .filter(medias::owner_id.eq_any(owners))
+            .load::<Media>(conn)?
+            .into_iter()
+            .filter(|media| {
+                media
+                    .relative_url()
+                    .map_or(false, |url| relative_urls.contains(&url))
+            })
+            .collect())
+    }
+
     pub fn list_all_medias(conn: &Connection) -> Result<Vec<Media>> {
         medias::table.load::<Media>(conn).map_err(Error::from)
     }
@@ -254,6 +298,27 @@ impl Media {
             .map_err(Error::from)
     }
 
+    /// Reads the content of a media stored on this instance.
+    pub fn read_file(&self) -> Result<Vec<u8>> {
+        if self.is_remote {
+            return Err(Error::NotFound);
+        }
+
+        if CONFIG.s3.is_some() {
+            #[cfg(not(feature="s3"))]
+            unreachable!();
+
+            #[cfg(feature = "s3")]
+            {
+                let data = CONFIG.s3.as_ref().unwrap().get_bucket()
+                    .get_object_blocking(&self.relative_url().ok_or(Error::NotFound)?)?;
+                Ok(data.to_vec())
+            }
+        } else {
+            Ok(fs::read(self.local_path().ok_or(Error::NotFound)?)?)
+        }
+    }
+
     pub fn save_remote(conn: &Connection, url: String, user: &User) -> Result<Media> {
         if url.contains(&['<', '>', '"'][..]) {
             Err(Error::Url)
@@ -273,6 +338,99 @@ impl Media {
         }
     }
 
+    /// Downloads a remote image to a new media file. Its original address is kept
+    /// in `remote_url`, to know when it changes.
+    ///
+    /// Only PNG, JPEG, GIF and WebP images are accepted.
+    pub fn save_download(
+        conn: &Connection,
+        url: String,
+        alt_text: String,
+        user: &User,
+    ) -> Result<Media> {
+        let file_name = url.split(&['?', '#'][..]).next().unwrap_or_default();
+        let url_ext = file_name
+            .rsplit('/')
+            .next()
+            .and_then(|file| file.rsplit_once('.'))
+            .map(|(_, ext)| ext.to_lowercase());
+        if let Some(url_ext) = url_ext {
+            if url_ext != "jpeg" && !DOWNLOADABLE_IMAGES.iter().any(|(_, ext)| *ext == url_ext) {
+                return Err(Error::InvalidValue);
+            }
+        }
+
+        let (data, content_type) = download(&url)?;
+        let ext = downloadable_image_extension(content_type.as_deref().unwrap_or_default())
+            .ok_or(Error::InvalidValue)?;
+        // The extension decides the type the file is served with, so it comes from
+        // the checked content type
+        let mut media = Media::save_file(
+            conn,
+            &data,
+            &format!("download.{}", ext),
+            alt_text,
+            None,
+            user,
+        )?;
+        diesel::update(&media)
+            .set(medias::remote_url.eq(&url))
+            .execute(conn)?;
+        media.remote_url = Some(url);
+        Ok(media)
+    }
+
+    /// Stores `data` in a new media file, with the extension of `file_name`.
+    pub fn save_file(
+        conn: &Connection,
+        data: &[u8],
+        file_name: &str,
+        alt_text: String,
+        content_warning: Option<String>,
+        user: &User,
+    ) -> Result<Media> {
+        let ext = file_extension(file_name);
+
+        let file_path = if CONFIG.s3.is_some() {
+            #[cfg(not(feature="s3"))]
+            unreachable!();
+
+            #[cfg(feature = "s3")]
+            {
+                use rocket::http::ContentType;
+
+                let dest = format!("static/media/{}.{}", GUID::rand(), ext);
+                let content_type = ContentType::from_extension(&ext).unwrap_or(ContentType::Binary);
+
+                let bucket = CONFIG.s3.as_ref().unwrap().get_bucket();
+                bucket.put_object_with_content_type_blocking(
+                    &dest,
+                    data,
+                    &content_type.to_string()
+                )?;
+
+                dest
+            }
+        } else {
+            let dest = format!("{}/{}.{}", CONFIG.media_directory, GUID::rand(), ext);
+            fs::write(&dest, data)?;
+            dest
+        };
+
+        Media::insert(
+            conn,
+            NewMedia {
+                file_path,
+                alt_text,
+                is_remote: false,
+                remote_url: None,
+                sensitive: content_warning.is_some(),
+                content_warning,
+                owner_id: user.id,
+            },
+        )
+    }
+
     pub fn set_owner(&self, conn: &Connection, user: &User) -> Result<()> {
         diesel::update(self)
             .set(medias::owner_id.eq(user.id))
@@ -406,13 +564,46 @@ impl Media {
             })
     }
 
+    /// The ActivityStreams representation of this media, to attach it to an article.
+    pub fn to_attachment(&self) -> Result<AnyBase> {
+        let url = self.url()?;
+        let attachment = match self.category() {
+            MediaCategory::Image => {
+                let mut image = Image::new();
+                image.set_url(url).set_name(self.alt_text.clone());
+                image.into_any_base()?
+            }
+            MediaCategory::Audio => {
+                let mut audio = Audio::new();
+                audio.set_url(url).set_name(self.alt_text.clone());
+                audio.into_any_base()?
+            }
+            MediaCategory::Video => {
+                let mut video = Video::new();
+                video.set_url(url).set_name(self.alt_text.clone());
+                video.into_any_base()?
+            }
+            MediaCategory::Unknown => {
+                let mut document = Document::new();
+                document.set_url(url).set_name(self.alt_text.clone());
+                document.into_any_base()?
+            }
+        };
+        Ok(attachment)
+    }
+
     pub fn get_media_processor<'a>(conn: &'a Connection, user: Vec<&User>) -> MediaProcessor<'a> {
         let uid = user.iter().map(|u| u.id).collect::<Vec<_>>();
         Box::new(move |id| {
             let media = Media::get(conn, id).ok()?;
             // if owner is user or check is disabled
             if uid.contains(&media.owner_id) || uid.is_empty() {
-                Some((media.url().ok()?, media.content_warning))
+                Some(ProcessedMedia {
+                    url: media.url().ok()?,
+                    category: media.category().to_string().to_owned(),
+                    alt_text: media.alt_text,
+                    content_warning: media.content_warning,
+                })
             } else {
                 None
             }
@@ -480,6 +671,35 @@ fn determine_mirror_s3_path(url: &str) -> String {
     }
 }
 
+/// The extension to save a file named `file_name` with, in lowercase.
+///
+/// It is empty if it contains something else than just letters and numbers.
+pub fn file_extension(file_name: &str) -> String {
+    file_name
+        .rsplit('/')
+        .next()
+        .and_then(|file| file.rsplit_once('.'))
+        .map(|(_, ext)| ext)
+        .filter(|ext| ext.chars().all(|c| c.is_alphanumeric()))
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// The extension to save a downloaded image of type `content_type` with,
+/// if it is one of `DOWNLOADABLE_IMAGES`.
+fn downloadable_image_extension(content_type: &str) -> Option<&'static str> {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    DOWNLOADABLE_IMAGES
+        .iter()
+        .find(|(image_type, _)| *image_type == mime)
+        .map(|(_, ext)| *ext)
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
@@ -636,4 +856,31 @@ pub(crate) mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn save_download_only_images() {
+        let conn = &db();
+        conn.test_transaction::<_, (), _>(|| {
+            let user = &fill_database(conn).0[0];
+            for url in &[
+                "https://example.com/emoji.svg",
+                "https://example.com/emoji.html?.png",
+                "https://example.com/emoji.JS",
+            ] {
+                assert!(Media::save_download(conn, url.to_string(), String::new(), user).is_err());
+            }
+
+            assert_eq!(downloadable_image_extension("image/png"), Some("png"));
+            assert_eq!(
+                downloadable_image_extension("Image/JPEG; charset=binary"),
+                Some("jpg")
+            );
+            assert_eq!(downloadable_image_extension("image/svg+xml"), None);
+            assert_eq!(downloadable_image_extension("text/html"), None);
+            assert_eq!(downloadable_image_extension(""), None);
+
+            clean(conn);
+            Ok(())
+        });
+    }
 }
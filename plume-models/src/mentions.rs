@@ -1,6 +1,6 @@
 use crate::{
-    comments::Comment, notifications::*, posts::Post, schema::mentions, users::User, Connection,
-    Error, Result,
+    comments::Comment, notifications::*, posts::Post, schema::mentions, user_blocks::UserBlock,
+    users::User, Connection, Error, Result,
 };
 use activitystreams::{
     base::BaseExt,
@@ -132,7 +132,7 @@ impl Mention {
 
     fn notify(&self, conn: &Connection) -> Result<()> {
         let m = self.get_mentioned(conn)?;
-        if m.is_local() {
+        if m.is_local() && !UserBlock::is_blocked(conn, &m, &self.get_user(conn)?)? {
             Notification::insert(
                 conn,
                 NewNotification {
@@ -0,0 +1,130 @@
+use crate::{posts::Post, schema::post_previews, Connection, Error, Result};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
+use plume_common::utils::random_hex;
+
+/// A secret link giving read-only access to a draft, for people without an account.
+#[derive(Clone, Queryable, Identifiable, Associations)]
+#[belongs_to(Post)]
+pub struct PostPreview {
+    pub id: i32,
+    pub post_id: i32,
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+    pub creation_date: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "post_previews"]
+pub struct NewPostPreview {
+    pub post_id: i32,
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+impl PostPreview {
+    insert!(post_previews, NewPostPreview);
+    get!(post_previews);
+
+    /// Creates a new preview link for `post`, valid for `validity`.
+    pub fn create(conn: &Connection, post: &Post, validity: Duration) -> Result<Self> {
+        if post.published {
+            return Err(Error::InvalidValue);
+        }
+        Self::insert(
+            conn,
+            NewPostPreview {
+                post_id: post.id,
+                token: random_hex(),
+                expires_at: Utc::now().naive_utc() + validity,
+            },
+        )
+    }
+
+    /// Finds a preview link that has not expired yet.
+    pub fn find_valid(conn: &Connection, token: &str) -> Result<Self> {
+        post_previews::table
+            .filter(post_previews::token.eq(token))
+            .filter(post_previews::expires_at.gt(Utc::now().naive_utc()))
+            .first(conn)
+            .map_err(Error::from)
+    }
+
+    /// Lists the preview links of a post that have not expired yet.
+    pub fn list_for_post(conn: &Connection, post_id: i32) -> Result<Vec<Self>> {
+        post_previews::table
+            .filter(post_previews::post_id.eq(post_id))
+            .filter(post_previews::expires_at.gt(Utc::now().naive_utc()))
+            .order(post_previews::expires_at.asc())
+            .load::<Self>(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn get_post(&self, conn: &Connection) -> Result<Post> {
+        Post::get(conn, self.post_id)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now().naive_utc()
+    }
+
+    /// Revokes this link.
+    pub fn delete(&self, conn: &Connection) -> Result<()> {
+        diesel::delete(self)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    /// Deletes all the links of a post, for instance once it has been published.
+    pub fn delete_for_post(conn: &Connection, post_id: i32) -> Result<()> {
+        diesel::delete(post_previews::table.filter(post_previews::post_id.eq(post_id)))
+            .execute(conn)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    pub fn delete_expired(conn: &Connection) -> Result<usize> {
+        diesel::delete(
+            post_previews::table.filter(post_previews::expires_at.le(Utc::now().naive_utc())),
+        )
+        .execute(conn)
+        .map_err(Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inbox::tests::fill_database, tests::db};
+    use diesel::Connection;
+
+    #[test]
+    fn create_and_revoke() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, _, _) = fill_database(conn);
+            let mut draft = posts[0].clone();
+            assert!(PostPreview::create(conn, &draft, Duration::days(1)).is_err());
+
+            draft.published = false;
+            let draft = draft.update(conn)?;
+            let preview = PostPreview::create(conn, &draft, Duration::days(1))?;
+            let expired = PostPreview::create(conn, &draft, Duration::days(-1))?;
+            assert_eq!(
+                PostPreview::find_valid(conn, &preview.token)?
+                    .get_post(conn)?
+                    .id,
+                draft.id
+            );
+            assert!(PostPreview::find_valid(conn, &expired.token).is_err());
+            assert_eq!(PostPreview::list_for_post(conn, draft.id)?.len(), 1);
+
+            preview.delete(conn)?;
+            assert!(PostPreview::find_valid(conn, &preview.token).is_err());
+            assert_eq!(PostPreview::delete_expired(conn)?, 1);
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{posts::Post, schema::post_revisions, users::User, Connection, Error, Result};
+use chrono::NaiveDateTime;
+use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
+use plume_common::utils::{diff_lines, DiffLine};
+
+/// A snapshot of a post, saved each time it is edited.
+#[derive(Clone, Queryable, Identifiable, Associations)]
+#[belongs_to(Post)]
+pub struct PostRevision {
+    pub id: i32,
+    pub post_id: i32,
+    pub author_id: Option<i32>,
+    pub title: String,
+    pub subtitle: String,
+    pub source: String,
+    pub creation_date: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "post_revisions"]
+pub struct NewPostRevision {
+    pub post_id: i32,
+    pub author_id: Option<i32>,
+    pub title: String,
+    pub subtitle: String,
+    pub source: String,
+}
+
+impl PostRevision {
+    insert!(post_revisions, NewPostRevision);
+    get!(post_revisions);
+
+    /// Saves the current state of `post` as a new revision, made by `author`.
+    pub fn snapshot(conn: &Connection, post: &Post, author: Option<&User>) -> Result<Self> {
+        Self::insert(
+            conn,
+            NewPostRevision {
+                post_id: post.id,
+                author_id: author.map(|a| a.id),
+                title: post.title.clone(),
+                subtitle: post.subtitle.clone(),
+                source: post.source.clone(),
+            },
+        )
+    }
+
+    /// Lists the revisions of a post, the most recent first.
+    pub fn list_for_post(conn: &Connection, post_id: i32) -> Result<Vec<Self>> {
+        post_revisions::table
+            .filter(post_revisions::post_id.eq(post_id))
+            .order(post_revisions::id.desc())
+            .load::<Self>(conn)
+            .map_err(Error::from)
+    }
+
+    /// Finds a revision, making sure it belongs to the given post.
+    pub fn find_for_post(conn: &Connection, post_id: i32, id: i32) -> Result<Self> {
+        post_revisions::table
+            .filter(post_revisions::post_id.eq(post_id))
+            .filter(post_revisions::id.eq(id))
+            .first(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn get_author(&self, conn: &Connection) -> Option<User> {
+        self.author_id.and_then(|id| User::get(conn, id).ok())
+    }
+
+    /// Line-based diff of the Markdown source, from `self` to `newer`.
+    pub fn diff<'a>(&'a self, newer: &'a PostRevision) -> Vec<DiffLine<'a>> {
+        diff_lines(&self.source, &newer.source)
+    }
+
+    /// Copies the content of this revision back to `post`.
+    ///
+    /// The caller is responsible for rendering the HTML content again and saving the post.
+    pub fn restore_to(&self, post: &mut Post) {
+        post.title = self.title.clone();
+        post.subtitle = self.subtitle.clone();
+        post.source = self.source.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inbox::tests::fill_database, tests::db};
+    use diesel::Connection;
+
+    #[test]
+    fn snapshot_and_restore() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, _) = fill_database(conn);
+            let mut post = posts[0].clone();
+
+            let first = PostRevision::snapshot(conn, &post, Some(&users[0]))?;
+            post.title = "Edited".to_owned();
+            post.source = "Hello\nWorld".to_owned();
+            let post = post.update(conn)?;
+            let second = PostRevision::snapshot(conn, &post, Some(&users[0]))?;
+
+            let revisions = PostRevision::list_for_post(conn, post.id)?;
+            assert_eq!(
+                revisions.iter().map(|r| r.id).collect::<Vec<_>>(),
+                vec![second.id, first.id]
+            );
+            assert_eq!(
+                first.diff(&second),
+                vec![DiffLine::Same("Hello"), DiffLine::Added("World")]
+            );
+            assert!(PostRevision::find_for_post(conn, post.id + 1, first.id).is_err());
+
+            let mut restored = post.clone();
+            first.restore_to(&mut restored);
+            assert_eq!(restored.title, posts[0].title);
+            assert_eq!(restored.source, posts[0].source);
+
+            Ok(())
+        });
+    }
+}
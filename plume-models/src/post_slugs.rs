@@ -0,0 +1,87 @@
+use crate::{
+    blog_slugs::BlogSlug,
+    posts::Post,
+    schema::{blogs, post_slugs},
+    Connection, Error, Result,
+};
+use chrono::NaiveDateTime;
+use diesel::{self, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+
+/// An address a published post had before its slug or its blog changed, that still
+/// leads to it.
+#[derive(Clone, Queryable, Identifiable, Associations)]
+#[belongs_to(Post)]
+pub struct PostSlug {
+    pub id: i32,
+    pub post_id: i32,
+    pub blog_id: i32,
+    pub slug: String,
+    pub creation_date: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "post_slugs"]
+pub struct NewPostSlug {
+    pub post_id: i32,
+    pub blog_id: i32,
+    pub slug: String,
+}
+
+impl PostSlug {
+    insert!(post_slugs, NewPostSlug);
+    list_by!(post_slugs, list_for_post, post_id as i32);
+
+    /// Finds the post that previously had the slug `slug` in the blog `blog_id`.
+    pub fn find_post(conn: &Connection, blog_id: i32, slug: &str) -> Result<Post> {
+        post_slugs::table
+            .filter(post_slugs::blog_id.eq(blog_id))
+            .filter(post_slugs::slug.eq(slug))
+            .select(post_slugs::post_id)
+            .first::<i32>(conn)
+            .map_err(Error::from)
+            .and_then(|id| Post::get(conn, id))
+    }
+
+    /// Finds a post from one of its previous addresses: an old slug in its blog, or
+    /// any of its slugs in a previous name of its blog.
+    pub fn find_moved(conn: &Connection, blog: &str, slug: &str) -> Result<Post> {
+        let blog_id = blogs::table
+            .filter(blogs::fqn.eq(blog))
+            .select(blogs::id)
+            .first::<i32>(conn)
+            .optional()?;
+        match blog_id {
+            Some(blog_id) => Self::find_post(conn, blog_id, slug),
+            None => {
+                let blog = BlogSlug::find_blog(conn, blog)?;
+                Post::find_by_slug(conn, slug, blog.id)
+                    .or_else(|_| Self::find_post(conn, blog.id, slug))
+            }
+        }
+    }
+
+    /// Remembers the address a post had before being updated.
+    pub(crate) fn record(conn: &Connection, previous: &Post) -> Result<Self> {
+        Self::release(conn, previous.blog_id, &previous.slug)?;
+        Self::insert(
+            conn,
+            NewPostSlug {
+                post_id: previous.id,
+                blog_id: previous.blog_id,
+                slug: previous.slug.clone(),
+            },
+        )
+    }
+
+    /// Forgets a previous address, once a post uses it again.
+    pub(crate) fn release(conn: &Connection, blog_id: i32, slug: &str) -> Result<()> {
+        diesel::delete(
+            post_slugs::table
+                .filter(post_slugs::blog_id.eq(blog_id))
+                .filter(post_slugs::slug.eq(slug)),
+        )
+        .execute(conn)
+        .map(|_| ())
+        .map_err(Error::from)
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{schema::post_templates, Connection, Error, Result};
+use chrono::NaiveDate;
+use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
+
+/// A skeleton for the new posts of a blog.
+#[derive(Queryable, Identifiable, Clone, AsChangeset, Debug)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct PostTemplate {
+    pub id: i32,
+    pub blog_id: i32,
+    pub name: String,
+    /// The title of the new posts, where `{date}`, `{year}`, `{month}`, `{day}` and `{week}`
+    /// are replaced with the current date.
+    pub title: String,
+    pub source: String,
+    /// Separated by commas, like in the editor
+    pub tags: String,
+    /// If empty, the default license of the instance is used
+    pub license: String,
+    pub cover_id: Option<i32>,
+}
+
+#[derive(Default, Insertable)]
+#[table_name = "post_templates"]
+pub struct NewPostTemplate {
+    pub blog_id: i32,
+    pub name: String,
+    pub title: String,
+    pub source: String,
+    pub tags: String,
+    pub license: String,
+    pub cover_id: Option<i32>,
+}
+
+impl PostTemplate {
+    insert!(post_templates, NewPostTemplate);
+    get!(post_templates);
+
+    pub fn list_for_blog(conn: &Connection, blog_id: i32) -> Result<Vec<PostTemplate>> {
+        post_templates::table
+            .filter(post_templates::blog_id.eq(blog_id))
+            .order(post_templates::name.asc())
+            .load::<PostTemplate>(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<Self> {
+        diesel::update(self).set(self).execute(conn)?;
+        Self::get(conn, self.id)
+    }
+
+    pub fn delete(&self, conn: &Connection) -> Result<()> {
+        diesel::delete(self)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    /// The title of a post started from this template on `date`.
+    ///
+    /// When the title contains the week, the year is the one this week belongs to,
+    /// that can differ from the calendar year at its start and at its end.
+    pub fn title_for(&self, date: NaiveDate) -> String {
+        let year = if self.title.contains("{week}") {
+            "%G"
+        } else {
+            "%Y"
+        };
+        [
+            ("{date}", "%Y-%m-%d"),
+            ("{year}", year),
+            ("{month}", "%m"),
+            ("{day}", "%d"),
+            ("{week}", "%V"),
+        ]
+        .iter()
+        .fold(self.title.clone(), |title, (pattern, format)| {
+            title.replace(pattern, &date.format(format).to_string())
+        })
+    }
+
+    pub fn tag_list(&self) -> Vec<String> {
+        self.tags
+            .split(',')
+            .map(|tag| tag.trim().to_owned())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{blogs::tests as blog_tests, tests::db};
+    use diesel::Connection;
+
+    #[test]
+    fn list_and_fill() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (_, blogs) = blog_tests::fill_database(conn);
+            let newsletter = PostTemplate::insert(
+                conn,
+                NewPostTemplate {
+                    blog_id: blogs[0].id,
+                    name: "Newsletter".to_owned(),
+                    title: "Newsletter, week {week} of {year}".to_owned(),
+                    source: "# News\n\n# Links\n".to_owned(),
+                    tags: "news, , weekly ".to_owned(),
+                    ..NewPostTemplate::default()
+                },
+            )?;
+            PostTemplate::insert(
+                conn,
+                NewPostTemplate {
+                    blog_id: blogs[0].id,
+                    name: "Interview".to_owned(),
+                    ..NewPostTemplate::default()
+                },
+            )?;
+            PostTemplate::insert(
+                conn,
+                NewPostTemplate {
+                    blog_id: blogs[1].id,
+                    name: "Newsletter".to_owned(),
+                    ..NewPostTemplate::default()
+                },
+            )?;
+
+            let names = PostTemplate::list_for_blog(conn, blogs[0].id)?
+                .into_iter()
+                .map(|t| t.name)
+                .collect::<Vec<_>>();
+            assert_eq!(names, vec!["Interview", "Newsletter"]);
+
+            assert_eq!(
+                newsletter.title_for(NaiveDate::from_ymd_opt(2026, 1, 2).unwrap()),
+                "Newsletter, week 01 of 2026"
+            );
+            assert_eq!(
+                newsletter.title_for(NaiveDate::from_ymd_opt(2027, 1, 1).unwrap()),
+                "Newsletter, week 53 of 2026"
+            );
+            assert_eq!(newsletter.tag_list(), vec!["news", "weekly"]);
+            Ok(())
+        });
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
-    ap_url, blogs::Blog, instance::Instance, medias::Media, mentions::Mention, post_authors::*,
-    safe_string::SafeString, schema::posts, tags::*, timeline::*, users::User, Connection, Error,
-    PostEvent::*, Result, CONFIG, POST_CHAN,
+    ap_url, blogs::Blog, deliveries::Delivery, emojis::Emoji, instance::Instance, medias::Media,
+    mentions::Mention, post_authors::*, post_slugs::PostSlug, safe_string::SafeString,
+    schema::posts, series::Series, tags::*, timeline::*, user_blocks::UserBlock, users::User,
+    Connection, Error, PostEvent::*, Result, UserEvent::*, CONFIG, POST_CHAN, USER_CHAN,
 };
 use activitystreams::{
     activity::{Create, Delete, Update},
@@ -10,26 +11,207 @@ use activitystreams::{
     link::{self, kind::MentionType},
     object::{kind::ImageType, ApObject, Article, AsApObject, Image, ObjectExt, Tombstone},
     prelude::*,
+    primitives::OneOrMany,
     time::OffsetDateTime,
 };
 use chrono::{NaiveDateTime, Utc};
-use diesel::{self, BelongingToDsl, ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::{
+    self, BelongingToDsl, BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl,
+};
 use once_cell::sync::Lazy;
 use plume_common::{
     activity_pub::{
         inbox::{AsActor, AsObject, FromId},
+        request::get,
         sign::Signer,
-        Hashtag, HashtagType, Id, IntoId, Licensed, LicensedArticle, ToAsString, ToAsUri,
-        PUBLIC_VISIBILITY,
+        ContentMapProperty, Hashtag, HashtagType, Id, IntoId, Licensed, LicensedArticle,
+        SensitiveProperty, ToAsString, ToAsUri, PUBLIC_VISIBILITY,
     },
-    utils::{iri_percent_encode_seg, md_to_html},
+    utils::{iri_percent_encode_seg, md_to_html, reading_stats, referenced_media},
 };
 use riker::actors::{Publish, Tell};
 use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use tracing::warn;
+use url::Url;
+use whatlang::{self, Lang};
+
+/// The maximum number of posts a blog can pin
+pub const MAX_PINNED_POSTS: i64 = 5;
+
+/// The ISO 639-1 codes of the languages whatlang can detect, with their ISO 639-3 code
+const LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("af", "afr"),
+    ("ak", "aka"),
+    ("am", "amh"),
+    ("ar", "ara"),
+    ("az", "aze"),
+    ("be", "bel"),
+    ("bg", "bul"),
+    ("bn", "ben"),
+    ("ca", "cat"),
+    ("cs", "ces"),
+    ("da", "dan"),
+    ("de", "deu"),
+    ("el", "ell"),
+    ("en", "eng"),
+    ("eo", "epo"),
+    ("es", "spa"),
+    ("et", "est"),
+    ("fa", "pes"),
+    ("fi", "fin"),
+    ("fr", "fra"),
+    ("gu", "guj"),
+    ("he", "heb"),
+    ("hi", "hin"),
+    ("hr", "hrv"),
+    ("hu", "hun"),
+    ("hy", "hye"),
+    ("id", "ind"),
+    ("it", "ita"),
+    ("ja", "jpn"),
+    ("jv", "jav"),
+    ("ka", "kat"),
+    ("km", "khm"),
+    ("kn", "kan"),
+    ("ko", "kor"),
+    ("la", "lat"),
+    ("lt", "lit"),
+    ("lv", "lav"),
+    ("mk", "mkd"),
+    ("ml", "mal"),
+    ("mr", "mar"),
+    ("my", "mya"),
+    ("nb", "nob"),
+    ("ne", "nep"),
+    ("nl", "nld"),
+    ("no", "nob"),
+    ("or", "ori"),
+    ("pa", "pan"),
+    ("pl", "pol"),
+    ("pt", "por"),
+    ("ro", "ron"),
+    ("ru", "rus"),
+    ("si", "sin"),
+    ("sk", "slk"),
+    ("sl", "slv"),
+    ("sn", "sna"),
+    ("sr", "srp"),
+    ("sv", "swe"),
+    ("ta", "tam"),
+    ("te", "tel"),
+    ("th", "tha"),
+    ("tk", "tuk"),
+    ("tl", "tgl"),
+    ("tr", "tur"),
+    ("uk", "ukr"),
+    ("ur", "urd"),
+    ("uz", "uzb"),
+    ("vi", "vie"),
+    ("yi", "yid"),
+    ("zh", "cmn"),
+    ("zu", "zul"),
+];
+
+/// Finds the language whatlang uses for a BCP 47 language tag.
+fn whatlang_lang(tag: &str) -> Option<Lang> {
+    let primary = tag.split('-').next()?.to_lowercase();
+    let code = LANGUAGE_CODES
+        .iter()
+        .find(|(short, _)| *short == primary)
+        .map_or(primary.as_str(), |(_, long)| *long);
+    Lang::from_code(code)
+}
+
+/// Checks that an address can be used as the canonical URL of a post: only HTTP(S)
+/// ones are accepted, as they end up in links.
+pub fn is_valid_canonical_url(url: &str) -> bool {
+    Url::parse(url).map_or(false, |url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Checks that a language tag looks like a BCP 47 one, like `en` or `pt-BR`.
+pub fn is_valid_language(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    subtags.next().map_or(false, |primary| {
+        (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_alphabetic())
+    }) && subtags.all(|subtag| {
+        (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+}
 
 static BLOG_FQN_CACHE: Lazy<Mutex<HashMap<i32, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
+/// Who can read a post. Represented in database as an integer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    /// Everyone, and it is listed in timelines and search results
+    Public,
+    /// Everyone, but it is only listed to the followers of its authors
+    Unlisted,
+    /// The followers of its authors, and the authors of its blog
+    Followers,
+    /// The authors of its blog only
+    BlogMembers,
+}
+
+impl TryFrom<i32> for Visibility {
+    type Error = ();
+
+    fn try_from(i: i32) -> std::result::Result<Self, ()> {
+        match i {
+            0 => Ok(Visibility::Public),
+            1 => Ok(Visibility::Unlisted),
+            2 => Ok(Visibility::Followers),
+            3 => Ok(Visibility::BlogMembers),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<Visibility> for i32 {
+    fn from(visibility: Visibility) -> Self {
+        match visibility {
+            Visibility::Public => 0,
+            Visibility::Unlisted => 1,
+            Visibility::Followers => 2,
+            Visibility::BlogMembers => 3,
+        }
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "public" => Ok(Visibility::Public),
+            "unlisted" => Ok(Visibility::Unlisted),
+            "followers" => Ok(Visibility::Followers),
+            "blog_members" => Ok(Visibility::BlogMembers),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+}
+
+impl Visibility {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Followers => "followers",
+            Visibility::BlogMembers => "blog_members",
+        }
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::Public
+    }
+}
+
 #[derive(Queryable, Identifiable, Clone, AsChangeset, Debug)]
 #[changeset_options(treat_none_as_null = "true")]
 pub struct Post {
@@ -45,6 +227,21 @@ pub struct Post {
     pub subtitle: String,
     pub source: String,
     pub cover_id: Option<i32>,
+    pub publish_at: Option<NaiveDateTime>,
+    pub visibility: i32,
+    /// When the post was pinned at the top of its blog
+    pub pinned_at: Option<NaiveDateTime>,
+    pub sensitive: bool,
+    pub content_warning: Option<String>,
+    /// The original address of the post, if it was first published elsewhere
+    pub canonical_url: Option<String>,
+    /// The language of the post, as a BCP 47 tag, if its authors chose it
+    pub language: Option<String>,
+    /// The post this one is a translation of
+    pub translation_of: Option<i32>,
+    pub word_count: i32,
+    /// In minutes
+    pub reading_time: i32,
 }
 
 #[derive(Insertable)]
@@ -61,6 +258,13 @@ pub struct NewPost {
     pub subtitle: String,
     pub source: String,
     pub cover_id: Option<i32>,
+    pub publish_at: Option<NaiveDateTime>,
+    pub visibility: i32,
+    pub sensitive: bool,
+    pub content_warning: Option<String>,
+    pub canonical_url: Option<String>,
+    pub language: Option<String>,
+    pub translation_of: Option<i32>,
 }
 
 impl Post {
@@ -69,7 +273,18 @@ impl Post {
     find_by!(posts, find_by_ap_url, ap_url as &str);
 
     last!(posts);
-    pub fn insert(conn: &Connection, mut new: NewPost) -> Result<Self> {
+    pub fn insert(conn: &Connection, new: NewPost) -> Result<Self> {
+        let post = Self::insert_unindexed(conn, new)?;
+
+        if post.published {
+            post.publish_published();
+        }
+
+        Ok(post)
+    }
+
+    /// Inserts a post without adding it to the search index, for imported posts.
+    pub(crate) fn insert_unindexed(conn: &Connection, mut new: NewPost) -> Result<Self> {
         if new.ap_url.is_empty() {
             let blog = Blog::get(conn, new.blog_id)?;
             new.ap_url = Self::ap_url(blog, &new.slug);
@@ -78,22 +293,62 @@ impl Post {
             .values(new)
             .execute(conn)?;
         let post = Self::last(conn)?;
+        let (word_count, reading_time) = reading_stats(&post.source);
+        diesel::update(&post)
+            .set((
+                posts::word_count.eq(word_count as i32),
+                posts::reading_time.eq(reading_time as i32),
+            ))
+            .execute(conn)?;
+        Ok(Post {
+            word_count: word_count as i32,
+            reading_time: reading_time as i32,
+            ..post
+        })
+    }
 
-        if post.published {
-            post.publish_published();
+    /// Computes the word count and reading time of every post, for the ones saved before they were stored.
+    pub(crate) fn update_all_reading_stats(conn: &Connection) -> Result<()> {
+        let sources = posts::table
+            .select((posts::id, posts::source))
+            .load::<(i32, String)>(conn)?;
+        for (id, source) in sources {
+            let (word_count, reading_time) = reading_stats(&source);
+            diesel::update(posts::table.find(id))
+                .set((
+                    posts::word_count.eq(word_count as i32),
+                    posts::reading_time.eq(reading_time as i32),
+                ))
+                .execute(conn)?;
         }
-
-        Ok(post)
+        Ok(())
     }
 
     pub fn update(&self, conn: &Connection) -> Result<Self> {
-        diesel::update(self).set(self).execute(conn)?;
+        let previous = Self::get(conn, self.id)?;
+        let (word_count, reading_time) = reading_stats(&self.source);
+        let changes = Post {
+            word_count: word_count as i32,
+            reading_time: reading_time as i32,
+            ..self.clone()
+        };
+        diesel::update(self).set(&changes).execute(conn)?;
+        // keep the previous address of published posts working
+        if previous.slug != self.slug || previous.blog_id != self.blog_id {
+            PostSlug::release(conn, self.blog_id, &self.slug)?;
+            if previous.published {
+                PostSlug::record(conn, &previous)?;
+            }
+        }
         let post = Self::get(conn, self.id)?;
-        // TODO: Call publish_published() when newly published
         if post.published {
-            let blog = post.get_blog(conn);
-            if blog.is_ok() && blog.unwrap().is_local() {
-                self.publish_updated();
+            if !previous.published {
+                post.publish_published();
+            } else {
+                let blog = post.get_blog(conn);
+                if blog.is_ok() && blog.unwrap().is_local() {
+                    self.publish_updated();
+                }
             }
         }
         Ok(post)
@@ -103,6 +358,9 @@ impl Post {
         for m in Mention::list_for_post(conn, self.id)? {
             m.delete(conn)?;
         }
+        if let Some(series) = Series::find_for_post(conn, self.id)? {
+            series.remove_post(conn, self)?;
+        }
         diesel::delete(self).execute(conn)?;
         self.publish_deleted();
         Ok(())
@@ -119,6 +377,7 @@ impl Post {
         posts::table
             .filter(posts::id.eq_any(ids))
             .filter(posts::published.eq(true))
+            .filter(posts::visibility.eq(i32::from(Visibility::Public)))
             .order(posts::creation_date.desc())
             .offset(min.into())
             .limit((max - min).into())
@@ -132,6 +391,7 @@ impl Post {
         posts::table
             .filter(posts::id.eq_any(ids))
             .filter(posts::published.eq(true))
+            .filter(posts::visibility.eq(i32::from(Visibility::Public)))
             .count()
             .load(conn)?
             .first()
@@ -195,6 +455,7 @@ impl Post {
         posts::table
             .filter(posts::id.eq_any(posts))
             .filter(posts::published.eq(true))
+            .filter(posts::visibility.le(i32::from(Visibility::Unlisted)))
             .order(posts::creation_date.desc())
             .limit(limit)
             .load::<Post>(conn)
@@ -205,6 +466,7 @@ impl Post {
         posts::table
             .filter(posts::blog_id.eq(blog.id))
             .filter(posts::published.eq(true))
+            .filter(posts::visibility.le(i32::from(Visibility::Unlisted)))
             .order(posts::creation_date.desc())
             .limit(limit)
             .load::<Post>(conn)
@@ -223,6 +485,7 @@ impl Post {
         posts::table
             .filter(posts::blog_id.eq(blog.id))
             .filter(posts::published.eq(true))
+            .filter(posts::visibility.le(i32::from(Visibility::Unlisted)))
             .count()
             .get_result(conn)
             .map_err(Error::from)
@@ -232,13 +495,211 @@ impl Post {
         posts::table
             .filter(posts::blog_id.eq(blog.id))
             .filter(posts::published.eq(true))
-            .order(posts::creation_date.desc())
+            .filter(posts::visibility.le(i32::from(Visibility::Unlisted)))
+            .order((
+                posts::pinned_at.is_null().asc(),
+                posts::pinned_at.desc(),
+                posts::creation_date.desc(),
+            ))
             .offset(min.into())
             .limit((max - min).into())
             .load::<Post>(conn)
             .map_err(Error::from)
     }
 
+    /// The name of the language of the post, as used by the search and timeline filters.
+    ///
+    /// It is the language its authors chose, or else the one detected in its content.
+    pub fn lang_name(&self) -> String {
+        match self.language {
+            Some(ref tag) => whatlang_lang(tag)
+                .map(|lang| lang.name().to_owned())
+                .unwrap_or_else(|| tag.clone()),
+            None => whatlang::detect(self.content.get())
+                .and_then(|i| {
+                    if i.is_reliable() {
+                        Some(i.lang())
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or(Lang::Eng)
+                .name()
+                .to_owned(),
+        }
+    }
+
+    /// The other published versions of this post, in other languages, that `user` can see.
+    pub fn list_translations(&self, conn: &Connection, user: Option<&User>) -> Result<Vec<Post>> {
+        let original = self.translation_of.unwrap_or(self.id);
+        let translations = posts::table
+            .filter(
+                posts::id
+                    .eq(original)
+                    .or(posts::translation_of.eq(original)),
+            )
+            .filter(posts::id.ne(self.id))
+            .filter(posts::published.eq(true))
+            .order(posts::creation_date.asc())
+            .load::<Post>(conn)?;
+        let mut visible = Vec::with_capacity(translations.len());
+        for translation in translations {
+            if translation.can_see(conn, user)? {
+                visible.push(translation);
+            }
+        }
+        Ok(visible)
+    }
+
+    /// Makes this post a translation of `original`, that must be in the same blog, or
+    /// removes it from its translations if `original` is `None`.
+    ///
+    /// All the translations of a post point to the first version, so the ones of this post
+    /// are moved too.
+    pub fn set_translation_of(&mut self, conn: &Connection, original: Option<&Post>) -> Result<()> {
+        let translation_of = match original {
+            Some(original) => {
+                let root = original.translation_of.unwrap_or(original.id);
+                if original.blog_id != self.blog_id || root == self.id {
+                    return Err(Error::InvalidValue);
+                }
+                Some(root)
+            }
+            None => None,
+        };
+        if translation_of == self.translation_of {
+            return Ok(());
+        }
+        if let Some(root) = translation_of {
+            diesel::update(posts::table.filter(posts::translation_of.eq(self.id)))
+                .set(posts::translation_of.eq(root))
+                .execute(conn)?;
+        }
+        diesel::update(&*self)
+            .set(posts::translation_of.eq(translation_of))
+            .execute(conn)?;
+        self.translation_of = translation_of;
+        Ok(())
+    }
+
+    /// The pinned posts of a blog, the most recently pinned first.
+    pub fn list_pinned_for_blog(conn: &Connection, blog: &Blog) -> Result<Vec<Post>> {
+        posts::table
+            .filter(posts::blog_id.eq(blog.id))
+            .filter(posts::published.eq(true))
+            .filter(posts::visibility.le(i32::from(Visibility::Unlisted)))
+            .filter(posts::pinned_at.is_not_null())
+            .order(posts::pinned_at.desc())
+            .load::<Post>(conn)
+            .map_err(Error::from)
+    }
+
+    /// The pinned posts of the blogs of an author, that they wrote.
+    pub fn list_pinned_for_author(conn: &Connection, author: &User) -> Result<Vec<Post>> {
+        use crate::schema::post_authors;
+
+        let posts = PostAuthor::belonging_to(author).select(post_authors::post_id);
+        posts::table
+            .filter(posts::id.eq_any(posts))
+            .filter(posts::published.eq(true))
+            .filter(posts::visibility.le(i32::from(Visibility::Unlisted)))
+            .filter(posts::pinned_at.is_not_null())
+            .order(posts::pinned_at.desc())
+            .load::<Post>(conn)
+            .map_err(Error::from)
+    }
+
+    /// Pins this post at the top of its blog.
+    ///
+    /// Only published posts can be pinned, and a blog can't have more than
+    /// [`MAX_PINNED_POSTS`] of them.
+    pub fn pin(&mut self, conn: &Connection) -> Result<()> {
+        if !self.published || self.visibility > i32::from(Visibility::Unlisted) {
+            return Err(Error::InvalidValue);
+        }
+        let pinned = posts::table
+            .filter(posts::blog_id.eq(self.blog_id))
+            .filter(posts::pinned_at.is_not_null())
+            .filter(posts::id.ne(self.id))
+            .count()
+            .get_result::<i64>(conn)?;
+        if pinned >= MAX_PINNED_POSTS {
+            return Err(Error::InvalidValue);
+        }
+        self.set_pinned_at(conn, Some(Utc::now().naive_utc()))
+    }
+
+    pub fn unpin(&mut self, conn: &Connection) -> Result<()> {
+        self.set_pinned_at(conn, None)
+    }
+
+    fn set_pinned_at(&mut self, conn: &Connection, pinned_at: Option<NaiveDateTime>) -> Result<()> {
+        diesel::update(&*self)
+            .set(posts::pinned_at.eq(pinned_at))
+            .execute(conn)?;
+        self.pinned_at = pinned_at;
+        Ok(())
+    }
+
+    /// Reads the `featured` collection of a remote actor, and pins the posts it contains.
+    ///
+    /// Only the posts that `owner` wrote or published can be pinned, and no more than
+    /// [`MAX_PINNED_POSTS`] of them. The ones that are pinned for now but that are
+    /// not in the collection anymore are unpinned.
+    pub(crate) fn sync_featured(conn: &Connection, url: &str, owner: &FeaturedOwner) -> Result<()> {
+        let res = get(url, Self::get_sender(), CONFIG.proxy().cloned())?;
+        let mut collection: serde_json::Value = serde_json::from_str(&res.text()?)?;
+        // the items may only be in the first page
+        if collection["orderedItems"].is_null() && collection["items"].is_null() {
+            collection = match collection["first"].clone() {
+                serde_json::Value::String(first) => {
+                    let res = get(&first, Self::get_sender(), CONFIG.proxy().cloned())?;
+                    serde_json::from_str(&res.text()?)?
+                }
+                first => first,
+            };
+        }
+        let items = collection["orderedItems"]
+            .as_array()
+            .or_else(|| collection["items"].as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        let now = Utc::now().naive_utc();
+        let mut featured = HashSet::new();
+        for item in items {
+            if featured.len() >= MAX_PINNED_POSTS as usize {
+                break;
+            }
+            let (id, object) = match item {
+                serde_json::Value::String(id) => (id, None),
+                item => match item["id"].as_str() {
+                    Some(id) => (id.to_owned(), serde_json::from_value(item).ok()),
+                    None => continue,
+                },
+            };
+            match Post::from_id(conn, &id, object, CONFIG.proxy()) {
+                Ok(mut post) => {
+                    if !owner.owns(conn, &post)? {
+                        warn!("{} is featured by an actor that didn't publish it", id);
+                        continue;
+                    }
+                    // keeps the order of the collection, the first item being the most recent
+                    let pinned_at = now - chrono::Duration::seconds(featured.len() as i64);
+                    post.set_pinned_at(conn, Some(pinned_at))?;
+                    featured.insert(post.id);
+                }
+                Err((_, e)) => warn!("Couldn't fetch featured post {}: {:?}", id, e),
+            }
+        }
+        for mut post in owner.pinned_posts(conn)? {
+            if !featured.contains(&post.id) {
+                post.unpin(conn)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn drafts_by_author(conn: &Connection, author: &User) -> Result<Vec<Post>> {
         use crate::schema::post_authors;
 
@@ -251,6 +712,67 @@ impl Post {
             .map_err(Error::from)
     }
 
+    /// Lists the unpublished posts whose scheduled publication date has passed.
+    pub fn list_due_for_publication(conn: &Connection) -> Result<Vec<Post>> {
+        posts::table
+            .filter(posts::published.eq(false))
+            .filter(posts::publish_at.le(Utc::now().naive_utc()))
+            .order(posts::publish_at.asc())
+            .load::<Post>(conn)
+            .map_err(Error::from)
+    }
+
+    /// Publishes every post whose scheduled publication date has passed.
+    ///
+    /// This is meant to be run periodically by a worker, and does everything
+    /// that would have been done if the post had been published directly:
+    /// mentions, federation, timelines, and (through `PostPublished`) search indexing.
+    pub fn publish_scheduled(conn: &Connection) -> Result<Vec<Post>> {
+        let mut published = vec![];
+        for post in Self::list_due_for_publication(conn)? {
+            let id = post.id;
+            match post.publish_now(conn) {
+                Ok(post) => published.push(post),
+                Err(e) => warn!("Failed to publish scheduled post {}: {:?}", id, e),
+            }
+        }
+        Ok(published)
+    }
+
+    fn publish_now(mut self, conn: &Connection) -> Result<Post> {
+        let blog = self.get_blog(conn)?;
+        self.published = true;
+        self.creation_date = self.publish_at.unwrap_or_else(|| Utc::now().naive_utc());
+        self.publish_at = None;
+        self.ap_url = Self::ap_url(blog, &self.slug);
+        let post = self.update(conn)?;
+
+        let (_, mentions, _) = md_to_html(
+            &post.source,
+            Some(&Instance::get_local()?.public_domain),
+            false,
+            None,
+            None,
+        );
+        let mentions = mentions
+            .into_iter()
+            .filter_map(|m| Mention::build_activity(conn, &m).ok())
+            .collect();
+        post.update_mentions(conn, mentions)?;
+
+        let author = post
+            .get_authors(conn)?
+            .into_iter()
+            .next()
+            .ok_or(Error::NotFound)?;
+        let act = post.create_activity(conn)?;
+        let dest = post.get_receivers(conn)?;
+        Delivery::broadcast(conn, &author, act, dest)?;
+
+        Timeline::add_to_all_timelines(conn, &post, Kind::Original)?;
+        Ok(post)
+    }
+
     pub fn ap_url(blog: Blog, slug: &str) -> String {
         ap_url(&format!(
             "{}/~/{}/{}/",
@@ -265,6 +787,13 @@ impl Post {
         title
     }
 
+    /// The canonical URL of this post, if it is safe to link to.
+    pub fn canonical_link(&self) -> Option<&str> {
+        self.canonical_url
+            .as_deref()
+            .filter(|url| is_valid_canonical_url(url))
+    }
+
     pub fn get_authors(&self, conn: &Connection) -> Result<Vec<User>> {
         use crate::schema::post_authors;
         use crate::schema::users;
@@ -310,6 +839,11 @@ impl Post {
         blog_fqn
     }
 
+    /// Removes the cached FQN of a blog, after it has been renamed.
+    pub(crate) fn forget_blog_fqn(blog_id: i32) {
+        BLOG_FQN_CACHE.lock().unwrap().remove(&blog_id);
+    }
+
     pub fn count_likes(&self, conn: &Connection) -> Result<i64> {
         use crate::schema::likes;
         likes::table
@@ -328,7 +862,67 @@ impl Post {
             .map_err(Error::from)
     }
 
+    pub fn visibility(&self) -> Visibility {
+        self.visibility.try_into().unwrap_or_default()
+    }
+
+    /// Whether this post is addressed to `user`, as an author of its blog
+    /// or (unless it is restricted to blog members) as a follower of one of its authors
+    /// who didn't block them.
+    pub fn is_addressed_to(&self, conn: &Connection, user: &User) -> Result<bool> {
+        if user.is_author_in(conn, &self.get_blog(conn)?)? {
+            return Ok(true);
+        }
+        if self.visibility() == Visibility::BlogMembers {
+            return Ok(false);
+        }
+        let authors = self.get_authors(conn)?;
+        for author in &authors {
+            if UserBlock::is_blocked(conn, author, user)? {
+                return Ok(false);
+            }
+        }
+        for author in authors {
+            if user.is_following(conn, author.id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether `user` (or an anonymous visitor, if `None`) is allowed to read this post.
+    pub fn can_see(&self, conn: &Connection, user: Option<&User>) -> Result<bool> {
+        match (self.visibility(), user) {
+            (Visibility::Public, _) | (Visibility::Unlisted, _) => Ok(true),
+            (_, Some(user)) => self.is_addressed_to(conn, user),
+            (_, None) => Ok(false),
+        }
+    }
+
+    /// The users activities about this post should be delivered to.
+    pub fn get_receivers(&self, conn: &Connection) -> Result<Vec<User>> {
+        match self.visibility() {
+            Visibility::Public | Visibility::Unlisted => User::one_by_instance(conn),
+            Visibility::Followers => {
+                let mut receivers = self.get_blog(conn)?.list_authors(conn)?;
+                for author in self.get_authors(conn)? {
+                    receivers.append(&mut author.get_followers(conn)?);
+                }
+                Ok(receivers)
+            }
+            Visibility::BlogMembers => self.get_blog(conn)?.list_authors(conn),
+        }
+    }
+
     pub fn get_receivers_urls(&self, conn: &Connection) -> Result<Vec<String>> {
+        if self.visibility() == Visibility::BlogMembers {
+            return Ok(self
+                .get_blog(conn)?
+                .list_authors(conn)?
+                .into_iter()
+                .map(|a| a.ap_url)
+                .collect());
+        }
         Ok(self
             .get_authors(conn)?
             .into_iter()
@@ -342,8 +936,25 @@ impl Post {
     }
 
     pub fn to_activity(&self, conn: &Connection) -> Result<LicensedArticle> {
-        let cc = self.get_receivers_urls(conn)?;
-        let to = vec![PUBLIC_VISIBILITY.to_string()];
+        let receivers = self.get_receivers_urls(conn)?;
+        let (to, cc) = match self.visibility() {
+            Visibility::Public => (vec![PUBLIC_VISIBILITY.to_string()], receivers),
+            Visibility::Unlisted => (receivers, vec![PUBLIC_VISIBILITY.to_string()]),
+            Visibility::Followers => (
+                // the followers collections let remote instances know who this post is for
+                self.get_authors(conn)?
+                    .into_iter()
+                    .map(|a| a.followers_endpoint)
+                    .chain(receivers)
+                    .collect(),
+                self.get_blog(conn)?
+                    .list_authors(conn)?
+                    .into_iter()
+                    .map(|a| a.ap_url)
+                    .collect(),
+            ),
+            Visibility::BlogMembers => (receivers, vec![]),
+        };
 
         let mut mentions_json = Mention::list_for_post(conn, self.id)?
             .into_iter()
@@ -354,6 +965,12 @@ impl Post {
             .map(|t| json!(t.to_activity().ok()))
             .collect::<Vec<serde_json::Value>>();
         mentions_json.append(&mut tags_json);
+        let mut emojis_json = Emoji::list_used_in(conn, &self.source)?
+            .into_iter()
+            .filter_map(|e| e.to_activity(conn).ok())
+            .map(|e| json!(e))
+            .collect::<Vec<serde_json::Value>>();
+        mentions_json.append(&mut emojis_json);
 
         let mut article = ApObject::new(Article::new());
         article.set_name(self.title.clone());
@@ -376,7 +993,16 @@ impl Post {
             OffsetDateTime::from_unix_timestamp_nanos(self.creation_date.timestamp_nanos().into())
                 .expect("OffsetDateTime"),
         );
-        article.set_summary(&*self.subtitle);
+        // other softwares show the summary of sensitive objects as their content warning
+        if self.sensitive {
+            article.set_summary(
+                self.content_warning
+                    .clone()
+                    .unwrap_or_else(|| self.subtitle.clone()),
+            );
+        } else {
+            article.set_summary(&*self.subtitle);
+        }
         article.set_many_tags(
             mentions_json
                 .iter()
@@ -397,7 +1023,31 @@ impl Post {
             article.set_icon(cover.into_any_base()?);
         }
 
+        let authors = self.get_authors(conn)?;
+        let attachments = referenced_media(&self.source)
+            .into_iter()
+            .filter_map(|id| Media::get(conn, id).ok())
+            // only the media the authors uploaded were rendered
+            .filter(|media| authors.iter().any(|author| author.id == media.owner_id))
+            .map(|media| media.to_attachment())
+            .collect::<Result<Vec<_>>>()?;
+        if !attachments.is_empty() {
+            article.set_many_attachments(attachments);
+        }
+
         article.set_url(self.ap_url.parse::<IriString>()?);
+        if let Some(canonical_url) = self.canonical_link() {
+            // the local address stays first: other Plume instances use it as the id of the post
+            article.add_url(AnyBase::from_arbitrary_json(serde_json::json!({
+                "type": "Link",
+                "href": canonical_url,
+                "rel": "canonical",
+                "mediaType": "text/html",
+            }))?);
+        }
+        if let Some(series) = Series::find_for_post(conn, self.id)? {
+            article.set_context(series.ap_url.parse::<IriString>()?);
+        }
         article.set_many_tos(
             to.into_iter()
                 .filter_map(|to| to.parse::<IriString>().ok())
@@ -411,7 +1061,22 @@ impl Post {
         let license = Licensed {
             license: Some(self.license.clone()),
         };
-        Ok(LicensedArticle::new(article, license))
+        let sensitive = SensitiveProperty {
+            sensitive: Some(self.sensitive),
+        };
+        let content_map = ContentMapProperty {
+            content_map: self.language.clone().map(|language| {
+                vec![(language, self.content.get().clone())]
+                    .into_iter()
+                    .collect()
+            }),
+        };
+        Ok(LicensedArticle::new(
+            article,
+            license,
+            sensitive,
+            content_map,
+        ))
     }
 
     pub fn create_activity(&self, conn: &Connection) -> Result<Create> {
@@ -584,7 +1249,7 @@ impl Post {
         Ok(act)
     }
 
-    fn publish_published(&self) {
+    pub(crate) fn publish_published(&self) {
         POST_CHAN.tell(
             Publish {
                 msg: PostPublished(Arc::new(self.clone())),
@@ -615,6 +1280,68 @@ impl Post {
     }
 }
 
+/// The ids of the actors or collections an object is addressed to.
+fn addressees(list: Option<&OneOrMany<AnyBase>>) -> Vec<String> {
+    list.map(|list| {
+        list.iter()
+            .filter_map(|addressee| addressee.id().map(|id| id.to_string()))
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Deduces the visibility of a remote post from the way it is addressed.
+fn addressing_visibility(to: &[String], cc: &[String], authors: &[User]) -> Visibility {
+    let is_public =
+        |url: &String| url == PUBLIC_VISIBILITY || url == "as:Public" || url == "Public";
+    if to.iter().any(is_public) {
+        Visibility::Public
+    } else if cc.iter().any(is_public) {
+        Visibility::Unlisted
+    } else if to
+        .iter()
+        .chain(cc.iter())
+        .any(|url| authors.iter().any(|a| &a.followers_endpoint == url))
+    {
+        Visibility::Followers
+    } else {
+        Visibility::BlogMembers
+    }
+}
+
+/// Finds the original address of an incoming article, in the links of its `url`.
+fn find_canonical_url(urls: Option<&OneOrMany<AnyBase>>) -> Option<String> {
+    urls?.iter().find_map(|url| {
+        let link = serde_json::to_value(url).ok()?;
+        let canonical = link["rel"] == "canonical"
+            || link["rel"]
+                .as_array()
+                .map_or(false, |rels| rels.iter().any(|rel| rel == "canonical"));
+        if canonical {
+            link["href"]
+                .as_str()
+                .filter(|href| is_valid_canonical_url(href))
+                .map(String::from)
+        } else {
+            None
+        }
+    })
+}
+
+/// The subtitle and the content warning of an incoming article.
+///
+/// The `summary` of sensitive articles is their content warning, and they have no subtitle.
+fn summary_and_warning(
+    sensitive: bool,
+    summary: Option<String>,
+) -> Result<(String, Option<String>)> {
+    if sensitive {
+        Ok((String::new(), summary.filter(|s| !s.is_empty())))
+    } else {
+        Ok((summary.ok_or(Error::MissingApProperty)?, None))
+    }
+}
+
 impl FromId<Connection> for Post {
     type Error = Error;
     type Object = LicensedArticle;
@@ -625,6 +1352,11 @@ impl FromId<Connection> for Post {
 
     fn from_activity(conn: &Connection, article: LicensedArticle) -> Result<Self> {
         let license = article.ext_one.license.unwrap_or_default();
+        let sensitive = article.ext_two.sensitive.unwrap_or(false);
+        let language = article
+            .ext_three
+            .language()
+            .filter(|language| is_valid_language(language));
         let article = article.inner;
 
         let (blog, authors) = article
@@ -651,6 +1383,12 @@ impl FromId<Connection> for Post {
                     (blog, authors)
                 }
             });
+        let visibility: i32 = addressing_visibility(
+            &addressees(article.to()),
+            &addressees(article.cc()),
+            &authors,
+        )
+        .into();
 
         let cover = article.icon().and_then(|icon| {
             icon.iter().next().and_then(|img| {
@@ -684,22 +1422,28 @@ impl FromId<Connection> for Post {
                 })
             })
             .unwrap_or_default();
+        let (subtitle, content_warning) = summary_and_warning(
+            sensitive,
+            article.summary().and_then(|summary| summary.to_as_string()),
+        )?;
+        let canonical_url = find_canonical_url(article.url());
+        let emojis = authors
+            .first()
+            .map(|author| Emoji::from_tags(conn, article.tag(), author))
+            .unwrap_or_default();
+        let content = SafeString::new(&Emoji::render(
+            conn,
+            &article
+                .content()
+                .and_then(|content| content.to_as_string())
+                .ok_or(Error::MissingApProperty)?,
+            &emojis,
+        ));
         let post = Post::from_db(conn, &ap_url)
             .and_then(|mut post| {
                 let mut updated = false;
 
                 let slug = Self::slug(&title);
-                let content = SafeString::new(
-                    &article
-                        .content()
-                        .and_then(|content| content.to_as_string())
-                        .ok_or(Error::MissingApProperty)?,
-                );
-                let subtitle = article
-                    .summary()
-                    .and_then(|summary| summary.to_as_string())
-                    .ok_or(Error::MissingApProperty)?;
-
                 if post.slug != slug {
                     post.slug = slug.to_string();
                     updated = true;
@@ -709,7 +1453,7 @@ impl FromId<Connection> for Post {
                     updated = true;
                 }
                 if post.content != content {
-                    post.content = content;
+                    post.content = content.clone();
                     updated = true;
                 }
                 if post.license != license {
@@ -717,7 +1461,20 @@ impl FromId<Connection> for Post {
                     updated = true;
                 }
                 if post.subtitle != subtitle {
-                    post.subtitle = subtitle;
+                    post.subtitle = subtitle.clone();
+                    updated = true;
+                }
+                if post.language != language {
+                    post.language = language.clone();
+                    updated = true;
+                }
+                if post.canonical_url != canonical_url {
+                    post.canonical_url = canonical_url.clone();
+                    updated = true;
+                }
+                if post.sensitive != sensitive || post.content_warning != content_warning {
+                    post.sensitive = sensitive;
+                    post.content_warning = content_warning.clone();
                     updated = true;
                 }
                 if post.source != source {
@@ -728,6 +1485,10 @@ impl FromId<Connection> for Post {
                     post.cover_id = cover;
                     updated = true;
                 }
+                if post.visibility != visibility {
+                    post.visibility = visibility;
+                    updated = true;
+                }
 
                 if updated {
                     post.update(conn)?;
@@ -742,12 +1503,7 @@ impl FromId<Connection> for Post {
                         blog_id: blog.ok_or(Error::NotFound)?.id,
                         slug: Self::slug(&title).to_string(),
                         title,
-                        content: SafeString::new(
-                            &article
-                                .content()
-                                .and_then(|content| content.to_as_string())
-                                .ok_or(Error::MissingApProperty)?,
-                        ),
+                        content,
                         published: true,
                         license,
                         // FIXME: This is wrong: with this logic, we may use the display URL as the AP ID. We need two different fields
@@ -762,12 +1518,16 @@ impl FromId<Connection> for Post {
                             )
                             .unwrap()
                         }),
-                        subtitle: article
-                            .summary()
-                            .and_then(|summary| summary.to_as_string())
-                            .ok_or(Error::MissingApProperty)?,
+                        subtitle,
                         source,
                         cover_id: cover,
+                        publish_at: None,
+                        visibility,
+                        sensitive,
+                        content_warning,
+                        canonical_url,
+                        language,
+                        translation_of: None,
                     },
                 )
                 .and_then(|post| {
@@ -786,7 +1546,7 @@ impl FromId<Connection> for Post {
             })?;
 
         // save mentions and tags
-        let mut hashtags = md_to_html(&post.source, None, false, None)
+        let mut hashtags = md_to_html(&post.source, None, false, None, None)
             .2
             .into_iter()
             .collect::<HashSet<_>>();
@@ -857,6 +1617,10 @@ pub struct PostUpdate {
     pub source: Option<String>,
     pub license: Option<String>,
     pub tags: Option<serde_json::Value>,
+    pub sensitive: Option<bool>,
+    pub content_warning: Option<String>,
+    pub canonical_url: Option<String>,
+    pub language: Option<String>,
 }
 
 impl FromId<Connection> for PostUpdate {
@@ -901,7 +1665,18 @@ impl FromId<Connection> for PostUpdate {
             tags: updated
                 .tag()
                 .and_then(|tags| serde_json::to_value(tags).ok()),
+            sensitive: updated.ext_two.sensitive,
+            content_warning: None,
+            canonical_url: find_canonical_url(updated.url()),
+            language: updated
+                .ext_three
+                .language()
+                .filter(|language| is_valid_language(language)),
         };
+        if post_update.sensitive == Some(true) {
+            post_update.content_warning = post_update.subtitle.take().filter(|s| !s.is_empty());
+            post_update.subtitle = Some(String::new());
+        }
         post_update.cover = updated.ap_object_ref().icon().and_then(|img| {
             img.iter()
                 .next()
@@ -942,7 +1717,12 @@ impl AsObject<User, Update, &Connection> for PostUpdate {
         }
 
         if let Some(content) = self.content {
-            post.content = SafeString::new(&content);
+            let tags = self
+                .tags
+                .clone()
+                .and_then(|tags| serde_json::from_value::<OneOrMany<AnyBase>>(tags).ok());
+            let emojis = Emoji::from_tags(conn, tags.as_ref(), &actor);
+            post.content = SafeString::new(&Emoji::render(conn, &content, &emojis));
         }
 
         if let Some(subtitle) = self.subtitle {
@@ -959,7 +1739,15 @@ impl AsObject<User, Update, &Connection> for PostUpdate {
             post.license = license;
         }
 
-        let mut txt_hashtags = md_to_html(&post.source, None, false, None)
+        if let Some(sensitive) = self.sensitive {
+            post.sensitive = sensitive;
+            post.content_warning = self.content_warning;
+        }
+
+        post.canonical_url = self.canonical_url;
+        post.language = self.language;
+
+        let mut txt_hashtags = md_to_html(&post.source, None, false, None, None)
             .2
             .into_iter()
             .collect::<HashSet<_>>();
@@ -1005,6 +1793,41 @@ impl IntoId for Post {
     }
 }
 
+/// A remote actor that has a `featured` collection.
+#[derive(Clone, Debug)]
+pub enum FeaturedOwner {
+    Author(Arc<User>),
+    Blog(Arc<Blog>),
+}
+
+impl FeaturedOwner {
+    /// Reads the `featured` collection at `url` in the background, and updates the
+    /// pinned posts of this actor to match it.
+    pub(crate) fn featured_found(self, url: String) {
+        USER_CHAN.tell(
+            Publish {
+                msg: RemoteFeaturedFound(self, url),
+                topic: "user.remote_featured_found".into(),
+            },
+            None,
+        )
+    }
+
+    fn owns(&self, conn: &Connection, post: &Post) -> Result<bool> {
+        match self {
+            FeaturedOwner::Author(user) => post.is_author(conn, user.id),
+            FeaturedOwner::Blog(blog) => Ok(post.blog_id == blog.id),
+        }
+    }
+
+    fn pinned_posts(&self, conn: &Connection) -> Result<Vec<Post>> {
+        match self {
+            FeaturedOwner::Author(user) => Post::list_pinned_for_author(conn, user),
+            FeaturedOwner::Blog(blog) => Post::list_pinned_for_blog(conn, blog),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum PostEvent {
     PostPublished(Arc<Post>),
@@ -1073,6 +1896,13 @@ mod tests {
                     subtitle: "Testing".into(),
                     source: "Hello".into(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -1102,6 +1932,62 @@ mod tests {
         });
     }
 
+    #[test]
+    fn publish_scheduled() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (_, users, blogs) = fill_database(conn);
+            let mut scheduled = vec![];
+            for (slug, delay) in &[("due", -60), ("later", 3600)] {
+                let post = Post::insert(
+                    conn,
+                    NewPost {
+                        blog_id: blogs[0].id,
+                        slug: slug.to_string(),
+                        title: slug.to_string(),
+                        content: SafeString::new("Hello"),
+                        published: false,
+                        license: "WTFPL".to_string(),
+                        creation_date: None,
+                        ap_url: String::new(),
+                        subtitle: "Scheduled".into(),
+                        source: "Hello".into(),
+                        cover_id: None,
+                        publish_at: Some(
+                            Utc::now().naive_utc() + chrono::Duration::seconds(*delay),
+                        ),
+                        visibility: Visibility::Public.into(),
+                        sensitive: false,
+                        content_warning: None,
+                        canonical_url: None,
+                        language: None,
+                        translation_of: None,
+                    },
+                )?;
+                PostAuthor::insert(
+                    conn,
+                    NewPostAuthor {
+                        post_id: post.id,
+                        author_id: users[0].id,
+                    },
+                )?;
+                scheduled.push(post);
+            }
+
+            let published = Post::publish_scheduled(conn)?;
+            assert_eq!(published.len(), 1);
+            assert_eq!(published[0].id, scheduled[0].id);
+
+            let due = Post::get(conn, scheduled[0].id)?;
+            assert!(due.published);
+            assert!(due.publish_at.is_none());
+            assert!(!Post::get(conn, scheduled[1].id)?.published);
+            assert!(Post::publish_scheduled(conn)?.is_empty());
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn to_activity() {
         let conn = db();
@@ -1117,6 +2003,7 @@ mod tests {
                 "license": "WTFPL",
                 "name": "Testing",
                 "published": format_datetime(&post.creation_date),
+                "sensitive": false,
                 "source": {
                     "content": "Hello",
                     "mediaType": "text/markdown"
@@ -1140,6 +2027,294 @@ mod tests {
         });
     }
 
+    #[test]
+    fn slug_history() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, _users, blogs) = fill_database(&conn);
+            let mut post = posts[0].clone();
+            post.slug = "renamed".to_owned();
+            let post = post.update(&conn)?;
+            assert_eq!(post.ap_url, posts[0].ap_url);
+            assert!(Post::find_by_slug(&conn, "testing", blogs[0].id).is_err());
+            assert_eq!(
+                PostSlug::find_post(&conn, blogs[0].id, "testing")?.id,
+                post.id
+            );
+
+            let mut post = post;
+            post.slug = "testing".to_owned();
+            let post = post.update(&conn)?;
+            assert!(PostSlug::find_post(&conn, blogs[0].id, "testing").is_err());
+            assert_eq!(
+                PostSlug::find_post(&conn, blogs[0].id, "renamed")?.id,
+                post.id
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn pin() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, blogs) = fill_database(&conn);
+            let others = (0..MAX_PINNED_POSTS)
+                .map(|i| {
+                    Post::insert(
+                        &conn,
+                        NewPost {
+                            blog_id: blogs[0].id,
+                            slug: format!("other-{}", i),
+                            title: format!("Other {}", i),
+                            content: SafeString::new("Hello"),
+                            published: true,
+                            license: "WTFPL".to_string(),
+                            creation_date: None,
+                            ap_url: String::new(),
+                            subtitle: String::new(),
+                            source: "Hello".into(),
+                            cover_id: None,
+                            publish_at: None,
+                            visibility: Visibility::Public.into(),
+                            sensitive: false,
+                            content_warning: None,
+                            canonical_url: None,
+                            language: None,
+                            translation_of: None,
+                        },
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut post = posts[0].clone();
+            post.pin(&conn)?;
+            assert_eq!(Post::blog_page(&conn, &blogs[0], (0, 10))?[0].id, post.id);
+            assert_eq!(
+                Post::list_pinned_for_author(&conn, &users[0])?
+                    .into_iter()
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>(),
+                vec![post.id]
+            );
+
+            for other in &others[1..] {
+                other.clone().pin(&conn)?;
+            }
+            assert_eq!(
+                Post::list_pinned_for_blog(&conn, &blogs[0])?.len() as i64,
+                MAX_PINNED_POSTS
+            );
+            assert!(others[0].clone().pin(&conn).is_err());
+
+            post.unpin(&conn)?;
+            others[0].clone().pin(&conn)?;
+            assert!(Post::list_pinned_for_author(&conn, &users[0])?.is_empty());
+            assert!(Post::get(&conn, post.id)?.pinned_at.is_none());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn featured_owner() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, blogs) = fill_database(&conn);
+            let owns = |owner: FeaturedOwner| owner.owns(&conn, &posts[0]);
+            assert!(owns(FeaturedOwner::Author(Arc::new(users[0].clone())))?);
+            assert!(!owns(FeaturedOwner::Author(Arc::new(users[1].clone())))?);
+            assert!(owns(FeaturedOwner::Blog(Arc::new(blogs[0].clone())))?);
+            assert!(!owns(FeaturedOwner::Blog(Arc::new(blogs[1].clone())))?);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn canonical_url() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (mut post, _mention, _posts, _users, _blogs) = prepare_activity(&conn);
+
+            post.canonical_url = Some("https://example.com/original".to_owned());
+            let act = post.to_activity(&conn)?;
+            assert_eq!(
+                to_value(&act)?["url"],
+                json!([
+                    "https://plu.me/~/BlogName/testing",
+                    {
+                        "type": "Link",
+                        "href": "https://example.com/original",
+                        "rel": "canonical",
+                        "mediaType": "text/html",
+                    }
+                ])
+            );
+            assert_eq!(
+                find_canonical_url(act.url()),
+                Some("https://example.com/original".to_owned())
+            );
+
+            post.canonical_url = Some("javascript:alert(1)".to_owned());
+            assert_eq!(post.canonical_link(), None);
+            let act = post.to_activity(&conn)?;
+            assert_eq!(find_canonical_url(act.url()), None);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn translations() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, blogs) = fill_database(&conn);
+            let mut original = posts[0].clone();
+            original.language = Some("fr".to_owned());
+            original.update(&conn)?;
+            let mut translation = Post::insert(
+                &conn,
+                NewPost {
+                    blog_id: blogs[0].id,
+                    slug: "testing-en".to_owned(),
+                    title: "Testing".to_owned(),
+                    content: SafeString::new("Hello"),
+                    published: true,
+                    license: "WTFPL".to_string(),
+                    creation_date: None,
+                    ap_url: String::new(),
+                    subtitle: String::new(),
+                    source: "Hello".into(),
+                    cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: Some("en".to_owned()),
+                    translation_of: None,
+                },
+            )?;
+
+            assert!(original
+                .set_translation_of(&conn, Some(&original.clone()))
+                .is_err());
+            translation.set_translation_of(&conn, Some(&original))?;
+            assert_eq!(translation.translation_of, Some(original.id));
+            assert!(original
+                .set_translation_of(&conn, Some(&translation))
+                .is_err());
+            assert_eq!(
+                original
+                    .list_translations(&conn, None)?
+                    .into_iter()
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>(),
+                vec![translation.id]
+            );
+            assert_eq!(
+                translation
+                    .list_translations(&conn, None)?
+                    .into_iter()
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>(),
+                vec![original.id]
+            );
+            assert_eq!(translation.lang_name(), "English");
+
+            diesel::update(&translation)
+                .set(posts::visibility.eq(i32::from(Visibility::BlogMembers)))
+                .execute(&*conn)?;
+            assert!(original.list_translations(&conn, None)?.is_empty());
+            assert_eq!(
+                original
+                    .list_translations(&conn, Some(&users[0]))?
+                    .into_iter()
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>(),
+                vec![translation.id]
+            );
+
+            let act = to_value(original.to_activity(&conn)?)?;
+            assert_eq!(act["contentMap"], json!({ "fr": original.content.get() }));
+
+            assert!(is_valid_language("pt-BR"));
+            assert!(!is_valid_language("klingon"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn content_warning() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (mut post, _mention, _posts, _users, _blogs) = prepare_activity(&conn);
+
+            post.sensitive = true;
+            let act = to_value(post.to_activity(&conn)?)?;
+            assert_eq!(act["sensitive"], json!(true));
+            assert_eq!(act["summary"], json!("Bye"));
+
+            post.content_warning = Some("Spoilers".to_owned());
+            let act = to_value(post.to_activity(&conn)?)?;
+            assert_eq!(act["summary"], json!("Spoilers"));
+
+            assert_eq!(
+                summary_and_warning(true, Some("Spoilers".to_owned()))?,
+                (String::new(), Some("Spoilers".to_owned()))
+            );
+            assert_eq!(
+                summary_and_warning(false, Some("Bye".to_owned()))?,
+                ("Bye".to_owned(), None)
+            );
+            assert!(summary_and_warning(false, None).is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn visibility() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (mut post, _mention, _posts, users, _blogs) = prepare_activity(&conn);
+
+            post.visibility = Visibility::Unlisted.into();
+            let act = to_value(post.to_activity(&conn)?)?;
+            assert_eq!(act["to"], json!([]));
+            assert_eq!(act["cc"], json!([PUBLIC_VISIBILITY]));
+            assert!(post.can_see(&conn, None)?);
+
+            post.visibility = Visibility::Followers.into();
+            let act = to_value(post.to_activity(&conn)?)?;
+            assert_eq!(act["to"], json!([&users[0].followers_endpoint]));
+            assert!(!post.can_see(&conn, None)?);
+            assert!(post.can_see(&conn, Some(&users[1]))?);
+
+            post.visibility = Visibility::BlogMembers.into();
+            assert!(post.can_see(&conn, Some(&users[0]))?);
+            assert!(!post.can_see(&conn, Some(&users[2]))?);
+
+            let public = vec![PUBLIC_VISIBILITY.to_owned()];
+            let followers = vec![users[0].followers_endpoint.clone()];
+            assert_eq!(
+                addressing_visibility(&public, &followers, &users),
+                Visibility::Public
+            );
+            assert_eq!(
+                addressing_visibility(&followers, &public, &users),
+                Visibility::Unlisted
+            );
+            assert_eq!(
+                addressing_visibility(&followers, &[], &users),
+                Visibility::Followers
+            );
+            assert_eq!(
+                addressing_visibility(&[users[1].ap_url.clone()], &[], &users),
+                Visibility::BlogMembers
+            );
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn create_activity() {
         let conn = db();
@@ -1159,6 +2334,7 @@ mod tests {
                     "license": "WTFPL",
                     "name": "Testing",
                     "published": format_datetime(&post.creation_date),
+                    "sensitive": false,
                     "source": {
                         "content": "Hello",
                         "mediaType": "text/markdown"
@@ -1204,6 +2380,7 @@ mod tests {
                     "license": "WTFPL",
                     "name": "Testing",
                     "published": format_datetime(&post.creation_date),
+                    "sensitive": false,
                     "source": {
                         "content": "Hello",
                         "mediaType": "text/markdown"
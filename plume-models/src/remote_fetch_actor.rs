@@ -1,5 +1,6 @@
 use crate::{
     db_conn::{DbConn, DbPool},
+    emojis::Emoji,
     follows,
     posts::Post,
     users::{User, UserEvent},
@@ -63,6 +64,30 @@ impl Actor for RemoteFetchActor {
                     error!("Failed to get database connection");
                 }
             },
+            RemoteFeaturedFound(owner, url) => match self.conn.get() {
+                Ok(conn) => {
+                    let conn = DbConn(conn);
+                    if let Err(e) = Post::sync_featured(&conn, &url, &owner) {
+                        warn!("Couldn't read the featured posts at {}: {:?}", url, e);
+                    }
+                }
+                _ => {
+                    error!("Failed to get database connection");
+                }
+            },
+            RemoteEmojiFound(id, author) => match self.conn.get() {
+                Ok(conn) => {
+                    let conn = DbConn(conn);
+                    if let Err(e) =
+                        Emoji::get(&conn, id).and_then(|emoji| emoji.download(&conn, &author))
+                    {
+                        warn!("Couldn't download the emoji {}: {:?}", id, e);
+                    }
+                }
+                _ => {
+                    error!("Failed to get database connection");
+                }
+            },
         }
     }
 }
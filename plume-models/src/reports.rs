@@ -0,0 +1,323 @@
+use crate::{
+    ap_url, comments::Comment, deliveries::Delivery, instance::Instance, posts::Post,
+    schema::reports, users::User, Connection, Error, Result, CONFIG,
+};
+use activitystreams::{activity::Flag, iri_string::types::IriString, prelude::*};
+use chrono::NaiveDateTime;
+use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl, SaveChangesDsl};
+use openssl::{hash::MessageDigest, pkey::PKey, rsa::Rsa, sign};
+use plume_common::activity_pub::{
+    inbox::AsActor,
+    request,
+    sign::{Error as SignError, Result as SignResult, Signer},
+};
+
+/// A report sent to the moderators about a user, or one of their posts or comments.
+#[derive(Clone, Queryable, Identifiable, AsChangeset)]
+pub struct Report {
+    pub id: i32,
+    /// `None` if the report comes from another instance that didn't tell who sent it
+    pub reporter_id: Option<i32>,
+    /// The reported user, or the author of the reported post or comment
+    pub user_id: i32,
+    pub post_id: Option<i32>,
+    pub comment_id: Option<i32>,
+    pub reason: String,
+    /// Whether a `Flag` was sent to the instance of the reported user
+    pub forwarded: bool,
+    pub resolved: bool,
+    pub ap_url: String,
+    pub creation_date: NaiveDateTime,
+}
+
+#[derive(Default, Insertable)]
+#[table_name = "reports"]
+pub struct NewReport {
+    pub reporter_id: Option<i32>,
+    pub user_id: i32,
+    pub post_id: Option<i32>,
+    pub comment_id: Option<i32>,
+    pub reason: String,
+    pub ap_url: String,
+}
+
+impl Report {
+    insert!(
+        reports,
+        NewReport,
+        |inserted, conn| if inserted.ap_url.is_empty() {
+            inserted.ap_url = ap_url(&format!("{}/reports/{}", CONFIG.base_url, inserted.id));
+            inserted.save_changes(conn).map_err(Error::from)
+        } else {
+            Ok(inserted)
+        }
+    );
+    get!(reports);
+    find_by!(reports, find_by_ap_url, ap_url as &str);
+
+    /// The reports that still need to be looked at, oldest first.
+    pub fn page_unresolved(conn: &Connection, (min, max): (i32, i32)) -> Result<Vec<Report>> {
+        reports::table
+            .filter(reports::resolved.eq(false))
+            .order(reports::creation_date.asc())
+            .offset(min.into())
+            .limit((max - min).into())
+            .load::<Report>(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn count_unresolved(conn: &Connection) -> Result<i64> {
+        reports::table
+            .filter(reports::resolved.eq(false))
+            .count()
+            .get_result(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn resolve(&mut self, conn: &Connection) -> Result<()> {
+        self.resolved = true;
+        self.save_changes::<Report>(conn)?;
+        Ok(())
+    }
+
+    pub fn get_reporter(&self, conn: &Connection) -> Result<Option<User>> {
+        self.reporter_id.map(|id| User::get(conn, id)).transpose()
+    }
+
+    pub fn get_user(&self, conn: &Connection) -> Result<User> {
+        User::get(conn, self.user_id)
+    }
+
+    pub fn get_post(&self, conn: &Connection) -> Result<Option<Post>> {
+        self.post_id.map(|id| Post::get(conn, id)).transpose()
+    }
+
+    pub fn get_comment(&self, conn: &Connection) -> Result<Option<Comment>> {
+        self.comment_id.map(|id| Comment::get(conn, id)).transpose()
+    }
+
+    /// Sends this report to the instance of the reported user, if it is a remote one.
+    ///
+    /// The `Flag` comes from the instance itself, so that the reporter stays anonymous.
+    pub fn forward(&mut self, conn: &Connection) -> Result<()> {
+        let user = self.get_user(conn)?;
+        if user.is_local() || self.forwarded {
+            return Ok(());
+        }
+        let sender = Instance::get_local_instance_user().ok_or(Error::NotFound)?;
+        Delivery::broadcast(conn, sender, self.to_activity(conn)?, vec![user])?;
+        self.forwarded = true;
+        self.save_changes::<Report>(conn)?;
+        Ok(())
+    }
+
+    pub fn to_activity(&self, conn: &Connection) -> Result<Flag> {
+        let sender = Instance::get_local_instance_user().ok_or(Error::NotFound)?;
+        let mut objects = vec![self.get_user(conn)?.ap_url.parse::<IriString>()?];
+        if let Some(post) = self.get_post(conn)? {
+            objects.push(post.ap_url.parse::<IriString>()?);
+        }
+        if let Some(comment) = self.get_comment(conn)? {
+            objects.push(
+                comment
+                    .ap_url
+                    .ok_or(Error::MissingApProperty)?
+                    .parse::<IriString>()?,
+            );
+        }
+
+        let mut act = Flag::new(sender.ap_url.parse::<IriString>()?, objects[0].clone());
+        act.set_many_objects(objects);
+        act.set_id(self.ap_url.parse::<IriString>()?);
+        act.set_content(self.reason.clone());
+
+        Ok(act)
+    }
+
+    /// Saves an incoming `Flag` activity as a report.
+    ///
+    /// Its objects can be a list of users, posts and comments, and they are only looked for in
+    /// the database: a report about something this instance doesn't know is ignored.
+    pub fn from_flag(conn: &Connection, act: &serde_json::Value) -> Result<Report> {
+        let id = act["id"].as_str().ok_or(Error::MissingApProperty)?;
+        if let Ok(report) = Report::find_by_ap_url(conn, id) {
+            return Ok(report);
+        }
+
+        let objects: Vec<&serde_json::Value> = match &act["object"] {
+            serde_json::Value::Array(objects) => objects.iter().collect(),
+            object => vec![object],
+        };
+        let mut report = NewReport {
+            reporter_id: act["actor"]
+                .as_str()
+                .and_then(|actor| User::find_by_ap_url(conn, actor).ok())
+                .map(|reporter| reporter.id),
+            reason: act["content"].as_str().unwrap_or_default().to_owned(),
+            ap_url: id.to_owned(),
+            ..NewReport::default()
+        };
+        let mut user_id = None;
+        for object in objects {
+            let object = match object.as_str().or_else(|| object["id"].as_str()) {
+                Some(object) => object,
+                None => continue,
+            };
+            if let Ok(user) = User::find_by_ap_url(conn, object) {
+                user_id = Some(user.id);
+            } else if let Ok(post) = Post::find_by_ap_url(conn, object) {
+                user_id = user_id.or(post.get_authors(conn)?.first().map(|a| a.id));
+                report.post_id = Some(post.id);
+            } else if let Ok(comment) = Comment::find_by_ap_url(conn, object) {
+                user_id = user_id.or(Some(comment.author_id));
+                report.comment_id = Some(comment.id);
+            }
+        }
+        report.user_id = user_id.ok_or(Error::NotFound)?;
+        Report::insert(conn, report)
+    }
+}
+
+/// A remote actor that is not a user, like the `Application` actor of a Mastodon instance,
+/// that sends the reports of its users.
+#[derive(Debug)]
+pub struct ServiceActor {
+    pub ap_url: String,
+    public_key: String,
+}
+
+impl ServiceActor {
+    /// Fetches an `Application` or a `Service` actor, to check the signature of its activities.
+    pub fn fetch(id: &str) -> Result<ServiceActor> {
+        let sender = Instance::get_local_instance_user().ok_or(Error::NotFound)?;
+        let res = request::get(id, sender, CONFIG.proxy().cloned())?;
+        let actor: serde_json::Value = serde_json::from_str(&res.text()?)?;
+        ServiceActor::from_json(id, &actor)
+    }
+
+    fn from_json(id: &str, actor: &serde_json::Value) -> Result<ServiceActor> {
+        if actor["id"] != id || !(actor["type"] == "Application" || actor["type"] == "Service") {
+            return Err(Error::InvalidValue);
+        }
+        Ok(ServiceActor {
+            ap_url: id.to_owned(),
+            public_key: actor["publicKey"]["publicKeyPem"]
+                .as_str()
+                .ok_or(Error::MissingApProperty)?
+                .to_owned(),
+        })
+    }
+}
+
+impl Signer for ServiceActor {
+    fn get_key_id(&self) -> String {
+        format!("{}#main-key", self.ap_url)
+    }
+
+    fn sign(&self, _to_sign: &str) -> SignResult<Vec<u8>> {
+        // Only local actors can sign
+        Err(SignError())
+    }
+
+    fn verify(&self, data: &str, signature: &[u8]) -> SignResult<bool> {
+        let key = PKey::from_rsa(Rsa::public_key_from_pem(self.public_key.as_ref())?)?;
+        let mut verifier = sign::Verifier::new(MessageDigest::sha256(), &key)?;
+        verifier.update(data.as_bytes())?;
+        verifier.verify(signature).map_err(SignError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inbox::tests::fill_database, tests::db};
+    use diesel::Connection;
+    use serde_json::json;
+
+    #[test]
+    fn queue() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, _) = fill_database(conn);
+            let mut report = Report::insert(
+                conn,
+                NewReport {
+                    reporter_id: Some(users[1].id),
+                    user_id: users[0].id,
+                    post_id: Some(posts[0].id),
+                    reason: "Spam".to_owned(),
+                    ..NewReport::default()
+                },
+            )?;
+            assert!(report.ap_url.ends_with(&format!("/reports/{}", report.id)));
+            assert_eq!(Report::count_unresolved(conn)?, 1);
+
+            report.resolve(conn)?;
+            assert_eq!(Report::count_unresolved(conn)?, 0);
+            assert!(Report::page_unresolved(conn, (0, 10))?.is_empty());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn incoming_flag() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, _) = fill_database(conn);
+            let flag = json!({
+                "id": "https://remote.example/flags/1",
+                "type": "Flag",
+                "actor": "https://remote.example/actor",
+                "object": [users[0].ap_url, posts[0].ap_url],
+                "content": "Not nice",
+            });
+            let report = Report::from_flag(conn, &flag)?;
+            assert_eq!(report.reporter_id, None);
+            assert_eq!(report.user_id, users[0].id);
+            assert_eq!(report.post_id, Some(posts[0].id));
+            assert_eq!(report.reason, "Not nice");
+
+            // The same flag is only saved once
+            assert_eq!(Report::from_flag(conn, &flag)?.id, report.id);
+            assert!(Report::from_flag(
+                conn,
+                &json!({
+                    "id": "https://remote.example/flags/2",
+                    "type": "Flag",
+                    "actor": "https://remote.example/actor",
+                    "object": "https://remote.example/unknown",
+                })
+            )
+            .is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn service_actor() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (_, users, _) = fill_database(conn);
+            let mut actor = json!({
+                "id": "https://remote.example/actor",
+                "type": "Application",
+                "publicKey": {
+                    "id": "https://remote.example/actor#main-key",
+                    "owner": "https://remote.example/actor",
+                    "publicKeyPem": users[0].public_key,
+                },
+            });
+            let service = ServiceActor::from_json("https://remote.example/actor", &actor)?;
+            let signature = users[0].sign("Not nice")?;
+            assert!(service.verify("Not nice", &signature)?);
+            assert!(!service.verify("Nice", &signature)?);
+            assert!(service.sign("Not nice").is_err());
+
+            // Users and other instances can't pretend to be this actor
+            assert!(ServiceActor::from_json("https://other.example/actor", &actor).is_err());
+            actor["type"] = json!("Person");
+            assert!(ServiceActor::from_json("https://remote.example/actor", &actor).is_err());
+            Ok(())
+        });
+    }
+}
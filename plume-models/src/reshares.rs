@@ -1,6 +1,6 @@
 use crate::{
-    instance::Instance, notifications::*, posts::Post, schema::reshares, timeline::*, users::User,
-    Connection, Error, Result, CONFIG,
+    instance::Instance, notifications::*, posts::Post, schema::reshares, timeline::*,
+    user_blocks::UserBlock, users::User, Connection, Error, Result, CONFIG,
 };
 use activitystreams::{
     activity::{ActorAndObjectRef, Announce, Undo},
@@ -82,8 +82,9 @@ impl Reshare {
 
     pub fn notify(&self, conn: &Connection) -> Result<()> {
         let post = self.get_post(conn)?;
+        let user = self.get_user(conn)?;
         for author in post.get_authors(conn)? {
-            if author.is_local() {
+            if author.is_local() && !UserBlock::is_blocked(conn, &author, &user)? {
                 Notification::insert(
                     conn,
                     NewNotification {
@@ -14,11 +14,42 @@ use std::{
     ops::Deref,
 };
 
+/// The MathML elements produced when rendering formulas in Markdown
+const MATHML_TAGS: &[&str] = &[
+    "math",
+    "semantics",
+    "annotation",
+    "mrow",
+    "mi",
+    "mn",
+    "mo",
+    "ms",
+    "mtext",
+    "mspace",
+    "msup",
+    "msub",
+    "msubsup",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mover",
+    "munder",
+    "munderover",
+    "mtable",
+    "mtr",
+    "mtd",
+    "mstyle",
+    "menclose",
+    "mpadded",
+    "mphantom",
+];
+
 lazy_static! {
     static ref CLEAN: Builder<'static> = {
         let mut b = Builder::new();
         b.add_generic_attributes(&["id", "dir"])
             .add_tags(&["iframe", "video", "audio", "label", "input"])
+            .add_tags(MATHML_TAGS)
             .id_prefix(Some("postcontent-"))
             .url_relative(UrlRelative::Custom(Box::new(url_add_prefix)))
             .add_tag_attributes(
@@ -29,6 +60,35 @@ lazy_static! {
             .add_tag_attributes("audio", ["src", "title", "controls"].iter())
             .add_tag_attributes("label", ["for"].iter())
             .add_tag_attributes("input", ["type", "checked"].iter())
+            .add_tag_attributes("math", ["display", "alttext"].iter())
+            .add_tag_attributes("mi", ["mathvariant"].iter())
+            .add_tag_attributes(
+                "mo",
+                [
+                    "accent",
+                    "fence",
+                    "form",
+                    "largeop",
+                    "lspace",
+                    "movablelimits",
+                    "rspace",
+                    "separator",
+                    "stretchy",
+                ]
+                .iter(),
+            )
+            .add_tag_attributes("mfrac", ["linethickness"].iter())
+            .add_tag_attributes("mover", ["accent"].iter())
+            .add_tag_attributes("munder", ["accentunder"].iter())
+            .add_tag_attributes("mspace", ["width"].iter())
+            .add_tag_attributes(
+                "mstyle",
+                ["displaystyle", "scriptlevel", "mathvariant"].iter(),
+            )
+            .add_tag_attributes("mtable", ["columnalign"].iter())
+            .add_tag_attributes("menclose", ["notation"].iter())
+            .add_tag_attributes("annotation", ["encoding"].iter())
+            .add_allowed_classes("img", ["emoji"].iter())
             .add_allowed_classes("input", ["cw-checkbox"].iter())
             .add_allowed_classes(
                 "span",
@@ -64,7 +124,8 @@ lazy_static! {
             )
             // Related to https://github.com/Plume-org/Plume/issues/637
             .add_allowed_classes("sup", ["footnote-reference", "footnote-definition-label"].iter())
-            .add_allowed_classes("div", ["footnote-definition"].iter())
+            .add_allowed_classes("div", ["footnote-definition", "media-gallery"].iter())
+            .add_allowed_classes("nav", ["toc"].iter())
             .attribute_filter(|elem, att, val| match (elem, att) {
                 ("input", "type") => Some("checkbox".into()),
                 ("input", "checked") => Some("checked".into()),
@@ -222,3 +283,39 @@ impl<'v> FromFormValue<'v> for SafeString {
         Ok(SafeString::new(&val))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plume_common::utils::md_to_html;
+
+    #[test]
+    fn navigation_survives_cleaning() {
+        let (html, _, _) = md_to_html(
+            "[TOC]\n\n# Intro\n\nText[^1].\n\n[^1]: A note.",
+            None,
+            false,
+            None,
+            None,
+        );
+        let clean = SafeString::new(&html);
+        let clean = clean.get();
+        assert!(clean.contains(r#"<nav class="toc" dir="auto">"#));
+        assert!(clean.contains(r##"<a href="#postcontent-intro""##));
+        assert!(clean.contains(r#"<h1 dir="auto" id="postcontent-intro">"#));
+        assert!(clean.contains(r#"id="postcontent-fnref-1""#));
+        assert!(clean.contains(r##"<a href="#postcontent-fn-1""##));
+        assert!(clean.contains(r#"<div class="footnote-definition" id="postcontent-fn-1">"#));
+        assert!(clean.contains(r##"<a href="#postcontent-fnref-1""##));
+    }
+
+    #[test]
+    fn math_survives_cleaning() {
+        let (html, _, _) = md_to_html("$\\frac{a}{b^2}$", None, false, None, None);
+        let clean = SafeString::new(&html);
+        let clean = clean.get();
+        assert!(clean.contains(r#"<math alttext="\frac{a}{b^2}" display="inline">"#));
+        assert!(clean.contains("<mfrac>"));
+        assert!(clean.contains("<msup>"));
+    }
+}
@@ -30,6 +30,15 @@ table! {
     }
 }
 
+table! {
+    blog_slugs (id) {
+        id -> Int4,
+        blog_id -> Int4,
+        slug -> Varchar,
+        creation_date -> Timestamp,
+    }
+}
+
 table! {
     blogs (id) {
         id -> Int4,
@@ -74,6 +83,20 @@ table! {
     }
 }
 
+table! {
+    deliveries (id) {
+        id -> Int4,
+        activity_id -> Varchar,
+        inbox -> Varchar,
+        sender -> Varchar,
+        body -> Text,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        creation_date -> Timestamp,
+    }
+}
+
 table! {
     email_blocklist (id) {
         id -> Int4,
@@ -84,6 +107,16 @@ table! {
     }
 }
 
+table! {
+    emojis (id) {
+        id -> Int4,
+        shortcode -> Varchar,
+        media_id -> Int4,
+        instance_id -> Int4,
+        ap_url -> Varchar,
+    }
+}
+
 table! {
     email_signups (id) {
         id -> Int4,
@@ -116,6 +149,10 @@ table! {
         default_license -> Text,
         long_description_html -> Varchar,
         short_description_html -> Varchar,
+        last_success_at -> Nullable<Timestamp>,
+        failing_since -> Nullable<Timestamp>,
+        failures -> Int4,
+        unreachable -> Bool,
     }
 }
 
@@ -198,6 +235,51 @@ table! {
     }
 }
 
+table! {
+    post_previews (id) {
+        id -> Int4,
+        post_id -> Int4,
+        token -> Varchar,
+        expires_at -> Timestamp,
+        creation_date -> Timestamp,
+    }
+}
+
+table! {
+    post_revisions (id) {
+        id -> Int4,
+        post_id -> Int4,
+        author_id -> Nullable<Int4>,
+        title -> Varchar,
+        subtitle -> Text,
+        source -> Text,
+        creation_date -> Timestamp,
+    }
+}
+
+table! {
+    post_slugs (id) {
+        id -> Int4,
+        post_id -> Int4,
+        blog_id -> Int4,
+        slug -> Varchar,
+        creation_date -> Timestamp,
+    }
+}
+
+table! {
+    post_templates (id) {
+        id -> Int4,
+        blog_id -> Int4,
+        name -> Varchar,
+        title -> Varchar,
+        source -> Text,
+        tags -> Text,
+        license -> Varchar,
+        cover_id -> Nullable<Int4>,
+    }
+}
+
 table! {
     posts (id) {
         id -> Int4,
@@ -212,6 +294,31 @@ table! {
         subtitle -> Text,
         source -> Text,
         cover_id -> Nullable<Int4>,
+        publish_at -> Nullable<Timestamp>,
+        visibility -> Int4,
+        pinned_at -> Nullable<Timestamp>,
+        sensitive -> Bool,
+        content_warning -> Nullable<Text>,
+        canonical_url -> Nullable<Text>,
+        language -> Nullable<Text>,
+        translation_of -> Nullable<Int4>,
+        word_count -> Int4,
+        reading_time -> Int4,
+    }
+}
+
+table! {
+    reports (id) {
+        id -> Int4,
+        reporter_id -> Nullable<Int4>,
+        user_id -> Int4,
+        post_id -> Nullable<Int4>,
+        comment_id -> Nullable<Int4>,
+        reason -> Text,
+        forwarded -> Bool,
+        resolved -> Bool,
+        ap_url -> Text,
+        creation_date -> Timestamp,
     }
 }
 
@@ -225,6 +332,27 @@ table! {
     }
 }
 
+table! {
+    series (id) {
+        id -> Int4,
+        blog_id -> Int4,
+        title -> Varchar,
+        slug -> Varchar,
+        description -> Text,
+        ap_url -> Varchar,
+        creation_date -> Timestamp,
+    }
+}
+
+table! {
+    series_posts (id) {
+        id -> Int4,
+        series_id -> Int4,
+        post_id -> Int4,
+        position -> Int4,
+    }
+}
+
 table! {
     tags (id) {
         id -> Int4,
@@ -251,6 +379,17 @@ table! {
     }
 }
 
+table! {
+    user_blocks (id) {
+        id -> Int4,
+        user_id -> Int4,
+        blocked_id -> Nullable<Int4>,
+        domain -> Nullable<Varchar>,
+        ap_url -> Text,
+        creation_date -> Timestamp,
+    }
+}
+
 table! {
     users (id) {
         id -> Int4,
@@ -275,6 +414,8 @@ table! {
         role -> Int4,
         preferred_theme -> Nullable<Varchar>,
         hide_custom_css -> Bool,
+        also_known_as -> Text,
+        moved_to -> Nullable<Text>,
     }
 }
 
@@ -282,11 +423,14 @@ joinable!(api_tokens -> apps (app_id));
 joinable!(api_tokens -> users (user_id));
 joinable!(blog_authors -> blogs (blog_id));
 joinable!(blog_authors -> users (author_id));
+joinable!(blog_slugs -> blogs (blog_id));
 joinable!(blogs -> instances (instance_id));
 joinable!(comment_seers -> comments (comment_id));
 joinable!(comment_seers -> users (user_id));
 joinable!(comments -> posts (post_id));
 joinable!(comments -> users (author_id));
+joinable!(emojis -> instances (instance_id));
+joinable!(emojis -> medias (media_id));
 joinable!(likes -> posts (post_id));
 joinable!(likes -> users (user_id));
 joinable!(list_elems -> blogs (blog_id));
@@ -299,10 +443,22 @@ joinable!(mentions -> users (mentioned_id));
 joinable!(notifications -> users (user_id));
 joinable!(post_authors -> posts (post_id));
 joinable!(post_authors -> users (author_id));
+joinable!(post_previews -> posts (post_id));
+joinable!(post_revisions -> posts (post_id));
+joinable!(post_revisions -> users (author_id));
+joinable!(post_slugs -> blogs (blog_id));
+joinable!(post_slugs -> posts (post_id));
+joinable!(post_templates -> blogs (blog_id));
+joinable!(post_templates -> medias (cover_id));
 joinable!(posts -> blogs (blog_id));
 joinable!(posts -> medias (cover_id));
+joinable!(reports -> comments (comment_id));
+joinable!(reports -> posts (post_id));
 joinable!(reshares -> posts (post_id));
 joinable!(reshares -> users (user_id));
+joinable!(series -> blogs (blog_id));
+joinable!(series_posts -> posts (post_id));
+joinable!(series_posts -> series (series_id));
 joinable!(tags -> posts (post_id));
 joinable!(timeline -> posts (post_id));
 joinable!(timeline -> timeline_definition (timeline_id));
@@ -313,11 +469,14 @@ allow_tables_to_appear_in_same_query!(
     api_tokens,
     apps,
     blog_authors,
+    blog_slugs,
     blogs,
     comments,
     comment_seers,
+    deliveries,
     email_blocklist,
     email_signups,
+    emojis,
     follows,
     instances,
     likes,
@@ -328,10 +487,18 @@ allow_tables_to_appear_in_same_query!(
     notifications,
     password_reset_requests,
     post_authors,
+    post_previews,
+    post_revisions,
+    post_slugs,
+    post_templates,
     posts,
+    reports,
     reshares,
+    series,
+    series_posts,
     tags,
     timeline,
     timeline_definition,
+    user_blocks,
     users,
 );
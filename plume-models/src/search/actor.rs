@@ -83,7 +83,7 @@ mod tests {
         db_conn::{DbPool, PragmaForeignKey},
         instance::{Instance, NewInstance},
         post_authors::{NewPostAuthor, PostAuthor},
-        posts::{NewPost, Post},
+        posts::{NewPost, Post, Visibility},
         safe_string::SafeString,
         search::{actor::SearchActor, tests::get_searcher, Query},
         users::{NewUser, User},
@@ -128,6 +128,13 @@ mod tests {
                 subtitle: "".to_owned(),
                 source: "".to_owned(),
                 cover_id: None,
+                publish_at: None,
+                visibility: Visibility::Public.into(),
+                sensitive: false,
+                content_warning: None,
+                canonical_url: None,
+                language: None,
+                translation_of: None,
             },
         )
         .unwrap();
@@ -13,7 +13,7 @@ pub(crate) mod tests {
         blogs::tests::fill_database,
         config::SearchTokenizerConfig,
         post_authors::*,
-        posts::{NewPost, Post},
+        posts::{NewPost, Post, Visibility},
         safe_string::SafeString,
         tests::db,
         CONFIG,
@@ -143,6 +143,13 @@ pub(crate) mod tests {
                     subtitle: "".to_owned(),
                     source: "".to_owned(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -212,6 +219,13 @@ pub(crate) mod tests {
                     subtitle: "".to_owned(),
                     source: "".to_owned(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
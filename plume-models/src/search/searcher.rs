@@ -1,6 +1,11 @@
 use crate::{
-    config::SearchTokenizerConfig, instance::Instance, posts::Post, schema::posts,
-    search::query::PlumeQuery, tags::Tag, Connection, Error, Result,
+    config::SearchTokenizerConfig,
+    instance::Instance,
+    posts::{Post, Visibility},
+    schema::posts,
+    search::query::PlumeQuery,
+    tags::Tag,
+    Connection, Error, Result,
 };
 use chrono::{Datelike, Utc};
 use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
@@ -13,7 +18,6 @@ use tantivy::{
     ReloadPolicy, TantivyError, Term,
 };
 use tracing::warn;
-use whatlang::{detect as detect_lang, Lang};
 
 #[derive(Debug)]
 pub enum SearcherError {
@@ -209,7 +213,7 @@ Then try to restart Plume
     }
 
     pub fn add_document(&self, conn: &Connection, post: &Post) -> Result<()> {
-        if !post.published {
+        if !post.published || post.visibility() != Visibility::Public {
             return Ok(());
         }
 
@@ -242,7 +246,7 @@ Then try to restart Plume
             content => post.content.get().clone(),
             subtitle => post.subtitle.clone(),
             title => post.title.clone(),
-            lang => detect_lang(post.content.get()).and_then(|i| if i.is_reliable() { Some(i.lang()) } else {None} ).unwrap_or(Lang::Eng).name(),
+            lang => post.lang_name(),
             license => post.license.clone(),
         ));
         Ok(())
@@ -314,6 +318,7 @@ Then try to restart Plume
         loop {
             let posts = posts::table
                 .filter(posts::published.eq(true))
+                .filter(posts::visibility.eq(i32::from(Visibility::Public)))
                 .filter(posts::id.gt(cursor))
                 .order(posts::id.asc())
                 .limit(PAGE_SIZE)
@@ -334,7 +339,7 @@ Then try to restart Plume
                     content => post.content.get().clone(),
                     subtitle => post.subtitle.clone(),
                     title => post.title.clone(),
-                    lang => detect_lang(post.content.get()).and_then(|i| if i.is_reliable() { Some(i.lang()) } else {None} ).unwrap_or(Lang::Eng).name(),
+                    lang => post.lang_name(),
                     license => post.license.clone(),
                 ));
                 cursor = post.id;
@@ -0,0 +1,412 @@
+use crate::{
+    ap_url,
+    blogs::Blog,
+    posts::Post,
+    schema::{posts, series, series_posts},
+    users::User,
+    Connection, Error, Result, CONFIG,
+};
+use activitystreams::{
+    base::AnyBase, collection::OrderedCollection, iri_string::types::IriString, prelude::*,
+};
+use chrono::NaiveDateTime;
+use diesel::{self, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SaveChangesDsl};
+use plume_common::{activity_pub::ActivityStream, utils::iri_percent_encode_seg};
+
+/// An ordered list of posts of a blog, for articles written in several parts.
+#[derive(Queryable, Identifiable, Clone, AsChangeset, Debug)]
+#[table_name = "series"]
+pub struct Series {
+    pub id: i32,
+    pub blog_id: i32,
+    pub title: String,
+    pub slug: String,
+    pub description: String,
+    pub ap_url: String,
+    pub creation_date: NaiveDateTime,
+}
+
+#[derive(Default, Insertable)]
+#[table_name = "series"]
+pub struct NewSeries {
+    pub blog_id: i32,
+    pub title: String,
+    pub slug: String,
+    pub description: String,
+    pub ap_url: String,
+}
+
+#[derive(Queryable, Identifiable, Clone, Associations)]
+#[belongs_to(Series)]
+#[belongs_to(Post)]
+pub struct SeriesPost {
+    pub id: i32,
+    pub series_id: i32,
+    pub post_id: i32,
+    pub position: i32,
+}
+
+#[derive(Insertable)]
+#[table_name = "series_posts"]
+pub struct NewSeriesPost {
+    pub series_id: i32,
+    pub post_id: i32,
+    pub position: i32,
+}
+
+impl SeriesPost {
+    insert!(series_posts, NewSeriesPost);
+    get!(series_posts);
+}
+
+impl Series {
+    insert!(series, NewSeries, |inserted, conn| {
+        if inserted.ap_url.is_empty() {
+            let blog = Blog::get(conn, inserted.blog_id)?;
+            inserted.ap_url = Series::ap_url(&blog, &inserted.slug);
+            return inserted.save_changes(conn).map_err(Error::from);
+        }
+        Ok(inserted)
+    });
+    get!(series);
+    find_by!(series, find_by_ap_url, ap_url as &str);
+    find_by!(series, find_by_slug, slug as &str, blog_id as i32);
+    list_by!(series, list_for_blog, blog_id as i32);
+
+    pub fn ap_url(blog: &Blog, slug: &str) -> String {
+        ap_url(&format!(
+            "{}/~/{}/series/{}",
+            CONFIG.base_url,
+            iri_percent_encode_seg(&blog.fqn),
+            iri_percent_encode_seg(slug)
+        ))
+    }
+
+    pub fn slug(title: &str) -> &str {
+        title
+    }
+
+    pub fn get_blog(&self, conn: &Connection) -> Result<Blog> {
+        Blog::get(conn, self.blog_id)
+    }
+
+    /// The series a post is part of, if any.
+    pub fn find_for_post(conn: &Connection, post_id: i32) -> Result<Option<Series>> {
+        let ids = series_posts::table
+            .filter(series_posts::post_id.eq(post_id))
+            .select(series_posts::series_id);
+        series::table
+            .filter(series::id.eq_any(ids))
+            .first(conn)
+            .optional()
+            .map_err(Error::from)
+    }
+
+    /// Lists the posts of this series, in order.
+    ///
+    /// Drafts are only included if `with_drafts` is `true`.
+    pub fn get_posts(&self, conn: &Connection, with_drafts: bool) -> Result<Vec<Post>> {
+        let mut query = series_posts::table
+            .inner_join(posts::table)
+            .filter(series_posts::series_id.eq(self.id))
+            .order((series_posts::position.asc(), series_posts::id.asc()))
+            .select(posts::all_columns)
+            .into_boxed();
+        if !with_drafts {
+            query = query.filter(posts::published.eq(true));
+        }
+        query.load::<Post>(conn).map_err(Error::from)
+    }
+
+    /// The published posts of this series that `user` (or an anonymous visitor, if `None`) is
+    /// allowed to read.
+    pub fn readable_posts(&self, conn: &Connection, user: Option<&User>) -> Result<Vec<Post>> {
+        Ok(self
+            .get_posts(conn, false)?
+            .into_iter()
+            .filter(|post| post.can_see(conn, user).unwrap_or(false))
+            .collect())
+    }
+
+    pub fn count_posts(&self, conn: &Connection) -> Result<i64> {
+        series_posts::table
+            .filter(series_posts::series_id.eq(self.id))
+            .count()
+            .get_result(conn)
+            .map_err(Error::from)
+    }
+
+    /// Returns the posts coming just before and just after `post` in this series, among the ones
+    /// `user` can read.
+    pub fn neighbours(
+        &self,
+        conn: &Connection,
+        post: &Post,
+        user: Option<&User>,
+    ) -> Result<(Option<Post>, Option<Post>)> {
+        let posts = self.readable_posts(conn, user)?;
+        let index = match posts.iter().position(|p| p.id == post.id) {
+            Some(index) => index,
+            None => return Ok((None, None)),
+        };
+        let previous = index.checked_sub(1).and_then(|i| posts.get(i)).cloned();
+        let next = posts.get(index + 1).cloned();
+        Ok((previous, next))
+    }
+
+    /// Appends `post` at the end of this series, removing it from any other series first.
+    pub fn add_post(&self, conn: &Connection, post: &Post) -> Result<SeriesPost> {
+        if let Some(current) = Series::find_for_post(conn, post.id)? {
+            if current.id == self.id {
+                return series_posts::table
+                    .filter(series_posts::post_id.eq(post.id))
+                    .first(conn)
+                    .map_err(Error::from);
+            }
+            current.remove_post(conn, post)?;
+        }
+
+        let position = series_posts::table
+            .filter(series_posts::series_id.eq(self.id))
+            .select(diesel::dsl::max(series_posts::position))
+            .first::<Option<i32>>(conn)?
+            .map(|p| p + 1)
+            .unwrap_or(0);
+        SeriesPost::insert(
+            conn,
+            NewSeriesPost {
+                series_id: self.id,
+                post_id: post.id,
+                position,
+            },
+        )
+    }
+
+    /// Removes `post` from this series, and deletes the series if it became empty.
+    pub fn remove_post(&self, conn: &Connection, post: &Post) -> Result<()> {
+        diesel::delete(
+            series_posts::table
+                .filter(series_posts::series_id.eq(self.id))
+                .filter(series_posts::post_id.eq(post.id)),
+        )
+        .execute(conn)?;
+        if self.count_posts(conn)? == 0 {
+            self.delete(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Puts `post` in the series named `title` of its blog, creating it if needed.
+    ///
+    /// An empty title removes the post from its current series.
+    pub fn set_for_post(conn: &Connection, post: &Post, title: &str) -> Result<Option<Series>> {
+        let title = title.trim();
+        if title.is_empty() {
+            if let Some(current) = Series::find_for_post(conn, post.id)? {
+                current.remove_post(conn, post)?;
+            }
+            return Ok(None);
+        }
+
+        let existing = series::table
+            .filter(series::blog_id.eq(post.blog_id))
+            .filter(series::slug.eq(Series::slug(title)))
+            .first::<Series>(conn)
+            .optional()?;
+        let series = match existing {
+            Some(series) => series,
+            None => Series::insert(
+                conn,
+                NewSeries {
+                    blog_id: post.blog_id,
+                    title: title.to_owned(),
+                    slug: Series::slug(title).to_owned(),
+                    ..NewSeries::default()
+                },
+            )?,
+        };
+        series.add_post(conn, post)?;
+        Ok(Some(series))
+    }
+
+    /// Moves `post` one step earlier (if `up` is `true`) or later in this series.
+    pub fn move_post(&self, conn: &Connection, post: &Post, up: bool) -> Result<()> {
+        let members = series_posts::table
+            .filter(series_posts::series_id.eq(self.id))
+            .order((series_posts::position.asc(), series_posts::id.asc()))
+            .load::<SeriesPost>(conn)?;
+        let index = members
+            .iter()
+            .position(|m| m.post_id == post.id)
+            .ok_or(Error::NotFound)?;
+        let other = if up {
+            index.checked_sub(1)
+        } else {
+            Some(index + 1).filter(|i| *i < members.len())
+        };
+
+        if let Some(other) = other {
+            // positions are renumbered, so that members that had the same one can be swapped too
+            let mut order = members.iter().map(|m| m.id).collect::<Vec<_>>();
+            order.swap(index, other);
+            for (position, id) in order.into_iter().enumerate() {
+                diesel::update(series_posts::table.filter(series_posts::id.eq(id)))
+                    .set(series_posts::position.eq(position as i32))
+                    .execute(conn)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<Self> {
+        diesel::update(self).set(self).execute(conn)?;
+        Self::get(conn, self.id)
+    }
+
+    pub fn delete(&self, conn: &Connection) -> Result<()> {
+        diesel::delete(self)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    pub fn to_activity(&self, conn: &Connection) -> Result<OrderedCollection> {
+        let items = self
+            .readable_posts(conn, None)?
+            .into_iter()
+            .filter_map(|p| p.ap_url.parse::<IriString>().ok())
+            .map(AnyBase::from)
+            .collect::<Vec<_>>();
+        let mut coll = OrderedCollection::new();
+        coll.set_id(self.ap_url.parse::<IriString>()?);
+        coll.set_name(self.title.clone());
+        if !self.description.is_empty() {
+            coll.set_summary(self.description.clone());
+        }
+        coll.set_attributed_to(self.get_blog(conn)?.ap_url.parse::<IriString>()?);
+        coll.set_total_items(items.len() as u64);
+        coll.set_many_ordered_items(items);
+        Ok(coll)
+    }
+
+    pub fn outbox(&self, conn: &Connection) -> Result<ActivityStream<OrderedCollection>> {
+        self.to_activity(conn).map(ActivityStream::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        inbox::tests::fill_database,
+        posts::{NewPost, Visibility},
+        safe_string::SafeString,
+        tests::db,
+    };
+    use diesel::Connection;
+
+    fn new_post(conn: &crate::Connection, blog: &Blog, title: &str) -> Post {
+        Post::insert(
+            conn,
+            NewPost {
+                blog_id: blog.id,
+                slug: title.to_owned(),
+                title: title.to_owned(),
+                content: SafeString::new(""),
+                published: true,
+                license: "CC-BY-SA".to_owned(),
+                ap_url: String::new(),
+                creation_date: None,
+                subtitle: String::new(),
+                source: String::new(),
+                cover_id: None,
+                publish_at: None,
+                visibility: Visibility::Public.into(),
+                sensitive: false,
+                content_warning: None,
+                canonical_url: None,
+                language: None,
+                translation_of: None,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn ordering_and_navigation() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (_, _, blogs) = fill_database(conn);
+            let parts = ["Part 1", "Part 2", "Part 3"]
+                .iter()
+                .map(|title| new_post(conn, &blogs[0], title))
+                .collect::<Vec<_>>();
+            for part in &parts {
+                Series::set_for_post(conn, part, "Tutorial")?;
+            }
+            let series = Series::find_for_post(conn, parts[0].id)?.unwrap();
+            assert_eq!(series.title, "Tutorial");
+            assert!(series.ap_url.ends_with("/series/Tutorial"));
+            assert_eq!(Series::list_for_blog(conn, blogs[0].id)?.len(), 1);
+
+            let (previous, next) = series.neighbours(conn, &parts[1], None)?;
+            assert_eq!(previous.map(|p| p.id), Some(parts[0].id));
+            assert_eq!(next.map(|p| p.id), Some(parts[2].id));
+
+            series.move_post(conn, &parts[2], true)?;
+            assert_eq!(
+                series
+                    .get_posts(conn, false)?
+                    .into_iter()
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>(),
+                vec![parts[0].id, parts[2].id, parts[1].id]
+            );
+
+            for part in &parts {
+                Series::set_for_post(conn, part, "")?;
+            }
+            assert!(Series::get(conn, series.id).is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn hides_private_parts() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (_, users, blogs) = fill_database(conn);
+            let parts = ["Part 1", "Part 2", "Part 3"]
+                .iter()
+                .map(|title| new_post(conn, &blogs[0], title))
+                .collect::<Vec<_>>();
+            for part in &parts {
+                Series::set_for_post(conn, part, "Tutorial")?;
+            }
+            diesel::update(&parts[1])
+                .set(posts::visibility.eq(i32::from(Visibility::Followers)))
+                .execute(conn)?;
+            let series = Series::find_for_post(conn, parts[0].id)?.unwrap();
+
+            let readable = series
+                .readable_posts(conn, None)?
+                .into_iter()
+                .map(|p| p.id)
+                .collect::<Vec<_>>();
+            assert_eq!(readable, vec![parts[0].id, parts[2].id]);
+            assert_eq!(series.to_activity(conn)?.total_items(), Some(2));
+            let (previous, next) = series.neighbours(conn, &parts[2], None)?;
+            assert_eq!(previous.map(|p| p.id), Some(parts[0].id));
+            assert!(next.is_none());
+
+            // the authors of the blog can still read it
+            assert_eq!(
+                series.readable_posts(conn, Some(&users[0]))?.len(),
+                parts.len()
+            );
+
+            Ok(())
+        });
+    }
+}
@@ -1,7 +1,9 @@
 use crate::{
     lists::List,
-    posts::Post,
-    schema::{posts, timeline, timeline_definition},
+    posts::{Post, Visibility},
+    schema::{instances, post_authors, posts, timeline, timeline_definition, user_blocks, users},
+    user_blocks::UserBlock,
+    users::User,
     Connection, Error, Result,
 };
 use diesel::{self, BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
@@ -37,6 +39,32 @@ struct TimelineEntry {
     pub timeline_id: i32,
 }
 
+/// The ids of the posts written by the users and domains that `$user_id` blocked, as a subquery.
+macro_rules! hidden_posts {
+    ($user_id:expr) => {{
+        let blocked_users = user_blocks::table
+            .filter(user_blocks::user_id.eq($user_id))
+            .select(user_blocks::blocked_id);
+        let blocked_domains = user_blocks::table
+            .filter(user_blocks::user_id.eq($user_id))
+            .select(user_blocks::domain);
+        let blocked_instances = instances::table
+            .filter(instances::public_domain.nullable().eq_any(blocked_domains))
+            .select(instances::id);
+        let blocked_authors = users::table
+            .filter(
+                users::id
+                    .nullable()
+                    .eq_any(blocked_users)
+                    .or(users::instance_id.eq_any(blocked_instances)),
+            )
+            .select(users::id);
+        post_authors::table
+            .filter(post_authors::author_id.eq_any(blocked_authors))
+            .select(post_authors::post_id)
+    }};
+}
+
 impl Timeline {
     insert!(timeline_definition, NewTimeline);
     get!(timeline_definition);
@@ -210,6 +238,30 @@ impl Timeline {
             .map_err(Error::from)
     }
 
+    /// Same as `get_page`, but without the posts of the people `user` blocked.
+    ///
+    /// Personal timelines already don't include them, but instance timelines do.
+    pub fn get_page_for(
+        &self,
+        conn: &Connection,
+        user: Option<&User>,
+        (min, max): (i32, i32),
+    ) -> Result<Vec<Post>> {
+        match user {
+            Some(user) if self.user_id.is_none() => timeline::table
+                .filter(timeline::timeline_id.eq(self.id))
+                .filter(timeline::post_id.ne_all(hidden_posts!(user.id)))
+                .inner_join(posts::table)
+                .order(posts::creation_date.desc())
+                .offset(min.into())
+                .limit((max - min).into())
+                .select(posts::all_columns)
+                .load::<Post>(conn)
+                .map_err(Error::from),
+            _ => self.get_page(conn, (min, max)),
+        }
+    }
+
     pub fn count_posts(&self, conn: &Connection) -> Result<i64> {
         timeline::table
             .filter(timeline::timeline_id.eq(self.id))
@@ -219,19 +271,96 @@ impl Timeline {
             .map_err(Error::from)
     }
 
+    /// Same as `count_posts`, but without the posts of the people `user` blocked.
+    pub fn count_posts_for(&self, conn: &Connection, user: Option<&User>) -> Result<i64> {
+        match user {
+            Some(user) if self.user_id.is_none() => timeline::table
+                .filter(timeline::timeline_id.eq(self.id))
+                .filter(timeline::post_id.ne_all(hidden_posts!(user.id)))
+                .inner_join(posts::table)
+                .count()
+                .get_result(conn)
+                .map_err(Error::from),
+            _ => self.count_posts(conn),
+        }
+    }
+
     pub fn add_to_all_timelines(conn: &Connection, post: &Post, kind: Kind<'_>) -> Result<()> {
         let timelines = timeline_definition::table
             .load::<Self>(conn.deref())
             .map_err(Error::from)?;
 
         for t in timelines {
-            if t.matches(conn, post, kind)? {
+            if t.matches(conn, post, kind)? && t.can_list(conn, post)? && !t.blocks(conn, kind)? {
                 t.add_post(conn, post)?;
             }
         }
         Ok(())
     }
 
+    /// Whether the visibility of `post` allows it to be listed in this timeline.
+    ///
+    /// Posts that are not public only go to the personal timelines of the people they are addressed to,
+    /// and personal timelines don't list the posts of the people their owner blocked.
+    fn can_list(&self, conn: &Connection, post: &Post) -> Result<bool> {
+        let user = match self.user_id {
+            Some(user_id) => User::get(conn, user_id)?,
+            None => return Ok(post.visibility() == Visibility::Public),
+        };
+        if UserBlock::hides_post(conn, &user, post)? {
+            return Ok(false);
+        }
+        if post.visibility() == Visibility::Public {
+            return Ok(true);
+        }
+        post.is_addressed_to(conn, &user)
+    }
+
+    /// Whether the owner of this timeline blocked the user who liked or reshared a post.
+    fn blocks(&self, conn: &Connection, kind: Kind<'_>) -> Result<bool> {
+        match (self.user_id, kind) {
+            (Some(user_id), Kind::Reshare(actor)) | (Some(user_id), Kind::Like(actor)) => {
+                UserBlock::is_blocked(conn, &User::get(conn, user_id)?, actor)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Removes `post` from the timelines its new visibility doesn't allow it to be listed in.
+    pub fn remove_from_hidden_timelines(conn: &Connection, post: &Post) -> Result<()> {
+        let ids = timeline::table
+            .filter(timeline::post_id.eq(post.id))
+            .select(timeline::timeline_id);
+        let timelines = timeline_definition::table
+            .filter(timeline_definition::id.eq_any(ids))
+            .load::<Self>(conn)?;
+        for t in timelines {
+            if !t.can_list(conn, post)? {
+                diesel::delete(
+                    timeline::table
+                        .filter(timeline::timeline_id.eq(t.id))
+                        .filter(timeline::post_id.eq(post.id)),
+                )
+                .execute(conn)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the posts of the users and domains `user` blocked from their timelines.
+    pub fn remove_hidden_for_user(conn: &Connection, user: &User) -> Result<()> {
+        let timelines = timeline_definition::table
+            .filter(timeline_definition::user_id.eq(user.id))
+            .select(timeline_definition::id);
+        diesel::delete(
+            timeline::table
+                .filter(timeline::timeline_id.eq_any(timelines))
+                .filter(timeline::post_id.eq_any(hidden_posts!(user.id))),
+        )
+        .execute(conn)?;
+        Ok(())
+    }
+
     pub fn add_post(&self, conn: &Connection, post: &Post) -> Result<()> {
         if self.includes_post(conn, post)? {
             return Ok(());
@@ -445,6 +574,13 @@ mod tests {
                     subtitle: "".to_string(),
                     source: "you must say GNU/Linux, not Linux!!!".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -464,6 +600,13 @@ mod tests {
                     subtitle: "".to_string(),
                     source: "so is Microsoft".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -517,6 +660,13 @@ mod tests {
                     creation_date: None,
                     subtitle: "".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -538,6 +688,13 @@ mod tests {
                     creation_date: None,
                     subtitle: "".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -582,6 +739,13 @@ mod tests {
                     subtitle: "".to_string(),
                     source: "you must say GNU/Linux, not Linux!!!".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -600,6 +764,13 @@ mod tests {
                     subtitle: "".to_string(),
                     source: "so is Microsoft".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -618,6 +789,156 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_page_for_blocking_user() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, blogs) = crate::inbox::tests::fill_database(conn);
+            let tl = Timeline::new_for_instance(conn, "all posts".to_owned(), "all".to_owned())?;
+            let other_post = Post::insert(
+                conn,
+                NewPost {
+                    blog_id: blogs[0].id,
+                    slug: "other".to_string(),
+                    title: "Other".to_string(),
+                    content: SafeString::new("Hi"),
+                    published: true,
+                    license: "WTFPL".to_string(),
+                    ap_url: "".to_string(),
+                    creation_date: None,
+                    subtitle: "".to_string(),
+                    source: "Hi".to_string(),
+                    cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
+                },
+            )?;
+            PostAuthor::insert(
+                conn,
+                NewPostAuthor {
+                    post_id: other_post.id,
+                    author_id: users[1].id,
+                },
+            )?;
+            tl.add_post(conn, &posts[0])?;
+            tl.add_post(conn, &other_post)?;
+
+            UserBlock::block(conn, &users[2], &users[0])?;
+            // The hidden post doesn't take the place of the other one
+            let page = tl.get_page_for(conn, Some(&users[2]), (0, 1))?;
+            assert_eq!(page.len(), 1);
+            assert_eq!(page[0].id, other_post.id);
+            assert_eq!(tl.count_posts_for(conn, Some(&users[2]))?, 1);
+            assert_eq!(tl.count_posts_for(conn, Some(&users[1]))?, 2);
+            assert_eq!(tl.count_posts_for(conn, None)?, 2);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_matches_length() {
+        let conn = &db();
+        conn.test_transaction::<_, (), _>(|| {
+            let (users, blogs) = blogTests::fill_database(conn);
+
+            let long_tl = Timeline::new_for_user(
+                conn,
+                users[0].id,
+                "Long reads".to_owned(),
+                "length > 200 and reading_time >= 1".to_owned(),
+            )
+            .unwrap();
+
+            let mut post = Post::insert(
+                conn,
+                NewPost {
+                    blog_id: blogs[0].id,
+                    slug: "short".to_string(),
+                    title: "Short".to_string(),
+                    content: SafeString::new("Not much to say"),
+                    published: true,
+                    license: "GPL".to_string(),
+                    ap_url: "".to_string(),
+                    creation_date: None,
+                    subtitle: "".to_string(),
+                    source: "Not much to say".to_string(),
+                    cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(post.word_count, 4);
+            assert!(!long_tl.matches(conn, &post, Kind::Original).unwrap());
+
+            post.source = "Much more to say. ".repeat(100);
+            let post = post.update(conn).unwrap();
+            assert_eq!(post.word_count, 400);
+            assert_eq!(post.reading_time, 2);
+            assert!(long_tl.matches(conn, &post, Kind::Original).unwrap());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_matches_sensitive() {
+        let conn = &db();
+        conn.test_transaction::<_, (), _>(|| {
+            let (users, blogs) = blogTests::fill_database(conn);
+
+            let safe_tl = Timeline::new_for_user(
+                conn,
+                users[0].id,
+                "Safe timeline".to_owned(),
+                "not sensitive".to_owned(),
+            )
+            .unwrap();
+
+            let mut post = Post::insert(
+                conn,
+                NewPost {
+                    blog_id: blogs[0].id,
+                    slug: "spoilers".to_string(),
+                    title: "The end of the story".to_string(),
+                    content: SafeString::new("Everybody dies"),
+                    published: true,
+                    license: "GPL".to_string(),
+                    ap_url: "".to_string(),
+                    creation_date: None,
+                    subtitle: "".to_string(),
+                    source: "Everybody dies".to_string(),
+                    cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
+                },
+            )
+            .unwrap();
+            assert!(safe_tl.matches(conn, &post, Kind::Original).unwrap());
+
+            post.sensitive = true;
+            post.content_warning = Some("Spoilers".to_owned());
+            assert!(!safe_tl.matches(conn, &post, Kind::Original).unwrap());
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_matches_lists_direct() {
         let conn = &db();
@@ -638,6 +959,13 @@ mod tests {
                     subtitle: "".to_string(),
                     source: "you must say GNU/Linux, not Linux!!!".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -778,6 +1106,13 @@ mod tests {
                     subtitle: "".to_string(),
                     source: "you must say GNU/Linux, not Linux!!!".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -810,6 +1145,13 @@ mod tests {
                     subtitle: "Stallman is our god".to_string(),
                     source: "you must say GNU/Linux, not Linux!!!".to_string(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
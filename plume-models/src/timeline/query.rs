@@ -8,7 +8,6 @@ use crate::{
     Connection, Result,
 };
 use plume_common::activity_pub::inbox::AsActor;
-use whatlang::{self, Lang};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QueryError {
@@ -193,6 +192,7 @@ impl<'a> TQ<'a> {
 enum Arg<'a> {
     In(WithList, List<'a>),
     Contains(WithContains, &'a str),
+    Compare(WithNumber, Comparison, i32),
     Boolean(Bool),
 }
 
@@ -207,6 +207,7 @@ impl<'a> Arg<'a> {
         match self {
             Arg::In(t, l) => t.matches(conn, timeline, post, l, kind),
             Arg::Contains(t, v) => t.matches(post, v),
+            Arg::Compare(t, c, v) => t.matches(post, *c, *v),
             Arg::Boolean(t) => t.matches(conn, timeline, post, kind),
         }
     }
@@ -264,17 +265,7 @@ impl WithList {
                             .any(|s| tags.iter().any(|t| s == &t.tag)))
                     }
                     (WithList::Lang, ListType::Prefix) => {
-                        let lang = whatlang::detect(post.content.get())
-                            .and_then(|i| {
-                                if i.is_reliable() {
-                                    Some(i.lang())
-                                } else {
-                                    None
-                                }
-                            })
-                            .unwrap_or(Lang::Eng)
-                            .name();
-                        list.contains_prefix(conn, lang)
+                        list.contains_prefix(conn, &post.lang_name())
                     }
                     (_, _) => Err(QueryError::RuntimeError(format!(
                         "The list '{}' is of the wrong type for this usage",
@@ -314,17 +305,7 @@ impl WithList {
                     Ok(list.iter().any(|s| tags.iter().any(|t| s == &t.tag)))
                 }
                 WithList::Lang => {
-                    let lang = whatlang::detect(post.content.get())
-                        .and_then(|i| {
-                            if i.is_reliable() {
-                                Some(i.lang())
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap_or(Lang::Eng)
-                        .name()
-                        .to_lowercase();
+                    let lang = post.lang_name().to_lowercase();
                     Ok(list.iter().any(|s| lang.starts_with(&s.to_lowercase())))
                 }
             },
@@ -349,11 +330,60 @@ impl WithContains {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+enum WithNumber {
+    Length,
+    ReadingTime,
+}
+
+impl WithNumber {
+    pub fn matches(&self, post: &Post, comparison: Comparison, value: i32) -> Result<bool> {
+        let number = match self {
+            WithNumber::Length => post.word_count,
+            WithNumber::ReadingTime => post.reading_time,
+        };
+        Ok(comparison.compare(number, value))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Lower,
+    LowerOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    fn parse(operator: &str) -> Option<Self> {
+        match operator {
+            "<" => Some(Comparison::Lower),
+            "<=" => Some(Comparison::LowerOrEqual),
+            "=" => Some(Comparison::Equal),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            ">" => Some(Comparison::Greater),
+            _ => None,
+        }
+    }
+
+    fn compare(self, left: i32, right: i32) -> bool {
+        match self {
+            Comparison::Lower => left < right,
+            Comparison::LowerOrEqual => left <= right,
+            Comparison::Equal => left == right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Greater => left > right,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum Bool {
     Followed { boosts: bool, likes: bool },
     HasCover,
     Local,
+    Sensitive,
     All,
 }
 
@@ -394,6 +424,7 @@ impl Bool {
             }
             Bool::HasCover => Ok(post.cover_id.is_some()),
             Bool::Local => Ok(post.get_blog(conn)?.is_local() && kind == Kind::Original),
+            Bool::Sensitive => Ok(post.sensitive),
             Bool::All => Ok(kind == Kind::Original),
         }
     }
@@ -552,7 +583,33 @@ fn parse_d<'a, 'b>(mut stream: &'b [Token<'a>]) -> QueryResult<(&'b [Token<'a>],
             }
             (t, _) => t.get_error(Token::Word(0, 0, "'contains'")),
         },
-        s @ "followed" | s @ "has_cover" | s @ "local" | s @ "all" => match s {
+        s @ "length" | s @ "reading_time" => match (
+            stream.get(1).ok_or(QueryError::UnexpectedEndOfQuery)?,
+            stream.get(2).ok_or(QueryError::UnexpectedEndOfQuery)?,
+        ) {
+            (Token::Word(_, _, op), Token::Word(b, e, n)) if Comparison::parse(op).is_some() => {
+                match n.parse() {
+                    Ok(n) => Ok((
+                        &stream[3..],
+                        Arg::Compare(
+                            match s {
+                                "length" => WithNumber::Length,
+                                "reading_time" => WithNumber::ReadingTime,
+                                _ => unreachable!(),
+                            },
+                            Comparison::parse(op).unwrap(),
+                            n,
+                        ),
+                    )),
+                    Err(_) => Token::Word(*b, *e, *n).get_error(Token::Word(0, 0, "a number")),
+                }
+            }
+            (Token::Word(_, _, op), t) if Comparison::parse(op).is_some() => {
+                t.get_error(Token::Word(0, 0, "a number"))
+            }
+            (t, _) => t.get_error(Token::Word(0, 0, "one of '<', '<=', '=', '>=' or '>'")),
+        },
+        s @ "followed" | s @ "has_cover" | s @ "local" | s @ "sensitive" | s @ "all" => match s {
             "followed" => {
                 let mut boosts = true;
                 let mut likes = false;
@@ -585,6 +642,7 @@ fn parse_d<'a, 'b>(mut stream: &'b [Token<'a>]) -> QueryResult<(&'b [Token<'a>],
             }
             "has_cover" => Ok((&stream[1..], Arg::Boolean(Bool::HasCover))),
             "local" => Ok((&stream[1..], Arg::Boolean(Bool::Local))),
+            "sensitive" => Ok((&stream[1..], Arg::Boolean(Bool::Sensitive))),
             "all" => Ok((&stream[1..], Arg::Boolean(Bool::All))),
             _ => unreachable!(),
         },
@@ -595,7 +653,8 @@ fn parse_d<'a, 'b>(mut stream: &'b [Token<'a>]) -> QueryResult<(&'b [Token<'a>],
                 0,
                 0,
                 "one of 'blog', 'author', 'license', 'tags', 'lang', \
-             'title', 'subtitle', 'content', 'followed', 'has_cover', 'local' or 'all'",
+             'title', 'subtitle', 'content', 'length', 'reading_time', 'followed', 'has_cover', \
+             'local', 'sensitive' or 'all'",
             )),
     }
 }
@@ -754,8 +813,23 @@ mod tests {
             ])
         );
 
+        let numbers = TimelineQuery::parse(r#"length > 2000 and reading_time <= 5"#).unwrap();
+        assert_eq!(
+            numbers.0,
+            TQ::And(vec![
+                TQ::Arg(
+                    Arg::Compare(WithNumber::Length, Comparison::Greater, 2000),
+                    false
+                ),
+                TQ::Arg(
+                    Arg::Compare(WithNumber::ReadingTime, Comparison::LowerOrEqual, 5),
+                    false
+                ),
+            ])
+        );
+
         let booleans = TimelineQuery::parse(
-            r#"followed include like exclude reshares and has_cover and local and all"#,
+            r#"followed include like exclude reshares and has_cover and local and sensitive and all"#,
         )
         .unwrap();
         assert_eq!(
@@ -770,6 +844,7 @@ mod tests {
                 ),
                 TQ::Arg(Arg::Boolean(Bool::HasCover), false),
                 TQ::Arg(Arg::Boolean(Bool::Local), false),
+                TQ::Arg(Arg::Boolean(Bool::Sensitive), false),
                 TQ::Arg(Arg::Boolean(Bool::All), false),
             ])
         );
@@ -829,8 +904,8 @@ mod tests {
                 0,
                 11,
                 "Syntax Error: Expected one of 'blog', \
-'author', 'license', 'tags', 'lang', 'title', 'subtitle', 'content', 'followed', 'has_cover', \
-'local' or 'all', got 'not_a_field'"
+'author', 'license', 'tags', 'lang', 'title', 'subtitle', 'content', 'length', 'reading_time', \
+'followed', 'has_cover', 'local', 'sensitive' or 'all', got 'not_a_field'"
                     .to_owned()
             )
         );
@@ -859,6 +934,27 @@ mod tests {
             )
         );
 
+        let expect_comparison = TimelineQuery::parse(r#"length contains 12"#).unwrap_err();
+        assert_eq!(
+            expect_comparison,
+            QueryError::SyntaxError(
+                7,
+                8,
+                "Syntax Error: Expected one of '<', '<=', '=', '>=' or '>', got 'contains'"
+                    .to_owned()
+            )
+        );
+
+        let expect_number = TimelineQuery::parse(r#"length > long"#).unwrap_err();
+        assert_eq!(
+            expect_number,
+            QueryError::SyntaxError(
+                9,
+                4,
+                "Syntax Error: Expected a number, got 'long'".to_owned()
+            )
+        );
+
         let expect_word = TimelineQuery::parse(r#"title contains ,"#).unwrap_err();
         assert_eq!(
             expect_word,
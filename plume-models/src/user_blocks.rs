@@ -0,0 +1,442 @@
+use crate::{
+    ap_url,
+    deliveries::Delivery,
+    follows::Follow,
+    instance::Instance,
+    notifications::Notification,
+    posts::Post,
+    schema::{user_blocks, users},
+    timeline::Timeline,
+    users::User,
+    Connection, Error, Result, CONFIG,
+};
+use activitystreams::{
+    activity::{Block as BlockAct, Undo},
+    base::AnyBase,
+    iri_string::types::IriString,
+    prelude::*,
+};
+use chrono::NaiveDateTime;
+use diesel::{
+    self, BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl, SaveChangesDsl,
+};
+use plume_common::activity_pub::{
+    inbox::{AsActor, AsObject, FromId},
+    sign::Signer,
+};
+
+/// A user, or all the users of a domain, that someone doesn't want to interact with.
+///
+/// Exactly one of `blocked_id` and `domain` is set.
+#[derive(Clone, Queryable, Identifiable, AsChangeset)]
+pub struct UserBlock {
+    pub id: i32,
+    pub user_id: i32,
+    pub blocked_id: Option<i32>,
+    pub domain: Option<String>,
+    /// The id of the `Block` activity, empty for domain blocks
+    pub ap_url: String,
+    pub creation_date: NaiveDateTime,
+}
+
+#[derive(Default, Insertable)]
+#[table_name = "user_blocks"]
+pub struct NewUserBlock {
+    pub user_id: i32,
+    pub blocked_id: Option<i32>,
+    pub domain: Option<String>,
+    pub ap_url: String,
+}
+
+impl UserBlock {
+    insert!(
+        user_blocks,
+        NewUserBlock,
+        |inserted, conn| if inserted.ap_url.is_empty() && inserted.blocked_id.is_some() {
+            inserted.ap_url = ap_url(&format!("{}/blocks/{}", CONFIG.base_url, inserted.id));
+            inserted.save_changes(conn).map_err(Error::from)
+        } else {
+            Ok(inserted)
+        }
+    );
+    get!(user_blocks);
+    find_by!(user_blocks, find_by_ap_url, ap_url as &str);
+
+    pub fn find(conn: &Connection, user_id: i32, blocked_id: i32) -> Result<UserBlock> {
+        user_blocks::table
+            .filter(user_blocks::user_id.eq(user_id))
+            .filter(user_blocks::blocked_id.eq(blocked_id))
+            .get_result(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn find_domain(conn: &Connection, user_id: i32, domain: &str) -> Result<UserBlock> {
+        user_blocks::table
+            .filter(user_blocks::user_id.eq(user_id))
+            .filter(user_blocks::domain.eq(domain))
+            .get_result(conn)
+            .map_err(Error::from)
+    }
+
+    pub fn list_for_user(conn: &Connection, user: &User) -> Result<Vec<UserBlock>> {
+        user_blocks::table
+            .filter(user_blocks::user_id.eq(user.id))
+            .order(user_blocks::creation_date.desc())
+            .load::<UserBlock>(conn)
+            .map_err(Error::from)
+    }
+
+    /// Whether `user` blocked `other`, or the domain of `other`.
+    pub fn is_blocked(conn: &Connection, user: &User, other: &User) -> Result<bool> {
+        let domain = other.get_instance(conn)?.public_domain;
+        diesel::select(diesel::dsl::exists(
+            user_blocks::table
+                .filter(user_blocks::user_id.eq(user.id))
+                .filter(
+                    user_blocks::blocked_id
+                        .eq(other.id)
+                        .or(user_blocks::domain.eq(domain)),
+                ),
+        ))
+        .get_result(conn)
+        .map_err(Error::from)
+    }
+
+    /// Whether `user` blocked one of the authors of `post`.
+    pub fn hides_post(conn: &Connection, user: &User, post: &Post) -> Result<bool> {
+        for author in post.get_authors(conn)? {
+            if UserBlock::is_blocked(conn, user, &author)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Blocks `target` for `user`, and lets `target` know about it if they are on another instance.
+    pub fn block(conn: &Connection, user: &User, target: &User) -> Result<UserBlock> {
+        if user.id == target.id {
+            return Err(Error::InvalidValue);
+        }
+        let block = UserBlock::insert(
+            conn,
+            NewUserBlock {
+                user_id: user.id,
+                blocked_id: Some(target.id),
+                ..NewUserBlock::default()
+            },
+        )?;
+        block.apply(conn)?;
+
+        let act = block.to_activity(conn)?;
+        Delivery::broadcast(conn, user, act, vec![target.clone()])?;
+        Ok(block)
+    }
+
+    /// Blocks all the users of `domain` for `user`. This is not federated.
+    pub fn block_domain(conn: &Connection, user: &User, domain: &str) -> Result<UserBlock> {
+        let domain = domain.trim().to_lowercase();
+        if domain.is_empty() || domain == Instance::get_local()?.public_domain {
+            return Err(Error::InvalidValue);
+        }
+        let block = UserBlock::insert(
+            conn,
+            NewUserBlock {
+                user_id: user.id,
+                domain: Some(domain),
+                ..NewUserBlock::default()
+            },
+        )?;
+        block.apply(conn)?;
+        Ok(block)
+    }
+
+    /// Removes this block, and sends an `Undo` to the blocked user if needed.
+    pub fn unblock(&self, conn: &Connection) -> Result<()> {
+        diesel::delete(self).execute(conn)?;
+        if let Some(blocked_id) = self.blocked_id {
+            let undo = self.build_undo(conn)?;
+            Delivery::broadcast(
+                conn,
+                &User::get(conn, self.user_id)?,
+                undo,
+                vec![User::get(conn, blocked_id)?],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The users this block is about.
+    pub fn get_blocked(&self, conn: &Connection) -> Result<Vec<User>> {
+        match (self.blocked_id, &self.domain) {
+            (Some(id), _) => Ok(vec![User::get(conn, id)?]),
+            (None, Some(domain)) => match Instance::find_by_domain(conn, domain) {
+                Ok(instance) => users::table
+                    .filter(users::instance_id.eq(instance.id))
+                    .load::<User>(conn)
+                    .map_err(Error::from),
+                Err(_) => Ok(vec![]),
+            },
+            (None, None) => Ok(vec![]),
+        }
+    }
+
+    /// Removes the follows between the blocking user and the blocked ones,
+    /// and the notifications and timeline entries about the blocked users.
+    fn apply(&self, conn: &Connection) -> Result<()> {
+        let user = User::get(conn, self.user_id)?;
+        for blocked in self.get_blocked(conn)? {
+            if let Ok(follow) = Follow::find(conn, user.id, blocked.id) {
+                let undo = follow.build_undo(conn)?;
+                follow.activity(conn, user.clone(), "")?;
+                // Remote users blocking someone here unfollow them on their own
+                if user.is_local() {
+                    Delivery::broadcast(conn, &user, undo, vec![blocked.clone()])?;
+                }
+            }
+            if let Ok(follow) = Follow::find(conn, blocked.id, user.id) {
+                follow.activity(conn, blocked, "")?;
+            }
+        }
+
+        for notification in Notification::find_for_user(conn, &user)? {
+            if let Ok(actor) = notification.get_actor(conn) {
+                if UserBlock::is_blocked(conn, &user, &actor)? {
+                    notification.delete(conn)?;
+                }
+            }
+        }
+        Timeline::remove_hidden_for_user(conn, &user)
+    }
+
+    pub fn to_activity(&self, conn: &Connection) -> Result<BlockAct> {
+        let user = User::get(conn, self.user_id)?;
+        let target = User::get(conn, self.blocked_id.ok_or(Error::InvalidValue)?)?;
+        let target_id = target.ap_url.parse::<IriString>()?;
+
+        let mut act = BlockAct::new(user.ap_url.parse::<IriString>()?, target_id.clone());
+        act.set_id(self.ap_url.parse::<IriString>()?);
+        act.set_many_tos(vec![target_id]);
+
+        Ok(act)
+    }
+
+    pub fn build_undo(&self, conn: &Connection) -> Result<Undo> {
+        let user = User::get(conn, self.user_id)?;
+        let target = User::get(conn, self.blocked_id.ok_or(Error::InvalidValue)?)?;
+
+        let mut undo = Undo::new(
+            user.ap_url.parse::<IriString>()?,
+            AnyBase::from_extended(self.to_activity(conn)?)?,
+        );
+        undo.set_id(format!("{}/undo", self.ap_url).parse::<IriString>()?);
+        undo.set_many_tos(vec![target.ap_url.parse::<IriString>()?]);
+
+        Ok(undo)
+    }
+}
+
+impl AsObject<User, BlockAct, &Connection> for User {
+    type Error = Error;
+    type Output = ();
+
+    fn activity(self, conn: &Connection, actor: User, id: &str) -> Result<()> {
+        if UserBlock::find_by_ap_url(conn, id).is_ok() {
+            return Ok(());
+        }
+        // Other instances only tell us about the blocks of our users
+        if !self.is_local() {
+            return Err(Error::Unauthorized);
+        }
+        let block = UserBlock::insert(
+            conn,
+            NewUserBlock {
+                user_id: actor.id,
+                blocked_id: Some(self.id),
+                domain: None,
+                ap_url: id.to_owned(),
+            },
+        )?;
+        block.apply(conn)
+    }
+}
+
+impl FromId<Connection> for UserBlock {
+    type Error = Error;
+    type Object = BlockAct;
+
+    fn from_db(conn: &Connection, id: &str) -> Result<Self> {
+        UserBlock::find_by_ap_url(conn, id)
+    }
+
+    fn from_activity(_conn: &Connection, _act: BlockAct) -> Result<Self> {
+        // Only `Undo` needs to find a block from its id, and undoing an unknown block does nothing
+        Err(Error::NotFound)
+    }
+
+    fn get_sender() -> &'static dyn Signer {
+        Instance::get_local_instance_user().expect("Failed to local instance user")
+    }
+}
+
+impl AsObject<User, Undo, &Connection> for UserBlock {
+    type Error = Error;
+    type Output = ();
+
+    fn activity(self, conn: &Connection, actor: User, _id: &str) -> Result<()> {
+        if self.user_id == actor.id {
+            diesel::delete(&self).execute(conn)?;
+            Ok(())
+        } else {
+            Err(Error::Unauthorized)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        follows::NewFollow,
+        inbox::{inbox, tests::fill_database},
+        schema::timeline,
+        tests::db,
+        users::tests as user_tests,
+    };
+    use diesel::Connection;
+    use serde_json::json;
+
+    #[test]
+    fn block_and_unblock() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let users = user_tests::fill_database(conn);
+            Follow::insert(
+                conn,
+                NewFollow {
+                    follower_id: users[1].id,
+                    following_id: users[0].id,
+                    ap_url: String::new(),
+                },
+            )?;
+            assert!(!UserBlock::is_blocked(conn, &users[0], &users[1])?);
+
+            let block = UserBlock::block(conn, &users[0], &users[1])?;
+            assert!(UserBlock::is_blocked(conn, &users[0], &users[1])?);
+            assert!(!UserBlock::is_blocked(conn, &users[1], &users[0])?);
+            assert!(!users[1].is_following(conn, users[0].id)?);
+            assert!(UserBlock::block(conn, &users[0], &users[0]).is_err());
+
+            block.unblock(conn)?;
+            assert!(!UserBlock::is_blocked(conn, &users[0], &users[1])?);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn block_hides_posts() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let (posts, users, _) = fill_database(conn);
+            let feed = Timeline::list_for_user(conn, Some(users[1].id))?.remove(0);
+            feed.add_post(conn, &posts[0])?;
+            let count = || {
+                timeline::table
+                    .filter(timeline::timeline_id.eq(feed.id))
+                    .count()
+                    .get_result::<i64>(conn)
+            };
+            assert_eq!(count()?, 1);
+
+            UserBlock::block(conn, &users[1], &users[0])?;
+            assert_eq!(count()?, 0);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn block_domain() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let users = user_tests::fill_database(conn);
+            let remote = Instance::get_remotes(conn)?.remove(0);
+            let mut other = users[2].clone();
+            other.instance_id = remote.id;
+            other.save_changes::<User>(conn)?;
+
+            UserBlock::block_domain(conn, &users[0], &format!(" {} ", remote.public_domain))?;
+            assert!(UserBlock::is_blocked(conn, &users[0], &other)?);
+            assert!(!UserBlock::is_blocked(conn, &users[0], &users[1])?);
+            assert!(UserBlock::find_domain(conn, users[0].id, &remote.public_domain).is_ok());
+            assert!(UserBlock::block_domain(
+                conn,
+                &users[0],
+                &Instance::get_local()?.public_domain
+            )
+            .is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn incoming_block() {
+        let conn = &db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let users = user_tests::fill_database(conn);
+            let block = json!({
+                "id": "https://plu.me/blocks/remote",
+                "type": "Block",
+                "actor": users[1].ap_url,
+                "object": users[0].ap_url,
+            });
+            inbox(conn, block.clone())?;
+            assert!(UserBlock::is_blocked(conn, &users[1], &users[0])?);
+
+            inbox(
+                conn,
+                json!({
+                    "id": "https://plu.me/blocks/remote/undo",
+                    "type": "Undo",
+                    "actor": users[1].ap_url,
+                    "object": block,
+                }),
+            )?;
+            assert!(!UserBlock::is_blocked(conn, &users[1], &users[0])?);
+
+            // Undoing a block that doesn't exist doesn't create it
+            assert!(inbox(
+                conn,
+                json!({
+                    "id": "https://plu.me/blocks/forged/undo",
+                    "type": "Undo",
+                    "actor": users[2].ap_url,
+                    "object": {
+                        "id": "https://plu.me/blocks/forged",
+                        "type": "Block",
+                        "actor": users[1].ap_url,
+                        "object": users[0].ap_url,
+                    },
+                }),
+            )
+            .is_err());
+            assert!(UserBlock::find_by_ap_url(conn, "https://plu.me/blocks/forged").is_err());
+            assert!(!UserBlock::is_blocked(conn, &users[1], &users[0])?);
+
+            // Blocks between users of other instances are not saved
+            let mut other = users[2].clone();
+            other.instance_id = Instance::get_remotes(conn)?[0].id;
+            other.save_changes::<User>(conn)?;
+            assert!(inbox(
+                conn,
+                json!({
+                    "id": "https://plu.me/blocks/remote/2",
+                    "type": "Block",
+                    "actor": users[1].ap_url,
+                    "object": other.ap_url,
+                }),
+            )
+            .is_err());
+            assert!(!UserBlock::is_blocked(conn, &users[1], &other)?);
+            Ok(())
+        });
+    }
+}
@@ -1,11 +1,25 @@
 use crate::{
-    ap_url, blocklisted_emails::BlocklistedEmail, blogs::Blog, comments::Comment, db_conn::DbConn,
-    follows::Follow, instance::*, medias::Media, notifications::Notification,
-    post_authors::PostAuthor, posts::Post, safe_string::SafeString, schema::users,
-    timeline::Timeline, Connection, Error, Result, UserEvent::*, CONFIG, ITEMS_PER_PAGE, USER_CHAN,
+    ap_url,
+    blocklisted_emails::BlocklistedEmail,
+    blogs::Blog,
+    comments::Comment,
+    db_conn::DbConn,
+    deliveries::Delivery,
+    follows::Follow,
+    instance::*,
+    medias::Media,
+    notifications::Notification,
+    post_authors::PostAuthor,
+    posts::{FeaturedOwner, Post},
+    safe_string::SafeString,
+    schema::users,
+    timeline::Timeline,
+    Connection, Error, Result,
+    UserEvent::*,
+    CONFIG, ITEMS_PER_PAGE, USER_CHAN,
 };
 use activitystreams::{
-    activity::Delete,
+    activity::{Delete, Move},
     actor::{ApActor, AsApActor, Endpoints, Person},
     base::{AnyBase, Base},
     collection::{OrderedCollection, OrderedCollectionPage},
@@ -31,8 +45,8 @@ use plume_common::{
         inbox::{AsActor, AsObject, FromId},
         request::get,
         sign::{gen_keypair, Error as SignError, Result as SignResult, Signer},
-        ActivityStream, ApSignature, CustomPerson, Id, IntoId, PublicKey, ToAsString, ToAsUri,
-        PUBLIC_VISIBILITY,
+        ActivityStream, ApSignature, CustomPerson, FeaturedProperty, Id, IntoId, MovedProperty,
+        PublicKey, ToAsString, ToAsUri, PUBLIC_VISIBILITY,
     },
     utils,
 };
@@ -84,6 +98,10 @@ pub struct User {
     pub role: i32,
     pub preferred_theme: Option<String>,
     pub hide_custom_css: bool,
+    /// The ActivityPub IDs of the other accounts of this user, one per line
+    pub also_known_as: String,
+    /// The ActivityPub ID of the account this user moved to
+    pub moved_to: Option<String>,
 }
 
 #[derive(Default, Insertable)]
@@ -332,9 +350,13 @@ impl User {
                     users::last_fetched_date.eq(Utc::now().naive_utc()),
                     users::public_key.eq(pub_key),
                 ))
-                .execute(conn)
-                .map(|_| ())
-                .map_err(Error::from)
+                .execute(conn)?;
+            self.set_moved_property(conn, &json.ext_three)?;
+
+            if let Some(featured) = &json.ext_two.featured {
+                FeaturedOwner::Author(Arc::new(self.clone())).featured_found(featured.to_string());
+            }
+            Ok(())
         })
     }
 
@@ -821,7 +843,46 @@ impl User {
             actor.set_icon(avatar.into_any_base()?);
         }
 
-        Ok(CustomPerson::new(actor, ap_signature))
+        let featured = FeaturedProperty {
+            featured: Some(self.featured_url().parse()?),
+        };
+        let moved = MovedProperty {
+            also_known_as: self
+                .aliases()
+                .into_iter()
+                .map(|alias| alias.parse::<IriString>())
+                .collect::<std::result::Result<_, _>>()?,
+            moved_to: self
+                .moved_to
+                .as_ref()
+                .map(|url| url.parse::<IriString>())
+                .transpose()?,
+        };
+
+        Ok(CustomPerson::new(actor, ap_signature, featured, moved))
+    }
+
+    /// The URL of the collection of the posts this user pinned in their blogs
+    pub fn featured_url(&self) -> String {
+        format!("{}/featured", self.ap_url.trim_end_matches('/'))
+    }
+
+    pub fn featured(&self, conn: &Connection) -> Result<ActivityStream<OrderedCollection>> {
+        self.featured_collection(conn).map(ActivityStream::new)
+    }
+    pub fn featured_collection(&self, conn: &Connection) -> Result<OrderedCollection> {
+        let posts = Post::list_pinned_for_author(conn, self)?
+            .into_iter()
+            .map(|post| {
+                let article = serde_json::to_value(post.to_activity(conn)?)?;
+                Ok(AnyBase::from_arbitrary_json(article)?)
+            })
+            .collect::<Result<Vec<AnyBase>>>()?;
+        let mut coll = OrderedCollection::new();
+        coll.set_id(self.featured_url().parse::<IriString>()?);
+        coll.set_total_items(posts.len() as u64);
+        coll.set_many_ordered_items(posts);
+        Ok(coll)
     }
 
     pub fn delete_activity(&self, conn: &Connection) -> Result<Delete> {
@@ -843,6 +904,136 @@ impl User {
         Ok(del)
     }
 
+    /// The ActivityPub IDs of the other accounts of this user
+    pub fn aliases(&self) -> Vec<&str> {
+        self.also_known_as
+            .lines()
+            .filter(|alias| !alias.is_empty())
+            .collect()
+    }
+
+    /// Replaces the other accounts of this user, given as `user@domain` or as URLs, one per line.
+    pub fn set_aliases(&mut self, conn: &Connection, aliases: &str) -> Result<()> {
+        let mut ap_urls = vec![];
+        for alias in aliases
+            .lines()
+            .map(|alias| alias.trim().trim_start_matches('@'))
+            .filter(|alias| !alias.is_empty())
+        {
+            let account = if alias.starts_with("https://") || alias.starts_with("http://") {
+                User::from_id(conn, alias, None, CONFIG.proxy()).map_err(|(_, e)| e)?
+            } else {
+                User::find_by_fqn(conn, alias)?
+            };
+            if account.id == self.id {
+                return Err(Error::InvalidValue);
+            }
+            if !ap_urls.contains(&account.ap_url) {
+                ap_urls.push(account.ap_url);
+            }
+        }
+
+        self.also_known_as = ap_urls.join("\n");
+        diesel::update(&*self)
+            .set(users::also_known_as.eq(&self.also_known_as))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Moves this account to `target`, that must list this account as one of its aliases.
+    ///
+    /// Remote followers receive a `Move` activity, and local ones directly follow `target`.
+    pub fn move_to(&mut self, conn: &Connection, target: &User) -> Result<()> {
+        if !self.is_local() || target.id == self.id {
+            return Err(Error::InvalidValue);
+        }
+        let target = target.with_fresh_aliases(conn)?;
+        if !target.aliases().contains(&self.ap_url.as_str()) {
+            return Err(Error::Unauthorized);
+        }
+
+        self.moved_to = Some(target.ap_url.clone());
+        diesel::update(&*self)
+            .set(users::moved_to.eq(&self.moved_to))
+            .execute(conn)?;
+        Delivery::broadcast(
+            conn,
+            &*self,
+            self.move_activity(&target)?,
+            self.get_followers(conn)?,
+        )?;
+        Follow::move_followers(conn, self, &target)
+    }
+
+    pub fn move_activity(&self, target: &User) -> Result<Move> {
+        let ap_url = self.ap_url.parse::<IriString>()?;
+        let mut act = Move::new(ap_url.clone(), ap_url);
+        act.set_id(format!("{}#move-{}", self.ap_url, Utc::now().timestamp()).parse()?);
+        act.set_target(target.ap_url.parse::<IriString>()?);
+        act.set_many_tos(vec![self.followers_endpoint.parse::<IriString>()?]);
+        act.set_many_ccs(vec![PUBLIC_VISIBILITY.parse::<IriString>()?]);
+        Ok(act)
+    }
+
+    /// Handles an incoming `Move` activity.
+    ///
+    /// The account it points to is fetched again, and the move is only accepted if it lists the
+    /// old account as one of its aliases. Local followers then follow the new account instead.
+    pub fn from_move(conn: &Connection, act: &serde_json::Value) -> Result<()> {
+        fn id_of(value: &serde_json::Value) -> Option<&str> {
+            value.as_str().or_else(|| value["id"].as_str())
+        }
+
+        let actor = id_of(&act["actor"]).ok_or(Error::MissingApProperty)?;
+        if id_of(&act["object"]) != Some(actor) {
+            return Err(Error::Unauthorized);
+        }
+        let target = id_of(&act["target"]).ok_or(Error::MissingApProperty)?;
+
+        let old = User::find_by_ap_url(conn, actor)?;
+        if old.is_local() {
+            return Err(Error::Unauthorized);
+        }
+        let target = match User::find_by_ap_url(conn, target) {
+            Ok(user) => user.with_fresh_aliases(conn)?,
+            Err(_) => User::from_id(conn, target, None, CONFIG.proxy()).map_err(|(_, e)| e)?,
+        };
+        if target.id == old.id || !target.aliases().contains(&old.ap_url.as_str()) {
+            return Err(Error::Unauthorized);
+        }
+
+        diesel::update(&old)
+            .set(users::moved_to.eq(&target.ap_url))
+            .execute(conn)?;
+        Follow::move_followers(conn, &old, &target)
+    }
+
+    /// This user, with up-to-date aliases if it is a remote one
+    fn with_fresh_aliases(&self, conn: &Connection) -> Result<User> {
+        if self.is_local() {
+            Ok(self.clone())
+        } else {
+            self.refetch(conn)?;
+            User::get(conn, self.id)
+        }
+    }
+
+    fn set_moved_property(&self, conn: &Connection, moved: &MovedProperty) -> Result<()> {
+        let also_known_as = moved
+            .also_known_as
+            .iter()
+            .map(|alias| alias.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        diesel::update(self)
+            .set((
+                users::also_known_as.eq(also_known_as),
+                users::moved_to.eq(moved.moved_to.as_ref().map(|url| url.to_string())),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
     pub fn avatar_url(&self, conn: &Connection) -> String {
         self.avatar_id
             .and_then(|id| Media::get(conn, id).and_then(|m| m.url()).ok())
@@ -1007,6 +1198,8 @@ impl FromId<Connection> for User {
         };
 
         let avatar_id = acct.object_ref().icon().and_then(|icon| icon.to_as_uri());
+        let featured = acct.ext_two.featured.as_ref().map(|url| url.to_string());
+        let moved = acct.ext_three.clone();
 
         let (ap_url, inst) = {
             let any_base = acct.into_any_base()?;
@@ -1046,6 +1239,7 @@ impl FromId<Connection> for User {
         };
 
         let user = User::insert(conn, new_user)?;
+        user.set_moved_property(conn, &moved)?;
         if let Some(avatar_id) = avatar_id {
             let avatar = Media::save_remote(conn, avatar_id, &user);
 
@@ -1055,6 +1249,9 @@ impl FromId<Connection> for User {
                 }
             }
         }
+        if let Some(featured) = featured {
+            FeaturedOwner::Author(Arc::new(user.clone())).featured_found(featured);
+        }
 
         Ok(user)
     }
@@ -1150,7 +1347,9 @@ impl NewUser {
                 display_name,
                 role: role as i32,
                 summary: summary.to_owned(),
-                summary_html: SafeString::new(&utils::md_to_html(summary, None, false, None).0),
+                summary_html: SafeString::new(
+                    &utils::md_to_html(summary, None, false, None, None).0,
+                ),
                 email: Some(email),
                 hashed_password: password,
                 instance_id: instance.id,
@@ -1176,16 +1375,8 @@ impl NewUser {
 #[derive(Clone, Debug)]
 pub enum UserEvent {
     RemoteUserFound(Arc<User>),
-}
-
-impl From<UserEvent> for Arc<User> {
-    fn from(event: UserEvent) -> Self {
-        use UserEvent::*;
-
-        match event {
-            RemoteUserFound(user) => user,
-        }
-    }
+    RemoteFeaturedFound(FeaturedOwner, String),
+    RemoteEmojiFound(i32, Arc<User>),
 }
 
 #[cfg(test)]
@@ -1260,7 +1451,7 @@ pub(crate) mod tests {
         Vec<crate::blogs::Blog>,
     ) {
         use crate::post_authors::NewPostAuthor;
-        use crate::posts::NewPost;
+        use crate::posts::{NewPost, Visibility};
 
         let (mut posts, users, blogs) = crate::inbox::tests::fill_database(conn);
         let user = &users[0];
@@ -1283,6 +1474,13 @@ pub(crate) mod tests {
                     subtitle: "".into(),
                     source: content,
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -1478,6 +1676,7 @@ pub(crate) mod tests {
                 "inbox": "https://plu.me/@/admin/inbox",
                 "name": "The admin",
                 "outbox": "https://plu.me/@/admin/outbox",
+                "featured": "https://plu.me/@/admin/featured",
                 "preferredUsername": "admin",
                 "publicKey": {
                     "id": "https://plu.me/@/admin/#main-key",
@@ -1506,6 +1705,7 @@ pub(crate) mod tests {
                 "inbox": "https://plu.me/@/other/inbox",
                 "name": "Another user",
                 "outbox": "https://plu.me/@/other/outbox",
+                "featured": "https://plu.me/@/other/featured",
                 "preferredUsername": "other",
                 "publicKey": {
                     "id": "https://plu.me/@/other/#main-key",
@@ -1590,4 +1790,47 @@ pub(crate) mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn move_to() {
+        let conn = db();
+        conn.test_transaction::<_, Error, _>(|| {
+            let mut users = fill_database(&conn);
+            let follower = users[0].clone();
+            let mut target = users[2].clone();
+            let old = &mut users[1];
+            let follow = Follow::insert(
+                &conn,
+                crate::follows::NewFollow {
+                    follower_id: follower.id,
+                    following_id: old.id,
+                    ap_url: String::new(),
+                },
+            )?;
+
+            // The new account has to list the old one first
+            assert!(old.move_to(&conn, &target).is_err());
+            assert!(old.moved_to.is_none());
+
+            target.set_aliases(&conn, &format!("@{}\n", old.fqn))?;
+            assert_eq!(target.aliases(), vec![old.ap_url.as_str()]);
+            assert_eq!(
+                target.to_activity(&conn)?.ext_three.also_known_as,
+                vec![old.ap_url.parse::<IriString>()?]
+            );
+            assert!(target.set_aliases(&conn, &target.fqn.clone()).is_err());
+
+            old.move_to(&conn, &target)?;
+            assert_eq!(
+                User::get(&conn, old.id)?.moved_to,
+                Some(target.ap_url.clone())
+            );
+            let moved = Follow::find(&conn, follower.id, target.id)?;
+            assert_ne!(moved.ap_url, follow.ap_url);
+            assert!(Follow::find_by_ap_url(&conn, &follow.ap_url).is_err());
+            assert!(Follow::find(&conn, follower.id, old.id).is_err());
+
+            Ok(())
+        });
+    }
 }
@@ -1,13 +1,14 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use rocket_contrib::json::Json;
 
 use crate::api::{authorization::*, Api, ApiError};
 use plume_api::posts::*;
-use plume_common::{activity_pub::broadcast, utils::md_to_html};
+use plume_common::utils::md_to_html;
 use plume_models::{
-    blogs::Blog, db_conn::DbConn, instance::Instance, medias::Media, mentions::*, post_authors::*,
-    posts::*, safe_string::SafeString, tags::*, timeline::*, users::User, Error, PlumeRocket,
-    CONFIG,
+    blogs::Blog, db_conn::DbConn, deliveries::Delivery, emojis::Emoji, instance::Instance,
+    medias::Media, mentions::*, post_authors::*, post_revisions::PostRevision,
+    post_templates::PostTemplate, posts::*, safe_string::SafeString, tags::*, timeline::*,
+    users::User, Error,
 };
 
 #[get("/posts/<id>")]
@@ -17,13 +18,24 @@ pub fn get(id: i32, auth: Option<Authorization<Read, Post>>, conn: DbConn) -> Ap
 
     if !post.published
         && !user
+            .clone()
             .and_then(|u| post.is_author(&conn, u.id).ok())
             .unwrap_or(false)
     {
         return Err(Error::Unauthorized.into());
     }
+    if !post.can_see(&conn, user.as_ref())? {
+        return Err(Error::Unauthorized.into());
+    }
 
     Ok(Json(PostData {
+        visibility: post.visibility().as_str().to_owned(),
+        sensitive: post.sensitive,
+        content_warning: post.content_warning.clone(),
+        canonical_url: post.canonical_url.clone(),
+        language: post.language.clone(),
+        word_count: post.word_count,
+        reading_time: post.reading_time,
         authors: post
             .get_authors(&conn)?
             .into_iter()
@@ -44,6 +56,7 @@ pub fn get(id: i32, auth: Option<Authorization<Read, Post>>, conn: DbConn) -> Ap
         published: post.published,
         license: post.license,
         cover_id: post.cover_id,
+        publish_at: post.publish_at.map(format_publish_date),
     }))
 }
 
@@ -56,7 +69,7 @@ pub fn list(
     conn: DbConn,
 ) -> Api<Vec<PostData>> {
     let user = auth.and_then(|a| User::get(&conn, a.0.user_id).ok());
-    let user_id = user.map(|u| u.id);
+    let user_id = user.as_ref().map(|u| u.id);
 
     Ok(Json(
         Post::list_filtered(&conn, title, subtitle, content)?
@@ -67,8 +80,16 @@ pub fn list(
                         .and_then(|u| p.is_author(&conn, u).ok())
                         .unwrap_or(false)
             })
+            .filter(|p| p.can_see(&conn, user.as_ref()).unwrap_or(false))
             .filter_map(|p| {
                 Some(PostData {
+                    visibility: p.visibility().as_str().to_owned(),
+                    sensitive: p.sensitive,
+                    content_warning: p.content_warning.clone(),
+                    canonical_url: p.canonical_url.clone(),
+                    language: p.language.clone(),
+                    word_count: p.word_count,
+                    reading_time: p.reading_time,
                     authors: p
                         .get_authors(&conn)
                         .ok()?
@@ -91,6 +112,7 @@ pub fn list(
                     published: p.published,
                     license: p.license,
                     cover_id: p.cover_id,
+                    publish_at: p.publish_at.map(format_publish_date),
                 })
             })
             .collect(),
@@ -102,27 +124,55 @@ pub fn create(
     auth: Authorization<Write, Post>,
     payload: Json<NewPostData>,
     conn: DbConn,
-    rockets: PlumeRocket,
 ) -> Api<PostData> {
-    let worker = &rockets.worker;
-
     let author = User::get(&conn, auth.0.user_id)?;
 
-    let slug = Post::slug(&payload.title);
+    let template = match payload.template_id {
+        Some(id) => {
+            let template = PostTemplate::get(&conn, id)?;
+            if !author.is_author_in(&conn, &Blog::get(&conn, template.blog_id)?)? {
+                return Err(Error::Unauthorized.into());
+            }
+            Some(template)
+        }
+        None => None,
+    };
+    let title = match template {
+        Some(ref t) if payload.title.is_empty() => t.title_for(Utc::now().naive_utc().date()),
+        _ => payload.title.clone(),
+    };
+    let source = match template {
+        Some(ref t) if payload.source.is_empty() => t.source.clone(),
+        _ => payload.source.clone(),
+    };
+
+    let slug = Post::slug(&title);
     let date = payload.creation_date.clone().and_then(|d| {
         NaiveDateTime::parse_from_str(format!("{} 00:00:00", d).as_ref(), "%Y-%m-%d %H:%M:%S").ok()
     });
 
+    let publish_at = match payload.publish_at {
+        Some(ref d) => Some(
+            DateTime::parse_from_rfc3339(d)
+                .map_err(|_| ApiError(Error::InvalidValue))?
+                .naive_utc(),
+        ),
+        None => None,
+    }
+    .filter(|d| payload.published != Some(false) && *d > Utc::now().naive_utc());
+
     let domain = &Instance::get_local()?.public_domain;
     let (content, mentions, hashtags) = md_to_html(
-        &payload.source,
+        &source,
         Some(domain),
         false,
         Some(Media::get_media_processor(&conn, vec![&author])),
+        Some(Emoji::get_emoji_processor(&conn)),
     );
 
     let blog = payload
         .blog_id
+        .or_else(|| template.as_ref().map(|t| t.blog_id))
         .or_else(|| {
             let blogs = Blog::find_for_author(&conn, &author).ok()?;
             if blogs.len() == 1 {
@@ -133,6 +183,9 @@ pub fn create(
         })
         .ok_or(ApiError(Error::NotFound))?;
 
+    if template.as_ref().map_or(false, |t| t.blog_id != blog) {
+        return Err(Error::InvalidValue.into());
+    }
     if Post::find_by_slug(&conn, slug, blog).is_ok() {
         return Err(Error::InvalidValue.into());
     }
@@ -142,19 +195,47 @@ pub fn create(
         NewPost {
             blog_id: blog,
             slug: slug.to_string(),
-            title: payload.title.clone(),
+            title,
             content: SafeString::new(content.as_ref()),
-            published: payload.published.unwrap_or(true),
-            license: payload.license.clone().unwrap_or_else(|| {
-                Instance::get_local()
-                    .map(|i| i.default_license)
-                    .unwrap_or_else(|_| String::from("CC-BY-SA"))
-            }),
+            published: payload.published.unwrap_or(true) && publish_at.is_none(),
+            license: payload
+                .license
+                .clone()
+                .or_else(|| {
+                    template
+                        .as_ref()
+                        .map(|t| t.license.clone())
+                        .filter(|l| !l.is_empty())
+                })
+                .unwrap_or_else(|| {
+                    Instance::get_local()
+                        .map(|i| i.default_license)
+                        .unwrap_or_else(|_| String::from("CC-BY-SA"))
+                }),
             creation_date: date,
             ap_url: String::new(),
             subtitle: payload.subtitle.clone().unwrap_or_default(),
-            source: payload.source.clone(),
-            cover_id: payload.cover_id,
+            source,
+            cover_id: payload.cover_id.or_else(|| {
+                template
+                    .as_ref()
+                    .and_then(|t| t.cover_id)
+                    .filter(|id| Media::get(&conn, *id).map_or(false, |m| m.owner_id == author.id))
+            }),
+            publish_at,
+            visibility: match payload.visibility {
+                Some(ref v) => v.parse::<Visibility>()?,
+                None => Visibility::Public,
+            }
+            .into(),
+            sensitive: payload.content_warning.is_some(),
+            content_warning: payload.content_warning.clone().filter(|cw| !cw.is_empty()),
+            canonical_url: payload
+                .canonical_url
+                .clone()
+                .filter(|url| url.starts_with("https://") || url.starts_with("http://")),
+            language: payload.language.clone().filter(|l| is_valid_language(l)),
+            translation_of: None,
         },
     )?;
 
@@ -165,8 +246,13 @@ pub fn create(
             post_id: post.id,
         },
     )?;
+    PostRevision::snapshot(&conn, &post, Some(&author))?;
 
-    if let Some(ref tags) = payload.tags {
+    let tags = payload
+        .tags
+        .clone()
+        .or_else(|| template.as_ref().map(|t| t.tag_list()));
+    if let Some(ref tags) = tags {
         for tag in tags {
             Tag::insert(
                 &conn,
@@ -201,13 +287,20 @@ pub fn create(
         }
 
         let act = post.create_activity(&conn)?;
-        let dest = User::one_by_instance(&conn)?;
-        worker.execute(move || broadcast(&author, act, dest, CONFIG.proxy().cloned()));
-    }
+        let dest = post.get_receivers(&conn)?;
+        Delivery::broadcast(&conn, &author, act, dest)?;
 
-    Timeline::add_to_all_timelines(&conn, &post, Kind::Original)?;
+        Timeline::add_to_all_timelines(&conn, &post, Kind::Original)?;
+    }
 
     Ok(Json(PostData {
+        visibility: post.visibility().as_str().to_owned(),
+        sensitive: post.sensitive,
+        content_warning: post.content_warning.clone(),
+        canonical_url: post.canonical_url.clone(),
+        language: post.language.clone(),
+        word_count: post.word_count,
+        reading_time: post.reading_time,
         authors: post
             .get_authors(&conn)?
             .into_iter()
@@ -228,6 +321,7 @@ pub fn create(
         published: post.published,
         license: post.license,
         cover_id: post.cover_id,
+        publish_at: post.publish_at.map(format_publish_date),
     }))
 }
 
@@ -241,3 +335,7 @@ pub fn delete(auth: Authorization<Write, Post>, conn: DbConn, id: i32) -> Api<()
     }
     Ok(Json(()))
 }
+
+fn format_publish_date(date: NaiveDateTime) -> String {
+    DateTime::<Utc>::from_utc(date, Utc).to_rfc3339()
+}
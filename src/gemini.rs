@@ -0,0 +1,428 @@
+//! A read-only Gemini mirror of the local blogs.
+//!
+//! Posts are served as gemtext converted from their Markdown source, following the same
+//! visibility rules as the web interface for anonymous readers.
+
+use atom_syndication::{EntryBuilder, FeedBuilder, LinkBuilder, Person, PersonBuilder, Text};
+use chrono::{DateTime, Utc};
+use native_tls::{Identity, TlsAcceptor, TlsStream};
+use plume_common::utils::{iri_percent_encode_seg, md_to_gemtext};
+use plume_models::{
+    blog_slugs::BlogSlug, blogs::Blog, config::GeminiConfig, db_conn::DbPool, instance::Instance,
+    medias::Media, post_slugs::PostSlug, posts::Post, Connection, Error, CONFIG, ITEMS_PER_PAGE,
+};
+use rocket::http::RawStr;
+use scheduled_thread_pool::ScheduledThreadPool;
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// The longest URL a request can contain, according to the Gemini specification
+const MAX_REQUEST_LENGTH: usize = 1024;
+/// How long a client has to send its request and to receive the response
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+enum Response {
+    /// 20: the MIME type and the body of the response
+    Success(String, String),
+    /// 31: the new address of the requested page
+    Redirect(String),
+    /// 40: something went wrong on our side
+    TemporaryFailure,
+    /// 51
+    NotFound,
+    /// 53: the request was for another host
+    ProxyRefused,
+    /// 59
+    BadRequest,
+}
+
+impl Response {
+    fn gemtext(body: String, lang: Option<&str>) -> Self {
+        match lang {
+            Some(lang) => Response::Success(format!("text/gemini; lang={}", lang), body),
+            None => Response::Success("text/gemini".to_owned(), body),
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            Response::Success(mime, body) => {
+                write!(stream, "20 {}\r\n", mime)?;
+                stream.write_all(body.as_bytes())
+            }
+            Response::Redirect(url) => write!(stream, "31 {}\r\n", url),
+            Response::TemporaryFailure => write!(stream, "40 Internal error\r\n"),
+            Response::NotFound => write!(stream, "51 Not found\r\n"),
+            Response::ProxyRefused => write!(stream, "53 Proxy request refused\r\n"),
+            Response::BadRequest => write!(stream, "59 Bad request\r\n"),
+        }
+    }
+}
+
+impl From<Error> for Response {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::NotFound | Error::Db(diesel::result::Error::NotFound) => Response::NotFound,
+            e => {
+                warn!("Gemini: {:?}", e);
+                Response::TemporaryFailure
+            }
+        }
+    }
+}
+
+/// Starts listening for Gemini requests in the background.
+pub fn start(config: &'static GeminiConfig, dbpool: DbPool) {
+    let certificate =
+        fs::read(&config.tls_certificate).expect("gemini: couldn't read GEMINI_TLS_CERTIFICATE");
+    let key = fs::read(&config.tls_key).expect("gemini: couldn't read GEMINI_TLS_KEY");
+    let acceptor = Identity::from_pkcs8(&certificate, &key)
+        .and_then(TlsAcceptor::new)
+        .expect("gemini: invalid TLS certificate or key");
+    let acceptor = Arc::new(acceptor);
+    let listener = TcpListener::bind((config.address.as_str(), config.port))
+        .expect("gemini: couldn't listen for connections");
+    info!("Serving Gemini on {}:{}", config.address, config.port);
+
+    thread::Builder::new()
+        .name("gemini".to_owned())
+        .spawn(move || {
+            let workers = ScheduledThreadPool::with_name("gemini {}", num_cpus::get());
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let acceptor = acceptor.clone();
+                        let dbpool = dbpool.clone();
+                        workers.execute(move || handle(stream, &acceptor, &dbpool, config));
+                    }
+                    Err(e) => warn!("Gemini: failed to accept a connection: {}", e),
+                }
+            }
+        })
+        .expect("gemini: couldn't start the server");
+}
+
+/// A connection that fails once its deadline has passed, so that clients sending or reading
+/// a few bytes at a time can't keep a worker busy.
+#[derive(Debug)]
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineStream {
+    fn remaining(&self) -> io::Result<Duration> {
+        self.deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "connection deadline passed"))
+    }
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.remaining()?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.remaining()?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn handle(stream: TcpStream, acceptor: &TlsAcceptor, dbpool: &DbPool, config: &GeminiConfig) {
+    let stream = DeadlineStream {
+        stream,
+        deadline: Instant::now() + TIMEOUT,
+    };
+    let mut stream = match acceptor.accept(stream) {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!("Gemini: TLS handshake failed: {}", e);
+            return;
+        }
+    };
+    let response = match read_request(&mut stream) {
+        Some(url) => match dbpool.get() {
+            Ok(conn) => respond(&conn, config, &url),
+            Err(_) => Response::TemporaryFailure,
+        },
+        None => Response::BadRequest,
+    };
+    if let Err(e) = response.write_to(&mut stream) {
+        warn!("Gemini: failed to send a response: {}", e);
+    }
+    let _ = stream.shutdown();
+}
+
+/// Reads the URL sent by the client, that ends with CRLF.
+fn read_request(stream: &mut TlsStream<DeadlineStream>) -> Option<String> {
+    let mut request = Vec::with_capacity(MAX_REQUEST_LENGTH + 2);
+    let mut buffer = [0; MAX_REQUEST_LENGTH + 2];
+    while !request.ends_with(b"\r\n") {
+        let read = stream.read(&mut buffer).ok()?;
+        if read == 0 || request.len() + read > MAX_REQUEST_LENGTH + 2 {
+            return None;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    request.truncate(request.len() - 2);
+    String::from_utf8(request).ok()
+}
+
+fn respond(conn: &Connection, config: &GeminiConfig, url: &str) -> Response {
+    let url = match url.strip_prefix("gemini://") {
+        Some(url) => url,
+        None => return Response::ProxyRefused,
+    };
+    let (host, path) = url.split_at(url.find('/').unwrap_or(url.len()));
+    let host = host.rsplit_once(':').map_or(host, |(host, _)| host);
+    let hostname = config.hostname.as_deref().unwrap_or(&CONFIG.base_url);
+    let hostname = hostname.rsplit_once(':').map_or(hostname, |(host, _)| host);
+    if !host.eq_ignore_ascii_case(hostname) {
+        return Response::ProxyRefused;
+    }
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    };
+    let segments = match path
+        .trim_start_matches('/')
+        .split('/')
+        .map(|segment| {
+            RawStr::from_str(segment)
+                .percent_decode()
+                .map(|s| s.into_owned())
+        })
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(segments) => segments,
+        Err(_) => return Response::BadRequest,
+    };
+
+    let response = match segments
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [""] => index(conn),
+        ["~", blog] => Ok(Response::Redirect(format!("{}/", blog_path(blog)))),
+        ["~", blog, ""] => {
+            let page = query.and_then(|q| q.parse().ok()).unwrap_or(1);
+            blog_index(conn, blog, page)
+        }
+        ["~", blog, "atom.xml"] => atom_feed(conn, config, blog),
+        ["~", blog, slug] => post(conn, blog, slug),
+        _ => Ok(Response::NotFound),
+    };
+    response.unwrap_or_else(Response::from)
+}
+
+fn blog_path(name: &str) -> String {
+    format!("/~/{}", iri_percent_encode_seg(name))
+}
+
+fn post_path(blog: &Blog, post: &Post) -> String {
+    format!(
+        "{}/{}",
+        blog_path(&blog.fqn),
+        iri_percent_encode_seg(&post.slug)
+    )
+}
+
+/// Finds a blog of this instance, or where it moved if it was renamed.
+fn find_blog(conn: &Connection, name: &str) -> Result<Result<Blog, Response>, Error> {
+    match Blog::find_by_name(conn, name, Instance::get_local()?.id) {
+        Ok(blog) => Ok(Ok(blog)),
+        Err(Error::Db(diesel::result::Error::NotFound)) => {
+            let blog = BlogSlug::find_blog(conn, name)?;
+            Ok(Err(Response::Redirect(format!(
+                "{}/",
+                blog_path(&blog.fqn)
+            ))))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn index(conn: &Connection) -> Result<Response, Error> {
+    let instance = Instance::get_local()?;
+    let mut body = format!("# {}\n\n", instance.name);
+    let description = md_to_gemtext(
+        instance.short_description.get(),
+        Some(CONFIG.base_url.as_str()),
+        None,
+    );
+    if !description.is_empty() {
+        body.push_str(&description);
+        body.push('\n');
+    }
+    body.push_str("## Blogs\n\n");
+    for blog in Blog::list_local(conn)? {
+        body.push_str(&format!("=> {}/ {}\n", blog_path(&blog.fqn), blog.title));
+    }
+    body.push_str(&format!(
+        "\n=> https://{} View on the web\n",
+        instance.public_domain
+    ));
+    Ok(Response::gemtext(body, None))
+}
+
+fn blog_index(conn: &Connection, name: &str, page: i32) -> Result<Response, Error> {
+    let blog = match find_blog(conn, name)? {
+        Ok(blog) => blog,
+        Err(redirect) => return Ok(redirect),
+    };
+    let page = page.max(1);
+    let posts = Post::blog_page(
+        conn,
+        &blog,
+        ((page - 1) * ITEMS_PER_PAGE, page * ITEMS_PER_PAGE),
+    )?;
+    let mut body = format!("# {}\n\n", blog.title);
+    if !blog.summary.is_empty() {
+        body.push_str(&md_to_gemtext(
+            &blog.summary,
+            Some(CONFIG.base_url.as_str()),
+            None,
+        ));
+        body.push('\n');
+    }
+    body.push_str(&format!(
+        "=> {}/atom.xml Atom feed\n\n",
+        blog_path(&blog.fqn)
+    ));
+    for post in posts {
+        body.push_str(&format!(
+            "=> {} {} {}\n",
+            post_path(&blog, &post),
+            post.creation_date.format("%Y-%m-%d"),
+            post.title
+        ));
+    }
+    if page > 1 {
+        body.push_str(&format!("\n=> ?{} Newer posts\n", page - 1));
+    }
+    if i64::from(page * ITEMS_PER_PAGE) < Post::count_for_blog(conn, &blog)? {
+        body.push_str(&format!("\n=> ?{} Older posts\n", page + 1));
+    }
+    body.push_str(&format!("\n=> {} View on the web\n", blog.ap_url));
+    Ok(Response::gemtext(body, None))
+}
+
+fn atom_feed(conn: &Connection, config: &GeminiConfig, name: &str) -> Result<Response, Error> {
+    let blog = match find_blog(conn, name)? {
+        Ok(blog) => blog,
+        Err(redirect) => return Ok(redirect),
+    };
+    let base_url = config.base_url(&Instance::get_local()?.public_domain);
+    let uri = format!("{}{}/atom.xml", base_url, blog_path(&blog.fqn));
+    let entries = Post::get_recents_for_blog(conn, &blog, 15)?;
+    let updated = entries
+        .first()
+        .map_or(blog.creation_date, |post| post.creation_date);
+    let entries = entries
+        .into_iter()
+        .map(|post| {
+            let url = format!("{}{}", base_url, post_path(&blog, &post));
+            Ok(EntryBuilder::default()
+                .title(post.title.clone())
+                .id(url.clone())
+                .links(vec![LinkBuilder::default().href(url).build()])
+                .summary(if post.subtitle.is_empty() {
+                    None
+                } else {
+                    Some(Text::from(post.subtitle.clone()))
+                })
+                .authors(
+                    post.get_authors(conn)?
+                        .into_iter()
+                        .map(|a| PersonBuilder::default().name(a.display_name).build())
+                        .collect::<Vec<Person>>(),
+                )
+                .updated(DateTime::<Utc>::from_utc(post.creation_date, Utc))
+                .build())
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let feed = FeedBuilder::default()
+        .title(blog.title.as_str())
+        .id(uri.as_str())
+        .updated(DateTime::<Utc>::from_utc(updated, Utc))
+        .entries(entries)
+        .links(vec![LinkBuilder::default()
+            .href(uri)
+            .rel("self")
+            .mime_type("application/atom+xml".to_string())
+            .build()])
+        .build();
+    Ok(Response::Success(
+        "application/atom+xml".to_owned(),
+        feed.to_string(),
+    ))
+}
+
+fn post(conn: &Connection, name: &str, slug: &str) -> Result<Response, Error> {
+    let blog = match find_blog(conn, name)? {
+        Ok(blog) => blog,
+        Err(redirect) => return Ok(redirect),
+    };
+    let post = match Post::find_by_slug(conn, slug, blog.id) {
+        Ok(post) => post,
+        Err(_) => {
+            let post = PostSlug::find_moved(conn, name, slug)?;
+            return Ok(Response::Redirect(post_path(&post.get_blog(conn)?, &post)));
+        }
+    };
+    // Gemini readers are anonymous
+    if !post.published || !post.can_see(conn, None)? {
+        return Ok(Response::NotFound);
+    }
+
+    let authors = post.get_authors(conn)?;
+    let mut body = format!("# {}\n\n", post.title);
+    if !post.subtitle.is_empty() {
+        body.push_str(&format!("{}\n\n", post.subtitle));
+    }
+    body.push_str(&format!(
+        "By {}, {}\n\n",
+        authors
+            .iter()
+            .map(|a| a.display_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        post.creation_date.format("%Y-%m-%d")
+    ));
+    if let Some(ref cw) = post.content_warning {
+        body.push_str(&format!("> Content warning: {}\n\n", cw));
+    }
+    body.push_str(&md_to_gemtext(
+        &post.source,
+        Some(CONFIG.base_url.as_str()),
+        Some(Media::get_media_processor(
+            conn,
+            authors.iter().collect::<Vec<_>>(),
+        )),
+    ));
+    body.push_str(&format!(
+        "\n=> {}/ {}\n=> {} View on the web\n",
+        blog_path(&blog.fqn),
+        blog.title,
+        post.ap_url
+    ));
+    Ok(Response::gemtext(body, post.language.as_deref()))
+}
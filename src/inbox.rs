@@ -4,7 +4,8 @@ use plume_common::activity_pub::{
     sign::{verify_http_headers, Signable},
 };
 use plume_models::{
-    db_conn::DbConn, headers::Headers, inbox::inbox, instance::Instance, users::User, Error, CONFIG,
+    db_conn::DbConn, headers::Headers, inbox::inbox, instance::Instance, reports::ServiceActor,
+    users::User, Error, CONFIG,
 };
 use rocket::{data::*, http::Status, response::status, Outcome::*, Request};
 use rocket_contrib::json::*;
@@ -26,8 +27,25 @@ pub fn handle_incoming(
         .or_else(|| activity["actor"]["id"].as_str())
         .ok_or(status::BadRequest(Some("Missing actor id for activity")))?;
 
-    let actor = User::from_id(&conn, actor_id, None, CONFIG.proxy())
-        .expect("instance::shared_inbox: user error");
+    let actor = match User::from_id(&conn, actor_id, None, CONFIG.proxy()) {
+        Ok(actor) => actor,
+        // Reports are usually sent by the instance actor, not by a user
+        Err(_) if act["type"] == "Flag" => {
+            let service = ServiceActor::fetch(actor_id)
+                .map_err(|_| status::BadRequest(Some("Unknown actor")))?;
+            if !verify_http_headers(&service, &headers.0, &sig).is_secure()
+                && !act.clone().verify(&service)
+            {
+                warn!(
+                    "Rejected invalid report supposedly from {}, with headers {:?}",
+                    service.ap_url, headers.0
+                );
+                return Err(status::BadRequest(Some("Invalid signature")));
+            }
+            return receive(&conn, act, actor_id);
+        }
+        Err(_) => return Err(status::BadRequest(Some("Unknown actor"))),
+    };
     if !verify_http_headers(&actor, &headers.0, &sig).is_secure() && !act.clone().verify(&actor) {
         // maybe we just know an old key?
         actor
@@ -49,13 +67,21 @@ pub fn handle_incoming(
             })?;
     }
 
-    if Instance::is_blocked(&conn, actor_id)
+    receive(&conn, act, actor_id)
+}
+
+fn receive(
+    conn: &DbConn,
+    act: serde_json::Value,
+    actor_id: &str,
+) -> Result<String, status::BadRequest<&'static str>> {
+    if Instance::is_blocked(conn, actor_id)
         .map_err(|_| status::BadRequest(Some("Can't tell if instance is blocked")))?
     {
         return Ok(String::new());
     }
 
-    Ok(match inbox(&conn, act) {
+    Ok(match inbox(conn, act) {
         Ok(_) => String::new(),
         Err(e) => {
             warn!("Shared inbox error: {:?}", e);
@@ -12,8 +12,11 @@ use clap::App;
 use diesel::r2d2::ConnectionManager;
 use plume_models::{
     db_conn::{DbPool, PragmaForeignKey},
+    deliveries::Delivery,
     instance::Instance,
     migrations::IMPORTED_MIGRATIONS,
+    post_previews::PostPreview,
+    posts::Post,
     remote_fetch_actor::RemoteFetchActor,
     search::{actor::SearchActor, Searcher as UnmanagedSearcher},
     Connection, CONFIG,
@@ -31,6 +34,8 @@ init_i18n!(
 );
 
 mod api;
+#[cfg(feature = "gemini")]
+mod gemini;
 mod inbox;
 mod mail;
 mod utils;
@@ -116,6 +121,51 @@ Then try to restart Plume.
         move || commiter.commit(),
     );
 
+    let publisher_pool = dbpool.clone();
+    workpool.execute_with_fixed_delay(
+        Duration::from_secs(10),
+        Duration::from_secs(60),
+        move || match publisher_pool.get() {
+            Ok(conn) => {
+                Post::publish_scheduled(&conn)
+                    .map(|_| ())
+                    .unwrap_or_else(|e| warn!("Failed to publish scheduled posts: {:?}", e));
+                PostPreview::delete_expired(&conn)
+                    .map(|_| ())
+                    .unwrap_or_else(|e| warn!("Failed to delete expired preview links: {:?}", e));
+            }
+            Err(_) => warn!("Failed to get database connection to publish scheduled posts"),
+        },
+    );
+
+    let delivery_pool = dbpool.clone();
+    workpool.execute_with_fixed_delay(Duration::from_secs(5), Duration::from_secs(5), move || {
+        match delivery_pool.get() {
+            Ok(conn) => Delivery::process_due(&conn)
+                .map(|_| ())
+                .unwrap_or_else(|e| warn!("Failed to send queued activities: {:?}", e)),
+            Err(_) => warn!("Failed to get database connection to send queued activities"),
+        }
+    });
+
+    let probe_pool = dbpool.clone();
+    workpool.execute_with_fixed_delay(
+        Duration::from_secs(60),
+        Duration::from_secs(60 * 60),
+        move || match probe_pool.get() {
+            Ok(conn) => Instance::probe_unreachable(&conn)
+                .unwrap_or_else(|e| warn!("Failed to probe unreachable instances: {:?}", e)),
+            Err(_) => warn!("Failed to get database connection to probe unreachable instances"),
+        },
+    );
+
+    #[cfg(feature = "gemini")]
+    {
+        if let Some(ref config) = CONFIG.gemini {
+            gemini::start(config, dbpool.clone());
+        }
+    }
+
     let search_unlocker = searcher.clone();
     ctrlc::set_handler(move || {
         search_unlocker.commit();
@@ -134,16 +184,27 @@ Then try to restart Plume.
         .mount(
             "/",
             routes![
+                routes::blocks::list,
+                routes::blocks::list_auth,
+                routes::blocks::block,
+                routes::blocks::block_domain,
+                routes::blocks::delete,
                 routes::blogs::details,
                 routes::blogs::activity_details,
                 routes::blogs::outbox,
                 routes::blogs::outbox_page,
+                routes::blogs::featured,
                 routes::blogs::new,
                 routes::blogs::new_auth,
                 routes::blogs::create,
                 routes::blogs::delete,
                 routes::blogs::edit,
                 routes::blogs::update,
+                routes::blogs::import,
+                routes::blogs::export,
+                routes::blogs::epub,
+                routes::blogs::epub_auth,
+                routes::blogs::print,
                 routes::blogs::atom_feed,
                 routes::comments::create,
                 routes::comments::delete,
@@ -161,6 +222,17 @@ Then try to restart Plume.
                 routes::instance::admin_email_blocklist,
                 routes::instance::add_email_blocklist,
                 routes::instance::delete_email_blocklist,
+                routes::instance::admin_emojis,
+                routes::instance::add_emoji,
+                routes::instance::delete_emoji,
+                routes::instance::admin_deliveries,
+                routes::instance::retry_delivery,
+                routes::instance::delete_delivery,
+                routes::instance::retry_failed_deliveries,
+                routes::instance::purge_failed_deliveries,
+                routes::instance::admin_reports,
+                routes::instance::resolve_report,
+                routes::instance::emoji,
                 routes::instance::edit_users,
                 routes::instance::toggle_block,
                 routes::instance::update_settings,
@@ -180,6 +252,12 @@ Then try to restart Plume.
                 routes::medias::set_avatar,
                 routes::notifications::notifications,
                 routes::notifications::notifications_auth,
+                routes::post_templates::list,
+                routes::post_templates::new,
+                routes::post_templates::create,
+                routes::post_templates::edit,
+                routes::post_templates::update,
+                routes::post_templates::delete,
                 routes::posts::details,
                 routes::posts::activity_details,
                 routes::posts::edit,
@@ -188,11 +266,31 @@ Then try to restart Plume.
                 routes::posts::new_auth,
                 routes::posts::create,
                 routes::posts::delete,
+                routes::posts::history,
+                routes::posts::restore_revision,
+                routes::posts::preview,
+                routes::posts::previews,
+                routes::posts::create_preview,
+                routes::posts::revoke_preview,
+                routes::posts::pin,
+                routes::posts::unpin,
+                routes::posts::epub,
+                routes::posts::print,
                 routes::posts::remote_interact,
                 routes::posts::remote_interact_post,
+                routes::reports::new,
+                routes::reports::new_auth,
+                routes::reports::create,
                 routes::reshares::create,
                 routes::reshares::create_auth,
                 routes::search::search,
+                routes::series::details,
+                routes::series::activity_details,
+                routes::series::atom_feed,
+                routes::series::epub,
+                routes::series::print,
+                routes::series::update,
+                routes::series::move_post,
                 routes::session::new,
                 routes::session::create,
                 routes::session::delete,
@@ -203,6 +301,7 @@ Then try to restart Plume.
                 routes::theme_files,
                 routes::plume_static_files,
                 routes::static_files,
+                routes::print_stylesheet,
                 routes::plume_media_files,
                 routes::tags::tag,
                 routes::timelines::details,
@@ -220,6 +319,8 @@ Then try to restart Plume.
                 routes::user::edit,
                 routes::user::edit_auth,
                 routes::user::update,
+                routes::user::update_aliases,
+                routes::user::move_account,
                 routes::user::delete,
                 routes::user::follow,
                 routes::user::follow_not_connected,
@@ -227,6 +328,7 @@ Then try to restart Plume.
                 routes::user::activity_details,
                 routes::user::outbox,
                 routes::user::outbox_page,
+                routes::user::featured,
                 routes::user::inbox,
                 routes::user::ap_followers,
                 routes::user::new,
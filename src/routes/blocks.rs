@@ -0,0 +1,99 @@
+use rocket::request::LenientForm;
+use rocket::response::{Flash, Redirect};
+use rocket_i18n::I18n;
+
+use crate::routes::errors::ErrorPage;
+use crate::template_utils::{IntoContext, Ructe};
+use crate::utils::requires_login;
+use plume_models::{db_conn::DbConn, user_blocks::UserBlock, users::User, Error, PlumeRocket};
+
+#[get("/blocks")]
+pub fn list(user: User, conn: DbConn, rockets: PlumeRocket) -> Result<Ructe, ErrorPage> {
+    let blocks = UserBlock::list_for_user(&conn, &user)?
+        .into_iter()
+        .map(|block| {
+            let blocked = block.blocked_id.and_then(|id| User::get(&conn, id).ok());
+            (block, blocked)
+        })
+        .collect();
+    Ok(render!(blocks::list(
+        &(&conn, &rockets).to_context(),
+        blocks
+    )))
+}
+
+#[get("/blocks", rank = 2)]
+pub fn list_auth(i18n: I18n) -> Flash<Redirect> {
+    requires_login(
+        &i18n!(
+            i18n.catalog,
+            "To see the accounts you blocked, you need to be logged in"
+        ),
+        uri!(list),
+    )
+}
+
+#[post("/@/<name>/block")]
+pub fn block(
+    name: String,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let target = User::find_by_fqn(&conn, &name)?;
+    let message = if let Ok(block) = UserBlock::find(&conn, user.id, target.id) {
+        block.unblock(&conn)?;
+        i18n!(rockets.intl.catalog, "You unblocked {}."; target.name())
+    } else {
+        UserBlock::block(&conn, &user, &target)?;
+        i18n!(rockets.intl.catalog, "You blocked {}."; target.name())
+    };
+    Ok(Flash::success(
+        Redirect::to(uri!(super::user::details: name = name)),
+        message,
+    ))
+}
+
+#[derive(FromForm)]
+pub struct DomainBlockForm {
+    pub domain: String,
+}
+
+#[post("/blocks", data = "<form>")]
+pub fn block_domain(
+    form: LenientForm<DomainBlockForm>,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    match UserBlock::block_domain(&conn, &user, &form.domain) {
+        Ok(block) => Ok(Flash::success(
+            Redirect::to(uri!(list)),
+            i18n!(rockets.intl.catalog, "You blocked {}."; block.domain.unwrap_or_default()),
+        )),
+        Err(Error::InvalidValue) | Err(Error::Db(_)) => Ok(Flash::error(
+            Redirect::to(uri!(list)),
+            i18n!(rockets.intl.catalog, "This domain can't be blocked."),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[post("/blocks/<id>/delete")]
+pub fn delete(
+    id: i32,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let block = UserBlock::get(&conn, id)?;
+    if block.user_id != user.id {
+        return Err(Error::Unauthorized.into());
+    }
+
+    block.unblock(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(list)),
+        i18n!(rockets.intl.catalog, "The block has been removed."),
+    ))
+}
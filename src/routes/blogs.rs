@@ -1,12 +1,24 @@
 use activitystreams::collection::{OrderedCollection, OrderedCollectionPage};
 use diesel::SaveChangesDsl;
+use guid_create::GUID;
+use multipart::server::{
+    save::{SaveResult, SavedData, SavedField},
+    Multipart,
+};
 use rocket::{
     http::ContentType,
     request::LenientForm,
-    response::{content::Content, Flash, Redirect},
+    response::{content::Content, Flash, Redirect, Response},
+    Data,
 };
 use rocket_i18n::I18n;
-use std::{borrow::Cow, collections::HashMap};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    env,
+    fs::{self, OpenOptions},
+    io::{Seek, SeekFrom},
+};
 use validator::{Validate, ValidationError, ValidationErrors};
 
 use crate::routes::{errors::ErrorPage, Page, RespondOrRedirect};
@@ -15,8 +27,21 @@ use crate::utils::requires_login;
 use plume_common::activity_pub::{ActivityStream, ApRequest, CustomGroup};
 use plume_common::utils;
 use plume_models::{
-    blog_authors::*, blogs::*, db_conn::DbConn, instance::Instance, medias::*, posts::Post,
-    safe_string::SafeString, users::User, Connection, PlumeRocket,
+    blog_authors::*,
+    blog_slugs::BlogSlug,
+    blogs::*,
+    db_conn::DbConn,
+    emojis::Emoji,
+    epub::Book,
+    export::export_blog,
+    import::{import_posts, ImportFormat, ImportOptions},
+    instance::Instance,
+    medias::*,
+    posts::Post,
+    safe_string::SafeString,
+    series::Series,
+    users::User,
+    Connection, Error, PlumeRocket,
 };
 
 #[get("/~/<name>?<page>", rank = 2)]
@@ -25,12 +50,21 @@ pub fn details(
     page: Option<Page>,
     conn: DbConn,
     rockets: PlumeRocket,
-) -> Result<Ructe, ErrorPage> {
+) -> Result<RespondOrRedirect, ErrorPage> {
     let page = page.unwrap_or_default();
-    let blog = Blog::find_by_fqn(&conn, &name)?;
+    let blog = match Blog::find_by_fqn(&conn, &name) {
+        Ok(blog) => blog,
+        Err(e) => {
+            return match BlogSlug::find_blog(&conn, &name) {
+                Ok(blog) => Ok(Redirect::moved(uri!(details: name = &blog.fqn, page = _)).into()),
+                Err(_) => Err(e.into()),
+            }
+        }
+    };
     let posts = Post::blog_page(&conn, &blog, page.limits())?;
     let articles_count = Post::count_for_blog(&conn, &blog)?;
     let authors = &blog.list_authors(&conn)?;
+    let series = Series::list_for_blog(&conn, blog.id)?;
 
     Ok(render!(blogs::details(
         &(&conn, &rockets).to_context(),
@@ -38,8 +72,10 @@ pub fn details(
         authors,
         page.0,
         Page::total(articles_count as i32),
-        posts
-    )))
+        posts,
+        series
+    ))
+    .into())
 }
 
 #[get("/~/<name>", rank = 1)]
@@ -48,7 +84,7 @@ pub fn activity_details(
     conn: DbConn,
     _ap: ApRequest,
 ) -> Option<ActivityStream<CustomGroup>> {
-    let blog = Blog::find_by_fqn(&conn, &name).ok()?;
+    let blog = find_blog(&conn, &name).ok()?;
     Some(ActivityStream::new(blog.to_activity(&conn).ok()?))
 }
 
@@ -101,7 +137,7 @@ pub fn create(
         Ok(_) => ValidationErrors::new(),
         Err(e) => e,
     };
-    if Blog::find_by_fqn(&conn, slug).is_ok() {
+    if Blog::find_by_fqn(&conn, slug).is_ok() || BlogSlug::find_blog(&conn, slug).is_ok() {
         errors.add(
             "title",
             ValidationError {
@@ -183,6 +219,8 @@ pub fn delete(name: String, conn: DbConn, rockets: PlumeRocket) -> RespondOrRedi
 pub struct EditForm {
     #[validate(custom(function = "valid_slug", message = "Invalid name"))]
     pub title: String,
+    #[validate(custom(function = "valid_slug", message = "Invalid name"))]
+    pub slug: String,
     pub summary: String,
     pub icon: Option<i32>,
     pub banner: Option<i32>,
@@ -209,6 +247,7 @@ pub fn edit(name: String, conn: DbConn, rockets: PlumeRocket) -> Result<Ructe, E
             medias,
             &EditForm {
                 title: blog.title.clone(),
+                slug: blog.actor_id.clone(),
                 summary: blog.summary.clone(),
                 icon: blog.icon_id,
                 banner: blog.banner_id,
@@ -305,6 +344,28 @@ pub fn update(
                 }
             }
 
+            let slug = Blog::slug(form.slug.trim());
+            if slug != blog.actor_id
+                && (Blog::find_by_fqn(&conn, slug).is_ok()
+                    || BlogSlug::find_blog(&conn, slug)
+                        .map(|b| b.id != blog.id)
+                        .unwrap_or(false))
+            {
+                let mut errors = ValidationErrors::new();
+                errors.add(
+                    "slug",
+                    ValidationError {
+                        code: Cow::from("existing_slug"),
+                        message: Some(Cow::from(i18n!(
+                            intl,
+                            "A blog with the same name already exists."
+                        ))),
+                        params: HashMap::new(),
+                    },
+                );
+                return Err(errors);
+            }
+
             blog.title = form.title.clone();
             blog.summary = form.summary.clone();
             blog.summary_html = SafeString::new(
@@ -319,6 +380,7 @@ pub fn update(
                             .iter()
                             .collect(),
                     )),
+                    Some(Emoji::get_emoji_processor(&conn)),
                 )
                 .0,
             );
@@ -327,8 +389,9 @@ pub fn update(
             blog.theme = form.theme.clone();
             blog.save_changes::<Blog>(&*conn)
                 .expect("Couldn't save blog changes");
+            blog.rename(&conn, slug).expect("Couldn't rename blog");
             Ok(Flash::success(
-                Redirect::to(uri!(details: name = name, page = _)),
+                Redirect::to(uri!(details: name = &blog.fqn, page = _)),
                 i18n!(intl, "Your blog information have been updated."),
             ))
         })
@@ -346,9 +409,181 @@ pub fn update(
         .into()
 }
 
+#[post("/~/<name>/import", data = "<data>")]
+pub fn import(
+    name: String,
+    data: Data,
+    ct: &ContentType,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let intl = &rockets.intl.catalog;
+    let blog = Blog::find_by_fqn(&conn, &name)?;
+    if !user.is_author_in(&conn, &blog)? {
+        return Ok(Flash::error(
+            Redirect::to(uri!(details: name = &name, page = _)),
+            i18n!(intl, "You are not allowed to edit this blog."),
+        ));
+    }
+    let unreadable = || {
+        Flash::error(
+            Redirect::to(uri!(edit: name = &name)),
+            i18n!(intl, "The export couldn't be read."),
+        )
+    };
+
+    let boundary = match ct.params().find(|&(k, _)| k == "boundary") {
+        Some((_, boundary)) if ct.is_form_data() => boundary,
+        _ => return Ok(unreadable()),
+    };
+    let fields = match Multipart::with_body(data.open(), boundary).save().temp() {
+        SaveResult::Full(entries) => entries.fields,
+        _ => return Ok(unreadable()),
+    };
+    let text = |name: &str| match fields.get(name).and_then(|v| v.iter().next()) {
+        Some(SavedField {
+            data: SavedData::Text(ref text),
+            ..
+        }) => Some(text.trim().to_owned()).filter(|t| !t.is_empty()),
+        _ => None,
+    };
+    let format = match text("format").and_then(|f| f.parse::<ImportFormat>().ok()) {
+        Some(format) => format,
+        None => return Ok(unreadable()),
+    };
+
+    let mut posts = vec![];
+    for file in fields.get("file").into_iter().flatten() {
+        let export = match file.data {
+            SavedData::Text(ref text) => text.clone().into_bytes(),
+            SavedData::Bytes(ref bytes) => bytes.clone(),
+            SavedData::File(ref path, _) => match fs::read(path) {
+                Ok(export) => export,
+                Err(_) => return Ok(unreadable()),
+            },
+        };
+        match format.parse(&export) {
+            Ok(parsed) => posts.extend(parsed),
+            Err(_) => return Ok(unreadable()),
+        }
+    }
+
+    let options = ImportOptions {
+        default_license: match text("license") {
+            Some(license) => license,
+            None => Instance::get_local()?.default_license,
+        },
+        site_url: text("site_url"),
+        index: text("index").is_some(),
+        federate: text("federate").is_some(),
+    };
+    let count = posts.len();
+    // downloading the images of the posts can take a while
+    rockets.worker.execute(
+        move || match import_posts(&conn, &blog, &user, posts, &options) {
+            Ok(report) => {
+                for (title, reason) in report.skipped {
+                    tracing::warn!(
+                        "Skipped {} while importing to {}: {}",
+                        title,
+                        blog.fqn,
+                        reason
+                    );
+                }
+            }
+            Err(e) => tracing::error!("Failed to import posts to {}: {:?}", blog.fqn, e),
+        },
+    );
+    Ok(Flash::success(
+        Redirect::to(uri!(details: name = &name, page = _)),
+        i18n!(
+            intl,
+            "One article is being imported, it will appear on this blog soon.",
+            "{0} articles are being imported, they will appear on this blog soon.";
+            count
+        ),
+    ))
+}
+
+#[get("/~/<name>/export")]
+pub fn export(name: String, user: User, conn: DbConn) -> Result<Response<'static>, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &name)?;
+    if !user.is_author_in(&conn, &blog)? {
+        return Err(Error::Unauthorized.into());
+    }
+
+    // the archive can be big, so it is written to a file, deleted once it has been sent
+    let path = env::temp_dir().join(format!("plume-export-{}.tar.gz", GUID::rand()));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(Error::from)?;
+    fs::remove_file(&path).map_err(Error::from)?;
+    let mut archive = export_blog(&conn, &blog, file)?;
+    archive.seek(SeekFrom::Start(0)).map_err(Error::from)?;
+    Ok(Response::build()
+        .header(ContentType::new("application", "gzip"))
+        .raw_header(
+            "Content-Disposition",
+            super::attachment_disposition(&format!("{}.tar.gz", blog.fqn)),
+        )
+        .sized_body(archive)
+        .finalize())
+}
+
+/// The published posts of `blog` that `user` can read.
+fn readable_posts(conn: &Connection, blog: &Blog, user: Option<&User>) -> Result<Vec<Post>, Error> {
+    Ok(Post::get_for_blog(conn, blog)?
+        .into_iter()
+        .filter(|post| post.can_see(conn, user).unwrap_or(false))
+        .collect())
+}
+
+/// A whole blog is expensive to turn into a book, so only users can download it.
+#[get("/~/<name>/epub")]
+pub fn epub(name: String, user: User, conn: DbConn) -> Result<Response<'static>, ErrorPage> {
+    let blog = find_blog(&conn, &name)?;
+    let posts = readable_posts(&conn, &blog, Some(&user))?;
+    super::epub_download(&conn, &Book::for_blog(&conn, &blog, posts), &blog.fqn)
+}
+
+#[get("/~/<name>/epub", rank = 2)]
+pub fn epub_auth(name: String, i18n: I18n) -> Flash<Redirect> {
+    requires_login(
+        &i18n!(
+            i18n.catalog,
+            "To download a blog as a book, you need to be logged in"
+        ),
+        uri!(epub: name = name),
+    )
+}
+
+#[get("/~/<name>/print")]
+pub fn print(name: String, conn: DbConn, rockets: PlumeRocket) -> Result<Ructe, ErrorPage> {
+    let blog = find_blog(&conn, &name)?;
+    let posts = readable_posts(&conn, &blog, rockets.user.as_ref())?;
+    let book = Book::for_blog(&conn, &blog, posts);
+    let posts = book
+        .posts
+        .into_iter()
+        .map(|post| {
+            let authors = post.get_authors(&conn)?;
+            Ok((post, authors))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(render!(posts::print(
+        &(&conn, &rockets).to_context(),
+        book.title,
+        posts
+    )))
+}
+
 #[get("/~/<name>/outbox")]
 pub fn outbox(name: String, conn: DbConn) -> Option<ActivityStream<OrderedCollection>> {
-    let blog = Blog::find_by_fqn(&conn, &name).ok()?;
+    let blog = find_blog(&conn, &name).ok()?;
     blog.outbox(&conn).ok()
 }
 #[allow(unused_variables)]
@@ -358,16 +593,29 @@ pub fn outbox_page(
     page: Page,
     conn: DbConn,
 ) -> Option<ActivityStream<OrderedCollectionPage>> {
-    let blog = Blog::find_by_fqn(&conn, &name).ok()?;
+    let blog = find_blog(&conn, &name).ok()?;
     blog.outbox_page(&conn, page.limits()).ok()
 }
+
+#[get("/~/<name>/featured")]
+pub fn featured(name: String, conn: DbConn) -> Option<ActivityStream<OrderedCollection>> {
+    let blog = find_blog(&conn, &name).ok()?;
+    blog.featured(&conn).ok()
+}
+
+/// Finds a blog by its name, or by one of its previous names, that are still used
+/// in its ActivityPub URLs.
+pub fn find_blog(conn: &Connection, name: &str) -> Result<Blog, Error> {
+    Blog::find_by_fqn(conn, name).or_else(|e| BlogSlug::find_blog(conn, name).map_err(|_| e))
+}
+
 #[get("/~/<name>/atom.xml")]
 pub fn atom_feed(name: String, conn: DbConn) -> Option<Content<String>> {
-    let blog = Blog::find_by_fqn(&conn, &name).ok()?;
+    let blog = find_blog(&conn, &name).ok()?;
     let entries = Post::get_recents_for_blog(&conn, &blog, 15).ok()?;
     let uri = Instance::get_local()
         .ok()?
-        .compute_box("~", &name, "atom.xml");
+        .compute_box("~", &blog.fqn, "atom.xml");
     let title = &blog.title;
     let default_updated = &blog.creation_date;
     let feed = super::build_atom_feed(entries, &uri, title, default_updated, &conn);
@@ -389,7 +637,7 @@ mod tests {
         db_conn::{DbConn, DbPool},
         instance::{Instance, NewInstance},
         post_authors::{NewPostAuthor, PostAuthor},
-        posts::{NewPost, Post},
+        posts::{NewPost, Post, Visibility},
         safe_string::SafeString,
         users::{NewUser, User, AUTH_COOKIE},
         Connection as Conn, CONFIG,
@@ -505,6 +753,13 @@ mod tests {
                     subtitle: "".to_owned(),
                     source: "".to_owned(),
                     cover_id: None,
+                    publish_at: None,
+                    visibility: Visibility::Public.into(),
+                    sensitive: false,
+                    content_warning: None,
+                    canonical_url: None,
+                    language: None,
+                    translation_of: None,
                 },
             )
             .unwrap();
@@ -11,13 +11,13 @@ use std::time::Duration;
 use crate::routes::errors::ErrorPage;
 use crate::template_utils::IntoContext;
 use plume_common::{
-    activity_pub::{broadcast, ActivityStream, ApRequest},
+    activity_pub::{ActivityStream, ApRequest},
     utils,
 };
 use plume_models::{
-    blogs::Blog, comments::*, db_conn::DbConn, inbox::inbox, instance::Instance, medias::Media,
-    mentions::Mention, posts::Post, safe_string::SafeString, tags::Tag, users::User, Error,
-    PlumeRocket, CONFIG,
+    blogs::Blog, comments::*, db_conn::DbConn, deliveries::Delivery, emojis::Emoji, inbox::inbox,
+    instance::Instance, medias::Media, mentions::Mention, posts::Post, safe_string::SafeString,
+    tags::Tag, users::User, Error, PlumeRocket,
 };
 
 #[derive(Default, FromForm, Debug, Validate)]
@@ -50,6 +50,7 @@ pub fn create(
                 ),
                 true,
                 Some(Media::get_media_processor(&conn, vec![&user])),
+                Some(Emoji::get_emoji_processor(&conn)),
             );
             let comm = Comment::insert(
                 &conn,
@@ -86,10 +87,8 @@ pub fn create(
 
             // federate
             let dest = User::one_by_instance(&conn).expect("comments::create: dest error");
-            let user_clone = user.clone();
-            rockets.worker.execute(move || {
-                broadcast(&user_clone, new_comment, dest, CONFIG.proxy().cloned())
-            });
+            Delivery::broadcast(&conn, &user, new_comment, dest)
+                .expect("comments::create: federation error");
 
             Flash::success(
                 Redirect::to(uri!(
@@ -156,10 +155,7 @@ pub fn delete(
                 serde_json::to_value(&delete_activity).map_err(Error::from)?,
             )?;
 
-            let user_c = user.clone();
-            rockets.worker.execute(move || {
-                broadcast(&user_c, delete_activity, dest, CONFIG.proxy().cloned())
-            });
+            Delivery::broadcast(&conn, &user, delete_activity, dest)?;
             rockets
                 .worker
                 .execute_after(Duration::from_secs(10 * 60), move || {
@@ -11,15 +11,19 @@ use validator::{Validate, ValidationErrors};
 use crate::inbox;
 use crate::routes::{errors::ErrorPage, rocket_uri_macro_static_files, Page, RespondOrRedirect};
 use crate::template_utils::{IntoContext, Ructe};
-use plume_common::activity_pub::{broadcast, inbox::FromId};
+use plume_common::activity_pub::{broadcast, inbox::FromId, ActivityStream, Emoji as EmojiObject};
 use plume_models::{
     admin::*,
     blocklisted_emails::*,
     comments::Comment,
     db_conn::DbConn,
+    deliveries::Delivery,
+    emojis::Emoji,
     headers::Headers,
     instance::*,
+    medias::{Media, MediaCategory},
     posts::Post,
+    reports::Report,
     safe_string::SafeString,
     timeline::Timeline,
     users::{Role, User},
@@ -35,8 +39,8 @@ pub fn index(conn: DbConn, rockets: PlumeRocket) -> Result<Ructe, ErrorPage> {
         let inst = Instance::get_local()?;
         let page = Page::default();
         let tl = &all_tl[0];
-        let posts = tl.get_page(&conn, page.limits())?;
-        let total_posts = tl.count_posts(&conn)?;
+        let posts = tl.get_page_for(&conn, rockets.user.as_ref(), page.limits())?;
+        let total_posts = tl.count_posts_for(&conn, rockets.user.as_ref())?;
         Ok(render!(instance::index(
             &(&conn, &rockets).to_context(),
             inst,
@@ -266,6 +270,215 @@ pub fn admin_email_blocklist(
     )))
 }
 
+#[get("/admin/emojis")]
+pub fn admin_emojis(admin: Admin, conn: DbConn, rockets: PlumeRocket) -> Result<Ructe, ErrorPage> {
+    let emojis = Emoji::list_local(&conn)?
+        .into_iter()
+        .map(|emoji| {
+            let url = emoji.url(&conn)?;
+            Ok((emoji, url))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(render!(instance::emojis(
+        &(&conn, &rockets).to_context(),
+        emojis,
+        Media::for_user(&conn, admin.0.id)?
+    )))
+}
+
+#[derive(FromForm)]
+pub struct NewEmojiForm {
+    pub shortcode: String,
+    pub media: i32,
+}
+
+#[post("/admin/emojis/new", data = "<form>")]
+pub fn add_emoji(
+    admin: Admin,
+    form: LenientForm<NewEmojiForm>,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let shortcode = form.shortcode.trim_matches(':');
+    let media = Media::get(&conn, form.media)?;
+    if media.owner_id != admin.0.id || media.category() != MediaCategory::Image {
+        return Err(Error::Unauthorized.into());
+    }
+    if !Emoji::is_valid_shortcode(shortcode) {
+        return Ok(Flash::error(
+            Redirect::to(uri!(admin_emojis)),
+            i18n!(
+                rockets.intl.catalog,
+                "Shortcodes can only contain letters, digits and underscores"
+            ),
+        ));
+    }
+
+    match Emoji::new_local(&conn, shortcode, &media) {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to(uri!(admin_emojis)),
+            i18n!(rockets.intl.catalog, "The emoji has been added"),
+        )),
+        Err(Error::Db(_)) => Ok(Flash::error(
+            Redirect::to(uri!(admin_emojis)),
+            i18n!(
+                rockets.intl.catalog,
+                "There is already an emoji with this shortcode"
+            ),
+        )),
+        Err(e) => {
+            tracing::warn!("Couldn't add the emoji {}: {:?}", shortcode, e);
+            Ok(Flash::error(
+                Redirect::to(uri!(admin_emojis)),
+                i18n!(rockets.intl.catalog, "The emoji couldn't be added"),
+            ))
+        }
+    }
+}
+
+#[post("/admin/emojis/<id>/delete")]
+pub fn delete_emoji(
+    _admin: Admin,
+    id: i32,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let emoji = Emoji::get(&conn, id)?;
+    if emoji.instance_id != Instance::get_local()?.id {
+        return Err(Error::Unauthorized.into());
+    }
+    emoji.delete(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(admin_emojis)),
+        i18n!(rockets.intl.catalog, "The emoji has been deleted"),
+    ))
+}
+
+#[get("/admin/deliveries?<page>")]
+pub fn admin_deliveries(
+    _admin: Admin,
+    page: Option<Page>,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let page = page.unwrap_or_default();
+    let deliveries = Delivery::page(&conn, page.limits())?;
+    Ok(render!(instance::deliveries(
+        &(&conn, &rockets).to_context(),
+        deliveries,
+        Delivery::count_failed(&conn)?,
+        page.0,
+        Page::total(Delivery::count(&conn)? as i32)
+    )))
+}
+
+#[post("/admin/deliveries/<id>/retry")]
+pub fn retry_delivery(
+    _admin: Admin,
+    id: i32,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    Delivery::get(&conn, id)?.retry(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(admin_deliveries: page = _)),
+        i18n!(
+            rockets.intl.catalog,
+            "This activity will be sent again soon"
+        ),
+    ))
+}
+
+#[post("/admin/deliveries/<id>/delete")]
+pub fn delete_delivery(
+    _admin: Admin,
+    id: i32,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    Delivery::get(&conn, id)?.delete(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(admin_deliveries: page = _)),
+        i18n!(rockets.intl.catalog, "This activity won't be sent"),
+    ))
+}
+
+#[post("/admin/deliveries/retry")]
+pub fn retry_failed_deliveries(
+    _admin: Admin,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let count = Delivery::retry_failed(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(admin_deliveries: page = _)),
+        i18n!(
+            rockets.intl.catalog,
+            "One failed activity will be sent again soon",
+            "{0} failed activities will be sent again soon";
+            count
+        ),
+    ))
+}
+
+#[post("/admin/deliveries/purge")]
+pub fn purge_failed_deliveries(
+    _admin: Admin,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let count = Delivery::purge_failed(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(admin_deliveries: page = _)),
+        i18n!(
+            rockets.intl.catalog,
+            "One failed activity has been deleted",
+            "{0} failed activities have been deleted";
+            count
+        ),
+    ))
+}
+
+#[get("/admin/reports?<page>")]
+pub fn admin_reports(
+    _mod: Moderator,
+    page: Option<Page>,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let page = page.unwrap_or_default();
+    let reports = Report::page_unresolved(&conn, page.limits())?;
+    Ok(render!(instance::reports(
+        &(&conn, &rockets).to_context(),
+        reports,
+        page.0,
+        Page::total(Report::count_unresolved(&conn)? as i32)
+    )))
+}
+
+#[post("/admin/reports/<id>/resolve")]
+pub fn resolve_report(
+    _mod: Moderator,
+    id: i32,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    Report::get(&conn, id)?.resolve(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(admin_reports: page = _)),
+        i18n!(
+            rockets.intl.catalog,
+            "The report has been marked as resolved"
+        ),
+    ))
+}
+
+#[get("/emojis/<shortcode>")]
+pub fn emoji(shortcode: String, conn: DbConn) -> Option<ActivityStream<EmojiObject>> {
+    let emoji = Emoji::find_by_shortcode(&conn, Instance::get_local().ok()?.id, &shortcode).ok()?;
+    Some(ActivityStream::new(emoji.to_activity(&conn).ok()?))
+}
+
 /// A structure to handle forms that are a list of items on which actions are applied.
 ///
 /// This is for instance the case of the user list in the administration.
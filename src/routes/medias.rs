@@ -93,23 +93,7 @@ pub fn upload(
 }
 
 fn save_uploaded_file(file: &SavedField) -> Result<Option<String>, plume_models::Error> {
-    // Remove extension if it contains something else than just letters and numbers
-    let ext = file
-        .headers
-        .filename
-        .as_ref()
-        .and_then(|f| {
-            f.rsplit('.')
-                .next()
-                .and_then(|ext| {
-                    if ext.chars().any(|c| !c.is_alphanumeric()) {
-                        None
-                    } else {
-                        Some(ext.to_lowercase())
-                    }
-                })
-        })
-        .unwrap_or_default();
+    let ext = file_extension(file.headers.filename.as_deref().unwrap_or_default());
 
     if CONFIG.s3.is_some() {
         #[cfg(not(feature="s3"))]
@@ -4,26 +4,28 @@ use atom_syndication::{
     ContentBuilder, Entry, EntryBuilder, Feed, FeedBuilder, LinkBuilder, Person, PersonBuilder,
 };
 use chrono::{naive::NaiveDateTime, DateTime, Utc};
-use plume_models::{posts::Post, Connection, CONFIG, ITEMS_PER_PAGE};
+use plume_models::{
+    epub::{Book, STYLESHEET},
+    posts::Post,
+    Connection, CONFIG, ITEMS_PER_PAGE,
+};
 use rocket::{
     http::{
         hyper::header::{CacheControl, CacheDirective, ETag, EntityTag},
         uri::{FromUriParam, Query},
-        RawStr, Status,
+        ContentType, RawStr, Status,
     },
     request::{self, FromFormValue, FromRequest, Request},
-    response::{self, Flash, NamedFile, Redirect, Responder, Response},
+    response::{self, content::Content, Flash, NamedFile, Redirect, Responder, Response},
     Outcome,
 };
 use std::{
     collections::hash_map::DefaultHasher,
     hash::Hasher,
+    io::Cursor,
     path::{Path, PathBuf},
 };
 
-#[cfg(feature = "s3")]
-use rocket::http::ContentType;
-
 /// Special return type used for routes that "cannot fail", and instead
 /// `Redirect`, or `Flash<Redirect>`, when we cannot deliver a `Ructe` Response
 #[allow(clippy::large_enum_variant)]
@@ -159,6 +161,15 @@ pub fn build_atom_feed(
 }
 
 fn post_to_atom(post: Post, conn: &Connection) -> Entry {
+    let mut links = vec![LinkBuilder::default().href(post.ap_url.clone()).build()];
+    if let Some(canonical_url) = post.canonical_url.clone() {
+        links.push(
+            LinkBuilder::default()
+                .href(canonical_url)
+                .rel("canonical")
+                .build(),
+        );
+    }
     EntryBuilder::default()
         .title(format!("<![CDATA[{}]]>", post.title))
         .content(
@@ -186,10 +197,60 @@ fn post_to_atom(post: Post, conn: &Connection) -> Entry {
         ))
         .updated(DateTime::<Utc>::from_utc(post.creation_date, Utc))
         .id(post.ap_url.clone())
-        .links(vec![LinkBuilder::default().href(post.ap_url).build()])
+        .links(links)
         .build()
 }
 
+/// Sends `book` as an EPUB file to download, named after `name`.
+pub fn epub_download(
+    conn: &Connection,
+    book: &Book,
+    name: &str,
+) -> Result<Response<'static>, errors::ErrorPage> {
+    let data = book.write(conn, Cursor::new(vec![]))?.into_inner();
+    Ok(Response::build()
+        .header(ContentType::new("application", "epub+zip"))
+        .raw_header(
+            "Content-Disposition",
+            attachment_disposition(&format!("{}.epub", name)),
+        )
+        .sized_body(Cursor::new(data))
+        .finalize())
+}
+
+/// The `Content-Disposition` header to download a file named `file_name`.
+///
+/// The plain `filename` parameter only keeps the characters that are safe everywhere,
+/// and the full name is given with `filename*` (RFC 5987) for the clients that support it.
+pub fn attachment_disposition(file_name: &str) -> String {
+    let is_safe = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-');
+    let ascii_name = file_name
+        .bytes()
+        .map(|b| if is_safe(b) { b as char } else { '_' })
+        .collect::<String>();
+    let encoded_name = file_name
+        .bytes()
+        .map(|b| {
+            if is_safe(b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect::<String>();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        ascii_name, encoded_name
+    )
+}
+
+/// The stylesheet of the printable versions of posts.
+#[get("/print.css")]
+pub fn print_stylesheet() -> Content<&'static str> {
+    Content(ContentType::CSS, STYLESHEET)
+}
+
+pub mod blocks;
 pub mod blogs;
 pub mod comments;
 pub mod email_signups;
@@ -198,9 +259,12 @@ pub mod instance;
 pub mod likes;
 pub mod medias;
 pub mod notifications;
+pub mod post_templates;
 pub mod posts;
+pub mod reports;
 pub mod reshares;
 pub mod search;
+pub mod series;
 pub mod session;
 pub mod tags;
 pub mod timelines;
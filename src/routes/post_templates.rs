@@ -0,0 +1,275 @@
+use rocket::request::LenientForm;
+use rocket::response::{Flash, Redirect};
+use std::{borrow::Cow, collections::HashMap};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::routes::{errors::ErrorPage, RespondOrRedirect};
+use crate::template_utils::{IntoContext, Ructe};
+use plume_models::{
+    blogs::Blog,
+    db_conn::DbConn,
+    medias::Media,
+    post_templates::{NewPostTemplate, PostTemplate},
+    users::User,
+    Error, PlumeRocket,
+};
+
+#[get("/~/<blog>/templates")]
+pub fn list(
+    blog: String,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    if !user.is_author_in(&conn, &blog)? {
+        return Ok(render!(errors::not_authorized(
+            &(&conn, &rockets).to_context(),
+            i18n!(rockets.intl.catalog, "You are not an author of this blog.")
+        )));
+    }
+
+    let templates = PostTemplate::list_for_blog(&conn, blog.id)?;
+    Ok(render!(post_templates::list(
+        &(&conn, &rockets).to_context(),
+        blog,
+        templates
+    )))
+}
+
+#[derive(Default, FromForm, Validate)]
+pub struct PostTemplateForm {
+    #[validate(length(min = 1, message = "The name can't be empty"))]
+    pub name: String,
+    pub title: String,
+    pub source: String,
+    pub tags: String,
+    pub license: String,
+    pub cover: Option<i32>,
+}
+
+impl PostTemplateForm {
+    fn cover(&self, conn: &DbConn, user: &User) -> Option<i32> {
+        self.cover
+            .filter(|id| Media::get(conn, *id).map_or(false, |m| m.owner_id == user.id))
+    }
+}
+
+/// Reports that the blog already has a template with this name.
+fn name_taken(errors: &mut ValidationErrors, rockets: &PlumeRocket) {
+    errors.add(
+        "name",
+        ValidationError {
+            code: Cow::from("existing_name"),
+            message: Some(Cow::from(i18n!(
+                rockets.intl.catalog,
+                "A template with the same name already exists."
+            ))),
+            params: HashMap::new(),
+        },
+    );
+}
+
+#[get("/~/<blog>/templates/new")]
+pub fn new(
+    blog: String,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    if !user.is_author_in(&conn, &blog)? {
+        return Ok(render!(errors::not_authorized(
+            &(&conn, &rockets).to_context(),
+            i18n!(rockets.intl.catalog, "You are not an author of this blog.")
+        )));
+    }
+
+    let medias = Media::for_user(&conn, user.id)?;
+    Ok(render!(post_templates::edit(
+        &(&conn, &rockets).to_context(),
+        blog,
+        None,
+        &PostTemplateForm::default(),
+        ValidationErrors::default(),
+        medias
+    )))
+}
+
+#[post("/~/<blog>/templates/new", data = "<form>")]
+pub fn create(
+    blog: String,
+    form: LenientForm<PostTemplateForm>,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<RespondOrRedirect, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    if !user.is_author_in(&conn, &blog)? {
+        return Err(Error::Unauthorized.into());
+    }
+
+    let mut errors = match form.validate() {
+        Ok(()) => ValidationErrors::new(),
+        Err(e) => e,
+    };
+    if errors.is_empty() {
+        let inserted = PostTemplate::insert(
+            &conn,
+            NewPostTemplate {
+                blog_id: blog.id,
+                name: form.name.trim().to_owned(),
+                title: form.title.clone(),
+                source: form.source.clone(),
+                tags: form.tags.clone(),
+                license: form.license.trim().to_owned(),
+                cover_id: form.cover(&conn, &user),
+            },
+        );
+        match inserted {
+            Ok(_) => {
+                return Ok(Flash::success(
+                    Redirect::to(uri!(list: blog = &blog.fqn)),
+                    i18n!(rockets.intl.catalog, "The template has been created."),
+                )
+                .into())
+            }
+            Err(Error::Db(_)) => name_taken(&mut errors, &rockets),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let medias = Media::for_user(&conn, user.id)?;
+    Ok(render!(post_templates::edit(
+        &(&conn, &rockets).to_context(),
+        blog,
+        None,
+        &*form,
+        errors,
+        medias
+    ))
+    .into())
+}
+
+#[get("/~/<blog>/templates/<id>/edit", rank = 2)]
+pub fn edit(
+    blog: String,
+    id: i32,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    let template = PostTemplate::get(&conn, id)?;
+    if template.blog_id != blog.id {
+        return Err(Error::NotFound.into());
+    }
+    if !user.is_author_in(&conn, &blog)? {
+        return Ok(render!(errors::not_authorized(
+            &(&conn, &rockets).to_context(),
+            i18n!(rockets.intl.catalog, "You are not an author of this blog.")
+        )));
+    }
+
+    let medias = Media::for_user(&conn, user.id)?;
+    let form = PostTemplateForm {
+        name: template.name.clone(),
+        title: template.title.clone(),
+        source: template.source.clone(),
+        tags: template.tags.clone(),
+        license: template.license.clone(),
+        cover: template.cover_id,
+    };
+    Ok(render!(post_templates::edit(
+        &(&conn, &rockets).to_context(),
+        blog,
+        Some(template),
+        &form,
+        ValidationErrors::default(),
+        medias
+    )))
+}
+
+#[post("/~/<blog>/templates/<id>/edit", data = "<form>")]
+pub fn update(
+    blog: String,
+    id: i32,
+    form: LenientForm<PostTemplateForm>,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<RespondOrRedirect, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    let mut template = PostTemplate::get(&conn, id)?;
+    if template.blog_id != blog.id || !user.is_author_in(&conn, &blog)? {
+        return Err(Error::Unauthorized.into());
+    }
+
+    if let Err(errors) = form.validate() {
+        let medias = Media::for_user(&conn, user.id)?;
+        return Ok(render!(post_templates::edit(
+            &(&conn, &rockets).to_context(),
+            blog,
+            Some(template),
+            &*form,
+            errors,
+            medias
+        ))
+        .into());
+    }
+
+    template.name = form.name.trim().to_owned();
+    template.title = form.title.clone();
+    template.source = form.source.clone();
+    template.tags = form.tags.clone();
+    template.license = form.license.trim().to_owned();
+    // Keep the cover if it was chosen by another author
+    if form.cover != template.cover_id {
+        template.cover_id = form.cover(&conn, &user);
+    }
+    match template.update(&conn) {
+        Ok(_) => {}
+        Err(Error::Db(_)) => {
+            let mut errors = ValidationErrors::new();
+            name_taken(&mut errors, &rockets);
+            let medias = Media::for_user(&conn, user.id)?;
+            let template = PostTemplate::get(&conn, id)?;
+            return Ok(render!(post_templates::edit(
+                &(&conn, &rockets).to_context(),
+                blog,
+                Some(template),
+                &*form,
+                errors,
+                medias
+            ))
+            .into());
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Flash::success(
+        Redirect::to(uri!(list: blog = &blog.fqn)),
+        i18n!(rockets.intl.catalog, "The template has been updated."),
+    )
+    .into())
+}
+
+#[post("/~/<blog>/templates/<id>/delete")]
+pub fn delete(
+    blog: String,
+    id: i32,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    let template = PostTemplate::get(&conn, id)?;
+    if template.blog_id != blog.id || !user.is_author_in(&conn, &blog)? {
+        return Err(Error::Unauthorized.into());
+    }
+
+    template.delete(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(list: blog = &blog.fqn)),
+        i18n!(rockets.intl.catalog, "The template has been deleted."),
+    ))
+}
@@ -1,7 +1,7 @@
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use rocket::http::uri::Uri;
 use rocket::request::LenientForm;
-use rocket::response::{Flash, Redirect};
+use rocket::response::{Flash, Redirect, Response};
 use rocket_i18n::I18n;
 use std::{
     borrow::Cow,
@@ -15,23 +15,31 @@ use crate::routes::{
 };
 use crate::template_utils::{IntoContext, Ructe};
 use crate::utils::requires_login;
-use plume_common::activity_pub::{broadcast, ActivityStream, ApRequest, LicensedArticle};
+use plume_common::activity_pub::{ActivityStream, ApRequest, LicensedArticle};
 use plume_common::utils::md_to_html;
 use plume_models::{
     blogs::*,
     comments::{Comment, CommentTree},
     db_conn::DbConn,
+    deliveries::Delivery,
+    emojis::Emoji,
+    epub::Book,
     inbox::inbox,
     instance::Instance,
     medias::Media,
     mentions::Mention,
     post_authors::*,
+    post_previews::PostPreview,
+    post_revisions::PostRevision,
+    post_slugs::PostSlug,
+    post_templates::PostTemplate,
     posts::*,
     safe_string::SafeString,
+    series::Series,
     tags::*,
     timeline::*,
     users::User,
-    Error, PlumeRocket, CONFIG,
+    Connection, Error, PlumeRocket, CONFIG,
 };
 
 #[get("/~/<blog>/<slug>?<responding_to>", rank = 4)]
@@ -41,10 +49,24 @@ pub fn details(
     responding_to: Option<i32>,
     conn: DbConn,
     rockets: PlumeRocket,
-) -> Result<Ructe, ErrorPage> {
+) -> Result<RespondOrRedirect, ErrorPage> {
     let user = rockets.user.clone();
-    let blog = Blog::find_by_fqn(&conn, &blog)?;
-    let post = Post::find_by_slug(&conn, &slug, blog.id)?;
+    let (blog, post) = match Blog::find_by_fqn(&conn, &blog)
+        .and_then(|b| Post::find_by_slug(&conn, &slug, b.id).map(|p| (b, p)))
+    {
+        Ok(found) => found,
+        Err(e) => {
+            return match PostSlug::find_moved(&conn, &blog, &slug) {
+                Ok(post) => Ok(Redirect::moved(uri!(
+                    details: blog = post.get_blog_fqn(&conn),
+                    slug = &post.slug,
+                    responding_to = _
+                ))
+                .into()),
+                Err(_) => Err(e.into()),
+            }
+        }
+    };
     if !(post.published
         || post
             .get_authors(&conn)?
@@ -54,7 +76,18 @@ pub fn details(
         return Ok(render!(errors::not_authorized(
             &(&conn, &rockets).to_context(),
             i18n!(rockets.intl.catalog, "This post isn't published yet.")
-        )));
+        ))
+        .into());
+    }
+    if !post.can_see(&conn, user.as_ref())? {
+        return Ok(render!(errors::not_authorized(
+            &(&conn, &rockets).to_context(),
+            i18n!(
+                rockets.intl.catalog,
+                "You are not allowed to read this article."
+            )
+        ))
+        .into());
     }
 
     let comments = CommentTree::from_post(&conn, &post, user.as_ref())?;
@@ -97,7 +130,7 @@ pub fn details(
             user.clone().and_then(|u| u.has_reshared(&conn, &post).ok()).unwrap_or(false),
             user.and_then(|u| u.is_following(&conn, post.get_authors(&conn).ok()?[0].id).ok()).unwrap_or(false),
             post.get_authors(&conn)?[0].clone()
-        )))
+        )).into())
 }
 
 #[get("/~/<blog>/<slug>", rank = 3)]
@@ -107,8 +140,13 @@ pub fn activity_details(
     _ap: ApRequest,
     conn: DbConn,
 ) -> Result<ActivityStream<LicensedArticle>, Option<String>> {
-    let blog = Blog::find_by_fqn(&conn, &blog).map_err(|_| None)?;
-    let post = Post::find_by_slug(&conn, &slug, blog.id).map_err(|_| None)?;
+    let post = Blog::find_by_fqn(&conn, &blog)
+        .and_then(|b| Post::find_by_slug(&conn, &slug, b.id))
+        .or_else(|_| PostSlug::find_moved(&conn, &blog, &slug))
+        .map_err(|_| None)?;
+    if !post.can_see(&conn, None).unwrap_or(false) {
+        return Err(None);
+    }
     if post.published {
         Ok(ActivityStream::new(
             post.to_activity(&conn)
@@ -119,6 +157,54 @@ pub fn activity_details(
     }
 }
 
+/// Finds a post that `user` is allowed to read, as `details` would show it.
+fn find_readable(
+    conn: &Connection,
+    blog: &str,
+    slug: &str,
+    user: Option<&User>,
+) -> Result<Post, ErrorPage> {
+    let blog = Blog::find_by_fqn(conn, blog)?;
+    let post = Post::find_by_slug(conn, slug, blog.id)?;
+    let is_author = match user {
+        Some(user) => post.get_authors(conn)?.iter().any(|a| a.id == user.id),
+        None => false,
+    };
+    if (post.published || is_author) && post.can_see(conn, user)? {
+        Ok(post)
+    } else {
+        Err(Error::Unauthorized.into())
+    }
+}
+
+#[get("/~/<blog>/<slug>/epub")]
+pub fn epub(
+    blog: String,
+    slug: String,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Response<'static>, ErrorPage> {
+    let post = find_readable(&conn, &blog, &slug, rockets.user.as_ref())?;
+    let name = post.slug.clone();
+    super::epub_download(&conn, &Book::for_post(&conn, post), &name)
+}
+
+#[get("/~/<blog>/<slug>/print")]
+pub fn print(
+    blog: String,
+    slug: String,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let post = find_readable(&conn, &blog, &slug, rockets.user.as_ref())?;
+    let authors = post.get_authors(&conn)?;
+    Ok(render!(posts::print(
+        &(&conn, &rockets).to_context(),
+        post.title.clone(),
+        vec![(post, authors)]
+    )))
+}
+
 #[get("/~/<blog>/new", rank = 2)]
 pub fn new_auth(blog: String, i18n: I18n) -> Flash<Redirect> {
     requires_login(
@@ -126,13 +212,14 @@ pub fn new_auth(blog: String, i18n: I18n) -> Flash<Redirect> {
             i18n.catalog,
             "To write a new post, you need to be logged in"
         ),
-        uri!(new: blog = blog),
+        uri!(new: blog = blog, template = _),
     )
 }
 
-#[get("/~/<blog>/new", rank = 1)]
+#[get("/~/<blog>/new?<template>", rank = 1)]
 pub fn new(
     blog: String,
+    template: Option<i32>,
     cl: ContentLen,
     conn: DbConn,
     rockets: PlumeRocket,
@@ -149,19 +236,36 @@ pub fn new(
     }
 
     let medias = Media::for_user(&conn, user.id)?;
+    let templates = PostTemplate::list_for_blog(&conn, b.id)?;
+    let form = match template.and_then(|id| templates.iter().find(|t| t.id == id)) {
+        Some(template) => NewPostForm {
+            title: template.title_for(Utc::now().naive_utc().date()),
+            content: template.source.clone(),
+            tags: template.tag_list().join(", "),
+            license: if template.license.is_empty() {
+                Instance::get_local()?.default_license
+            } else {
+                template.license.clone()
+            },
+            cover: template.cover_id,
+            ..NewPostForm::default()
+        },
+        None => NewPostForm {
+            license: Instance::get_local()?.default_license,
+            ..NewPostForm::default()
+        },
+    };
     Ok(render!(posts::new(
         &(&conn, &rockets).to_context(),
         i18n!(rockets.intl.catalog, "New post"),
         b,
         false,
-        &NewPostForm {
-            license: Instance::get_local()?.default_license,
-            ..NewPostForm::default()
-        },
+        &form,
         true,
         None,
         ValidationErrors::default(),
         medias,
+        templates,
         cl.0
     )))
 }
@@ -211,11 +315,26 @@ pub fn edit(
             license: post.license.clone(),
             draft: true,
             cover: post.cover_id,
+            publish_at: post
+                .publish_at
+                .map(|date| date.format("%Y-%m-%dT%H:%M").to_string())
+                .unwrap_or_default(),
+            series: Series::find_for_post(&conn, post.id)?
+                .map(|s| s.title)
+                .unwrap_or_default(),
+            visibility: post.visibility().as_str().to_owned(),
+            sensitive: post.sensitive,
+            content_warning: post.content_warning.clone().unwrap_or_default(),
+            canonical_url: post.canonical_url.clone().unwrap_or_default(),
+            language: post.language.clone().unwrap_or_default(),
+            translation_of: post.translation_of,
+            slug: Some(post.slug.clone()),
         },
         !post.published,
         Some(post),
         ValidationErrors::default(),
         medias,
+        vec![],
         cl.0
     )))
 }
@@ -235,10 +354,16 @@ pub fn update(
     let user = rockets.user.clone().unwrap();
     let intl = &rockets.intl.catalog;
 
+    // the slug of drafts follows their title, published posts can be given a new one
     let new_slug = if !post.published {
         Post::slug(&form.title).to_string()
     } else {
-        post.slug.clone()
+        form.slug
+            .as_deref()
+            .map(|s| Post::slug(s.trim()))
+            .filter(|s| !s.is_empty())
+            .unwrap_or(&post.slug)
+            .to_string()
     };
 
     let mut errors = match form.validate() {
@@ -246,12 +371,31 @@ pub fn update(
         Err(e) => e,
     };
 
-    if new_slug != slug && Post::find_by_slug(&conn, &new_slug, b.id).is_ok() {
+    let field = if post.published { "slug" } else { "title" };
+    if new_slug != slug
+        && (Post::find_by_slug(&conn, &new_slug, b.id).is_ok()
+            || PostSlug::find_post(&conn, b.id, &new_slug)
+                .map(|p| p.id != post.id)
+                .unwrap_or(false))
+    {
         errors.add(
-            "title",
+            field,
             ValidationError {
                 code: Cow::from("existing_slug"),
-                message: Some(Cow::from("A post with the same title already exists.")),
+                message: Some(Cow::from(if post.published {
+                    "Another article already uses this address."
+                } else {
+                    "A post with the same title already exists."
+                })),
+                params: HashMap::new(),
+            },
+        );
+    } else if new_slug != slug && is_reserved_slug(&new_slug) {
+        errors.add(
+            field,
+            ValidationError {
+                code: Cow::from("invalid_slug"),
+                message: Some(Cow::from("Invalid title")),
                 params: HashMap::new(),
             },
         );
@@ -284,15 +428,28 @@ pub fn update(
                         .iter()
                         .collect(),
                 )),
+                Some(Emoji::get_emoji_processor(&conn)),
             );
 
             // update publication date if when this article is no longer a draft
             let newly_published = if !post.published && !form.draft {
-                post.published = true;
-                post.creation_date = Utc::now().naive_utc();
-                post.ap_url = Post::ap_url(post.get_blog(&conn).unwrap(), &new_slug);
-                true
+                match form.scheduled_date() {
+                    Some(date) => {
+                        post.publish_at = Some(date);
+                        false
+                    }
+                    None => {
+                        post.published = true;
+                        post.publish_at = None;
+                        post.creation_date = Utc::now().naive_utc();
+                        post.ap_url = Post::ap_url(post.get_blog(&conn).unwrap(), &new_slug);
+                        true
+                    }
+                }
             } else {
+                if !post.published {
+                    post.publish_at = None;
+                }
                 false
             };
 
@@ -303,7 +460,30 @@ pub fn update(
             post.source = form.content.clone();
             post.license = form.license.clone();
             post.cover_id = form.cover;
+            let visibility_changed = post.visibility != i32::from(form.visibility());
+            post.visibility = form.visibility().into();
+            let (sensitive, content_warning) = form.content_warning();
+            post.sensitive = sensitive;
+            post.content_warning = content_warning;
+            post.canonical_url = form.canonical_url();
+            post.language = form.language();
             post.update(&conn).expect("post::update: update error");
+            if visibility_changed {
+                Timeline::remove_from_hidden_timelines(&conn, &post)
+                    .expect("post::update: timelines error");
+            }
+            PostRevision::snapshot(&conn, &post, Some(&user))
+                .expect("post::update: revision save error");
+            Series::set_for_post(&conn, &post, &form.series).expect("post::update: series error");
+            let original = form
+                .translation_of
+                .and_then(|id| Post::get(&conn, id).ok())
+                .filter(|original| {
+                    original.blog_id == post.blog_id
+                        && original.translation_of.unwrap_or(original.id) != post.id
+                });
+            post.set_translation_of(&conn, original.as_ref())
+                .expect("post::update: translation error");
 
             if post.published {
                 post.update_mentions(
@@ -342,20 +522,20 @@ pub fn update(
                     let act = post
                         .create_activity(&conn)
                         .expect("post::update: act error");
-                    let dest = User::one_by_instance(&conn).expect("post::update: dest error");
-                    rockets
-                        .worker
-                        .execute(move || broadcast(&user, act, dest, CONFIG.proxy().cloned()));
+                    let dest = post.get_receivers(&conn).expect("post::update: dest error");
+                    Delivery::broadcast(&conn, &user, act, dest)
+                        .expect("post::update: federation error");
 
                     Timeline::add_to_all_timelines(&conn, &post, Kind::Original).ok();
                 } else {
                     let act = post
                         .update_activity(&conn)
                         .expect("post::update: act error");
-                    let dest = User::one_by_instance(&conn).expect("posts::update: dest error");
-                    rockets
-                        .worker
-                        .execute(move || broadcast(&user, act, dest, CONFIG.proxy().cloned()));
+                    let dest = post
+                        .get_receivers(&conn)
+                        .expect("posts::update: dest error");
+                    Delivery::broadcast(&conn, &user, act, dest)
+                        .expect("posts::update: federation error");
                 }
             }
 
@@ -371,6 +551,8 @@ pub fn update(
         }
     } else {
         let medias = Media::for_user(&conn, user.id).expect("posts:update: medias error");
+        let templates =
+            PostTemplate::list_for_blog(&conn, b.id).expect("posts:update: templates error");
         render!(posts::new(
             &(&conn, &rockets).to_context(),
             i18n!(intl, "Edit {0}"; &form.title),
@@ -381,6 +563,7 @@ pub fn update(
             Some(post),
             errors,
             medias,
+            templates,
             cl.0
         ))
         .into()
@@ -397,19 +580,128 @@ pub struct NewPostForm {
     pub license: String,
     pub draft: bool,
     pub cover: Option<i32>,
+    #[validate(custom(function = "valid_publish_date", message = "Invalid date"))]
+    pub publish_at: String,
+    pub series: String,
+    #[validate(custom(function = "valid_visibility", message = "Invalid visibility"))]
+    pub visibility: String,
+    pub sensitive: bool,
+    pub content_warning: String,
+    #[validate(custom(function = "valid_canonical_url", message = "Invalid address"))]
+    pub canonical_url: String,
+    #[validate(custom(function = "valid_language", message = "Invalid language"))]
+    pub language: String,
+    pub translation_of: Option<i32>,
+    /// Only used when editing published posts
+    pub slug: Option<String>,
+}
+
+impl NewPostForm {
+    /// The date at which this post should be published, if it is in the future
+    /// and the post is not kept as a draft.
+    pub fn scheduled_date(&self) -> Option<NaiveDateTime> {
+        if self.draft {
+            return None;
+        }
+        parse_publish_date(&self.publish_at).filter(|date| *date > Utc::now().naive_utc())
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.visibility.parse().unwrap_or_default()
+    }
+
+    pub fn language(&self) -> Option<String> {
+        Some(self.language.trim().to_owned()).filter(|language| !language.is_empty())
+    }
+
+    pub fn canonical_url(&self) -> Option<String> {
+        Some(self.canonical_url.trim().to_owned()).filter(|url| !url.is_empty())
+    }
+
+    /// Whether the post is sensitive, and its content warning if there is one.
+    ///
+    /// Giving a content warning is enough to mark it as sensitive.
+    pub fn content_warning(&self) -> (bool, Option<String>) {
+        let warning = self.content_warning.trim();
+        if warning.is_empty() {
+            (self.sensitive, None)
+        } else {
+            (true, Some(warning.to_owned()))
+        }
+    }
+}
+
+/// The addresses of the other pages of a blog, that posts can't use.
+const RESERVED_SLUGS: &[&str] = &[
+    "new",
+    "edit",
+    "outbox",
+    "atom.xml",
+    "epub",
+    "print",
+    "export",
+    "featured",
+    "templates",
+];
+
+fn is_reserved_slug(slug: &str) -> bool {
+    RESERVED_SLUGS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(slug))
 }
 
 pub fn valid_slug(title: &str) -> Result<(), ValidationError> {
     let slug = Post::slug(title);
     if slug.is_empty() {
         Err(ValidationError::new("empty_slug"))
-    } else if slug == "new" {
+    } else if is_reserved_slug(slug) {
         Err(ValidationError::new("invalid_slug"))
     } else {
         Ok(())
     }
 }
 
+pub fn valid_publish_date(date: &str) -> Result<(), ValidationError> {
+    if date.is_empty() || parse_publish_date(date).is_some() {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_date"))
+    }
+}
+
+pub fn valid_visibility(visibility: &str) -> Result<(), ValidationError> {
+    if visibility.is_empty() || visibility.parse::<Visibility>().is_ok() {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_visibility"))
+    }
+}
+
+pub fn valid_canonical_url(url: &str) -> Result<(), ValidationError> {
+    let url = url.trim();
+    if url.is_empty() || is_valid_canonical_url(url) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_url"))
+    }
+}
+
+pub fn valid_language(language: &str) -> Result<(), ValidationError> {
+    let language = language.trim();
+    if language.is_empty() || is_valid_language(language) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_language"))
+    }
+}
+
+/// Parses the value of a `datetime-local` input (interpreted as UTC).
+fn parse_publish_date(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+}
+
 #[post("/~/<blog_name>/new", data = "<form>")]
 pub fn create(
     blog_name: String,
@@ -426,7 +718,9 @@ pub fn create(
         Ok(_) => ValidationErrors::new(),
         Err(e) => e,
     };
-    if Post::find_by_slug(&conn, slug, blog.id).is_ok() {
+    if Post::find_by_slug(&conn, slug, blog.id).is_ok()
+        || PostSlug::find_post(&conn, blog.id, slug).is_ok()
+    {
         errors.add(
             "title",
             ValidationError {
@@ -468,22 +762,32 @@ pub fn create(
                     .iter()
                     .collect(),
             )),
+            Some(Emoji::get_emoji_processor(&conn)),
         );
 
-        let post = Post::insert(
+        let publish_at = form.scheduled_date();
+        let (sensitive, content_warning) = form.content_warning();
+        let mut post = Post::insert(
             &conn,
             NewPost {
                 blog_id: blog.id,
                 slug: slug.to_string(),
                 title: form.title.to_string(),
                 content: SafeString::new(&content),
-                published: !form.draft,
+                published: !form.draft && publish_at.is_none(),
                 license: form.license.clone(),
                 ap_url: "".to_string(),
                 creation_date: None,
                 subtitle: form.subtitle.clone(),
                 source: form.content.clone(),
                 cover_id: form.cover,
+                publish_at,
+                visibility: form.visibility().into(),
+                sensitive,
+                content_warning,
+                canonical_url: form.canonical_url(),
+                language: form.language(),
+                translation_of: None,
             },
         )
         .expect("post::create: post save error");
@@ -496,6 +800,15 @@ pub fn create(
             },
         )
         .expect("post::create: author save error");
+        PostRevision::snapshot(&conn, &post, Some(&user))
+            .expect("post::create: revision save error");
+        Series::set_for_post(&conn, &post, &form.series).expect("post::create: series error");
+        let original = form
+            .translation_of
+            .and_then(|id| Post::get(&conn, id).ok())
+            .filter(|original| original.blog_id == post.blog_id);
+        post.set_translation_of(&conn, original.as_ref())
+            .expect("post::create: translation error");
 
         let tags = form
             .tags
@@ -541,9 +854,10 @@ pub fn create(
             let act = post
                 .create_activity(&conn)
                 .expect("posts::create: activity error");
-            let dest = User::one_by_instance(&conn).expect("posts::create: dest error");
-            let worker = &rockets.worker;
-            worker.execute(move || broadcast(&user, act, dest, CONFIG.proxy().cloned()));
+            let dest = post
+                .get_receivers(&conn)
+                .expect("posts::create: dest error");
+            Delivery::broadcast(&conn, &user, act, dest)?;
 
             Timeline::add_to_all_timelines(&conn, &post, Kind::Original)?;
         }
@@ -559,6 +873,7 @@ pub fn create(
         .into())
     } else {
         let medias = Media::for_user(&conn, user.id).expect("posts::create: medias error");
+        let templates = PostTemplate::list_for_blog(&conn, blog.id)?;
         Ok(render!(posts::new(
             &(&conn, &rockets).to_context(),
             i18n!(rockets.intl.catalog, "New article"),
@@ -569,6 +884,7 @@ pub fn create(
             None,
             errors,
             medias,
+            templates,
             cl.0
         ))
         .into())
@@ -603,17 +919,14 @@ pub fn delete(
             ));
         }
 
-        let dest = User::one_by_instance(&conn)?;
+        let dest = post.get_receivers(&conn)?;
         let delete_activity = post.build_delete(&conn)?;
         inbox(
             &conn,
             serde_json::to_value(&delete_activity).map_err(Error::from)?,
         )?;
 
-        let user_c = user.clone();
-        rockets
-            .worker
-            .execute(move || broadcast(&user_c, delete_activity, dest, CONFIG.proxy().cloned()));
+        Delivery::broadcast(&conn, &user, delete_activity, dest)?;
         rockets
             .worker
             .execute_after(Duration::from_secs(10 * 60), move || {
@@ -632,6 +945,331 @@ pub fn delete(
     }
 }
 
+#[get("/~/<blog>/<slug>/history?<from>&<to>")]
+pub fn history(
+    blog: String,
+    slug: String,
+    from: Option<i32>,
+    to: Option<i32>,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    let post = Post::find_by_slug(&conn, &slug, blog.id)?;
+    if !post.is_author(&conn, user.id)? {
+        return Ok(render!(errors::not_authorized(
+            &(&conn, &rockets).to_context(),
+            i18n!(
+                rockets.intl.catalog,
+                "You are not allowed to see the history of this article."
+            )
+        )));
+    }
+
+    let revisions = PostRevision::list_for_post(&conn, post.id)?;
+    // by default, compare the last revision with the one before it
+    let newer = to
+        .and_then(|id| revisions.iter().find(|r| r.id == id))
+        .or_else(|| revisions.first())
+        .cloned();
+    let older = from
+        .and_then(|id| revisions.iter().find(|r| r.id == id))
+        .or_else(|| revisions.get(1))
+        .or_else(|| newer.as_ref())
+        .cloned();
+    let diff = match (&older, &newer) {
+        (Some(older), Some(newer)) => older.diff(newer),
+        _ => vec![],
+    };
+
+    Ok(render!(posts::history(
+        &(&conn, &rockets).to_context(),
+        post,
+        blog,
+        revisions,
+        older.as_ref().map(|r| r.id),
+        newer.as_ref().map(|r| r.id),
+        diff
+    )))
+}
+
+#[post("/~/<blog_name>/<slug>/history/<id>/restore")]
+pub fn restore_revision(
+    blog_name: String,
+    slug: String,
+    id: i32,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let intl = &rockets.intl.catalog;
+    let blog = Blog::find_by_fqn(&conn, &blog_name)?;
+    let mut post = Post::find_by_slug(&conn, &slug, blog.id)?;
+    if !post.is_author(&conn, user.id)? {
+        return Ok(Flash::error(
+            Redirect::to(uri!(
+                details: blog = blog_name,
+                slug = slug,
+                responding_to = _
+            )),
+            i18n!(intl, "You are not allowed to restore this article."),
+        ));
+    }
+
+    let revision = PostRevision::find_for_post(&conn, post.id, id)?;
+    revision.restore_to(&mut post);
+    let (content, mentions, hashtags) = md_to_html(
+        &post.source,
+        Some(&Instance::get_local()?.public_domain),
+        false,
+        Some(Media::get_media_processor(
+            &conn,
+            blog.list_authors(&conn)?.iter().collect(),
+        )),
+        Some(Emoji::get_emoji_processor(&conn)),
+    );
+    post.content = SafeString::new(&content);
+    let post = post.update(&conn)?;
+    PostRevision::snapshot(&conn, &post, Some(&user))?;
+
+    if post.published {
+        post.update_mentions(
+            &conn,
+            mentions
+                .into_iter()
+                .filter_map(|m| Mention::build_activity(&conn, &m).ok())
+                .collect(),
+        )?;
+    }
+    let hashtags = hashtags
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter_map(|t| Tag::build_activity(t).ok())
+        .collect::<Vec<_>>();
+    post.update_hashtags(&conn, hashtags)?;
+
+    if post.published {
+        let act = post.update_activity(&conn)?;
+        let dest = post.get_receivers(&conn)?;
+        Delivery::broadcast(&conn, &user, act, dest)?;
+    }
+
+    Ok(Flash::success(
+        Redirect::to(uri!(
+            details: blog = blog_name,
+            slug = slug,
+            responding_to = _
+        )),
+        i18n!(intl, "The article has been restored to this revision."),
+    ))
+}
+
+#[get("/preview/<token>")]
+pub fn preview(
+    token: String,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<RespondOrRedirect, ErrorPage> {
+    let preview = PostPreview::find_valid(&conn, &token)?;
+    let post = preview.get_post(&conn)?;
+    let blog = post.get_blog(&conn)?;
+    if post.published {
+        // the links are not needed anymore once the article is out
+        PostPreview::delete_for_post(&conn, post.id)?;
+        return Ok(Redirect::to(uri!(
+            details: blog = &blog.fqn,
+            slug = &post.slug,
+            responding_to = _
+        ))
+        .into());
+    }
+
+    let tags = Tag::for_post(&conn, post.id)?;
+    let author = post.get_authors(&conn)?.swap_remove(0);
+    Ok(render!(posts::preview(
+        &(&conn, &rockets).to_context(),
+        post,
+        blog,
+        tags,
+        author
+    ))
+    .into())
+}
+
+#[get("/~/<blog>/<slug>/previews")]
+pub fn previews(
+    blog: String,
+    slug: String,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    let post = Post::find_by_slug(&conn, &slug, blog.id)?;
+    if !post.is_author(&conn, user.id)? {
+        return Ok(render!(errors::not_authorized(
+            &(&conn, &rockets).to_context(),
+            i18n!(
+                rockets.intl.catalog,
+                "You are not allowed to share a preview of this article."
+            )
+        )));
+    }
+
+    let previews = PostPreview::list_for_post(&conn, post.id)?
+        .into_iter()
+        .map(|p| {
+            let url = format!(
+                "https://{}{}",
+                CONFIG.base_url,
+                uri!(preview: token = &p.token)
+            );
+            (p, url)
+        })
+        .collect();
+    Ok(render!(posts::previews(
+        &(&conn, &rockets).to_context(),
+        post,
+        blog,
+        previews
+    )))
+}
+
+#[derive(FromForm)]
+pub struct NewPreviewForm {
+    pub days: i64,
+}
+
+#[post("/~/<blog>/<slug>/previews", data = "<form>")]
+pub fn create_preview(
+    blog: String,
+    slug: String,
+    form: LenientForm<NewPreviewForm>,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let intl = &rockets.intl.catalog;
+    let b = Blog::find_by_fqn(&conn, &blog)?;
+    let post = Post::find_by_slug(&conn, &slug, b.id)?;
+    if !post.is_author(&conn, user.id)? {
+        return Ok(Flash::error(
+            Redirect::to(uri!(details: blog = blog, slug = slug, responding_to = _)),
+            i18n!(
+                intl,
+                "You are not allowed to share a preview of this article."
+            ),
+        ));
+    }
+    if post.published {
+        return Ok(Flash::error(
+            Redirect::to(uri!(details: blog = blog, slug = slug, responding_to = _)),
+            i18n!(intl, "This article is already published."),
+        ));
+    }
+
+    let days = form.days.max(1).min(30);
+    PostPreview::create(&conn, &post, chrono::Duration::days(days))?;
+    Ok(Flash::success(
+        Redirect::to(uri!(previews: blog = blog, slug = slug)),
+        i18n!(intl, "A new preview link has been created."),
+    ))
+}
+
+#[post("/~/<blog>/<slug>/previews/<id>/revoke")]
+pub fn revoke_preview(
+    blog: String,
+    slug: String,
+    id: i32,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let intl = &rockets.intl.catalog;
+    let b = Blog::find_by_fqn(&conn, &blog)?;
+    let post = Post::find_by_slug(&conn, &slug, b.id)?;
+    let preview = PostPreview::get(&conn, id)?;
+    if !post.is_author(&conn, user.id)? || preview.post_id != post.id {
+        return Ok(Flash::error(
+            Redirect::to(uri!(details: blog = blog, slug = slug, responding_to = _)),
+            i18n!(
+                intl,
+                "You are not allowed to share a preview of this article."
+            ),
+        ));
+    }
+
+    preview.delete(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(previews: blog = blog, slug = slug)),
+        i18n!(intl, "The preview link has been revoked."),
+    ))
+}
+
+#[post("/~/<blog>/<slug>/pin")]
+pub fn pin(
+    blog: String,
+    slug: String,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let intl = &rockets.intl.catalog;
+    let b = Blog::find_by_fqn(&conn, &blog)?;
+    let mut post = Post::find_by_slug(&conn, &slug, b.id)?;
+    let back = uri!(details: blog = blog, slug = slug, responding_to = _);
+    if !user.is_author_in(&conn, &b)? {
+        return Ok(Flash::error(
+            Redirect::to(back),
+            i18n!(intl, "You are not allowed to pin articles of this blog."),
+        ));
+    }
+
+    match post.pin(&conn) {
+        Ok(()) => Ok(Flash::success(
+            Redirect::to(back),
+            i18n!(intl, "This article is now pinned."),
+        )),
+        Err(Error::InvalidValue) => Ok(Flash::error(
+            Redirect::to(back),
+            i18n!(
+                intl,
+                "Only public published articles can be pinned, and a blog can pin up to {0} of them.";
+                MAX_PINNED_POSTS
+            ),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[post("/~/<blog>/<slug>/unpin")]
+pub fn unpin(
+    blog: String,
+    slug: String,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let intl = &rockets.intl.catalog;
+    let b = Blog::find_by_fqn(&conn, &blog)?;
+    let mut post = Post::find_by_slug(&conn, &slug, b.id)?;
+    let back = uri!(details: blog = blog, slug = slug, responding_to = _);
+    if !user.is_author_in(&conn, &b)? {
+        return Ok(Flash::error(
+            Redirect::to(back),
+            i18n!(intl, "You are not allowed to pin articles of this blog."),
+        ));
+    }
+
+    post.unpin(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(back),
+        i18n!(intl, "This article is not pinned anymore."),
+    ))
+}
+
 #[get("/~/<blog_name>/<slug>/remote_interact")]
 pub fn remote_interact(
     conn: DbConn,
@@ -0,0 +1,177 @@
+use rocket::request::LenientForm;
+use rocket::response::{Flash, Redirect};
+use rocket_i18n::I18n;
+use validator::{Validate, ValidationErrors};
+
+use crate::routes::{errors::ErrorPage, RespondOrRedirect};
+use crate::template_utils::{IntoContext, Ructe};
+use crate::utils::requires_login;
+use plume_common::activity_pub::inbox::AsActor;
+use plume_models::{
+    comments::Comment,
+    db_conn::DbConn,
+    posts::Post,
+    reports::{NewReport, Report},
+    users::User,
+    Connection, Error, PlumeRocket,
+};
+
+#[derive(Default, FromForm, Validate)]
+pub struct ReportForm {
+    pub post: Option<i32>,
+    pub comment: Option<i32>,
+    pub user: Option<i32>,
+    #[validate(length(min = 1, message = "Please tell the moderators what is wrong"))]
+    pub reason: String,
+    pub forward: bool,
+}
+
+/// What is reported: a comment, a post or a user, and the user who wrote it.
+pub struct ReportTarget {
+    pub user: User,
+    pub post: Option<Post>,
+    pub comment: Option<Comment>,
+}
+
+impl ReportTarget {
+    /// Finds what `reporter` wants to report, among what they can see.
+    fn find(
+        conn: &Connection,
+        post: Option<i32>,
+        comment: Option<i32>,
+        user: Option<i32>,
+        reporter: &User,
+    ) -> Result<Self, ErrorPage> {
+        if let Some(comment) = comment {
+            let comment = Comment::get(conn, comment)?;
+            if !comment.can_see(conn, Some(reporter)) {
+                return Err(Error::NotFound.into());
+            }
+            Ok(ReportTarget {
+                user: comment.get_author(conn)?,
+                post: None,
+                comment: Some(comment),
+            })
+        } else if let Some(post) = post {
+            let post = Post::get(conn, post)?;
+            if !post.published || !post.can_see(conn, Some(reporter))? {
+                return Err(Error::NotFound.into());
+            }
+            let user = post
+                .get_authors(conn)?
+                .into_iter()
+                .next()
+                .ok_or(Error::NotFound)?;
+            Ok(ReportTarget {
+                user,
+                post: Some(post),
+                comment: None,
+            })
+        } else if let Some(user) = user {
+            Ok(ReportTarget {
+                user: User::get(conn, user)?,
+                post: None,
+                comment: None,
+            })
+        } else {
+            Err(Error::NotFound.into())
+        }
+    }
+
+    fn url(&self, conn: &Connection) -> Result<String, ErrorPage> {
+        Ok(match (&self.post, &self.comment) {
+            (Some(post), _) => post.url(conn)?,
+            (None, Some(comment)) => {
+                format!(
+                    "{}#comment-{}",
+                    comment.get_post(conn)?.url(conn)?,
+                    comment.id
+                )
+            }
+            (None, None) => uri!(super::user::details: name = &self.user.fqn).to_string(),
+        })
+    }
+}
+
+#[get("/report?<post>&<comment>&<user>")]
+pub fn new(
+    post: Option<i32>,
+    comment: Option<i32>,
+    user: Option<i32>,
+    reporter: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let target = ReportTarget::find(&conn, post, comment, user, &reporter)?;
+    let form = ReportForm {
+        post,
+        comment,
+        user,
+        ..ReportForm::default()
+    };
+    Ok(render!(reports::new(
+        &(&conn, &rockets).to_context(),
+        target,
+        &form,
+        ValidationErrors::default()
+    )))
+}
+
+#[get("/report?<post>&<comment>&<user>", rank = 2)]
+pub fn new_auth(
+    post: Option<i32>,
+    comment: Option<i32>,
+    user: Option<i32>,
+    i18n: I18n,
+) -> Flash<Redirect> {
+    requires_login(
+        &i18n!(
+            i18n.catalog,
+            "To report something, you need to be logged in"
+        ),
+        uri!(new: post = post, comment = comment, user = user),
+    )
+}
+
+#[post("/report", data = "<form>")]
+pub fn create(
+    form: LenientForm<ReportForm>,
+    reporter: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<RespondOrRedirect, ErrorPage> {
+    let target = ReportTarget::find(&conn, form.post, form.comment, form.user, &reporter)?;
+    if let Err(errors) = form.validate() {
+        return Ok(render!(reports::new(
+            &(&conn, &rockets).to_context(),
+            target,
+            &*form,
+            errors
+        ))
+        .into());
+    }
+
+    let mut report = Report::insert(
+        &conn,
+        NewReport {
+            reporter_id: Some(reporter.id),
+            user_id: target.user.id,
+            post_id: target.post.as_ref().map(|p| p.id),
+            comment_id: target.comment.as_ref().map(|c| c.id),
+            reason: form.reason.trim().to_owned(),
+            ap_url: String::new(),
+        },
+    )?;
+    if form.forward && !target.user.is_local() {
+        report.forward(&conn)?;
+    }
+
+    Ok(Flash::success(
+        Redirect::to(target.url(&conn)?),
+        i18n!(
+            rockets.intl.catalog,
+            "Thank you, the moderators will look at your report."
+        ),
+    )
+    .into())
+}
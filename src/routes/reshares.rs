@@ -3,20 +3,13 @@ use rocket_i18n::I18n;
 
 use crate::routes::errors::ErrorPage;
 use crate::utils::requires_login;
-use plume_common::activity_pub::broadcast;
 use plume_models::{
-    blogs::Blog, db_conn::DbConn, inbox::inbox, posts::Post, reshares::*, timeline::*, users::User,
-    Error, PlumeRocket, CONFIG,
+    blogs::Blog, db_conn::DbConn, deliveries::Delivery, inbox::inbox, posts::Post, reshares::*,
+    timeline::*, users::User, Error,
 };
 
 #[post("/~/<blog>/<slug>/reshare")]
-pub fn create(
-    blog: String,
-    slug: String,
-    user: User,
-    conn: DbConn,
-    rockets: PlumeRocket,
-) -> Result<Redirect, ErrorPage> {
+pub fn create(blog: String, slug: String, user: User, conn: DbConn) -> Result<Redirect, ErrorPage> {
     let b = Blog::find_by_fqn(&conn, &blog)?;
     let post = Post::find_by_slug(&conn, &slug, b.id)?;
 
@@ -28,9 +21,7 @@ pub fn create(
 
         let dest = User::one_by_instance(&conn)?;
         let act = reshare.to_activity(&conn)?;
-        rockets
-            .worker
-            .execute(move || broadcast(&user, act, dest, CONFIG.proxy().cloned()));
+        Delivery::broadcast(&conn, &user, act, dest)?;
     } else {
         let reshare = Reshare::find_by_user_on_post(&conn, user.id, post.id)?;
         let delete_act = reshare.build_undo(&conn)?;
@@ -40,9 +31,7 @@ pub fn create(
         )?;
 
         let dest = User::one_by_instance(&conn)?;
-        rockets
-            .worker
-            .execute(move || broadcast(&user, delete_act, dest, CONFIG.proxy().cloned()));
+        Delivery::broadcast(&conn, &user, delete_act, dest)?;
     }
 
     Ok(Redirect::to(uri!(
@@ -0,0 +1,189 @@
+use activitystreams::collection::OrderedCollection;
+use rocket::{
+    http::ContentType,
+    request::LenientForm,
+    response::{content::Content, Flash, Redirect, Response},
+};
+
+use crate::routes::{blogs::find_blog, errors::ErrorPage, RespondOrRedirect};
+use crate::template_utils::{IntoContext, Ructe};
+use plume_common::activity_pub::{ActivityStream, ApRequest};
+use plume_models::{
+    blog_slugs::BlogSlug, blogs::Blog, db_conn::DbConn, epub::Book, posts::Post, series::Series,
+    users::User, Error, PlumeRocket,
+};
+
+#[get("/~/<blog>/series/<slug>", rank = 6)]
+pub fn details(
+    blog: String,
+    slug: String,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<RespondOrRedirect, ErrorPage> {
+    let blog = match Blog::find_by_fqn(&conn, &blog) {
+        Ok(blog) => blog,
+        Err(e) => {
+            return match BlogSlug::find_blog(&conn, &blog) {
+                Ok(blog) => {
+                    Ok(Redirect::moved(uri!(details: blog = &blog.fqn, slug = &slug)).into())
+                }
+                Err(_) => Err(e.into()),
+            }
+        }
+    };
+    let series = Series::find_by_slug(&conn, &slug, blog.id)?;
+    let is_author = rockets
+        .user
+        .clone()
+        .and_then(|u| u.is_author_in(&conn, &blog).ok())
+        .unwrap_or(false);
+    let posts = if is_author {
+        series.get_posts(&conn, true)?
+    } else {
+        series.readable_posts(&conn, rockets.user.as_ref())?
+    };
+
+    Ok(render!(series::details(
+        &(&conn, &rockets).to_context(),
+        blog,
+        series,
+        posts,
+        is_author
+    ))
+    .into())
+}
+
+#[get("/~/<blog>/series/<slug>", rank = 5)]
+pub fn activity_details(
+    blog: String,
+    slug: String,
+    _ap: ApRequest,
+    conn: DbConn,
+) -> Option<ActivityStream<OrderedCollection>> {
+    let blog = find_blog(&conn, &blog).ok()?;
+    let series = Series::find_by_slug(&conn, &slug, blog.id).ok()?;
+    series.outbox(&conn).ok()
+}
+
+#[get("/~/<blog>/series/<slug>/atom.xml", rank = 2)]
+pub fn atom_feed(blog: String, slug: String, conn: DbConn) -> Option<Content<String>> {
+    let blog = find_blog(&conn, &blog).ok()?;
+    let series = Series::find_by_slug(&conn, &slug, blog.id).ok()?;
+    // the latest parts come first in the feed
+    let entries = series
+        .readable_posts(&conn, None)
+        .ok()?
+        .into_iter()
+        .rev()
+        .collect();
+    let uri = format!("{}/atom.xml", series.ap_url);
+    let feed = super::build_atom_feed(entries, &uri, &series.title, &series.creation_date, &conn);
+    Some(Content(
+        ContentType::new("application", "atom+xml"),
+        feed.to_string(),
+    ))
+}
+
+#[get("/~/<blog>/series/<slug>/epub", rank = 2)]
+pub fn epub(
+    blog: String,
+    slug: String,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Response<'static>, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    let series = Series::find_by_slug(&conn, &slug, blog.id)?;
+    let posts = series.readable_posts(&conn, rockets.user.as_ref())?;
+    super::epub_download(
+        &conn,
+        &Book::for_series(&conn, &series, posts)?,
+        &series.slug,
+    )
+}
+
+#[get("/~/<blog>/series/<slug>/print", rank = 2)]
+pub fn print(
+    blog: String,
+    slug: String,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Ructe, ErrorPage> {
+    let blog = Blog::find_by_fqn(&conn, &blog)?;
+    let series = Series::find_by_slug(&conn, &slug, blog.id)?;
+    let posts = series
+        .readable_posts(&conn, rockets.user.as_ref())?
+        .into_iter()
+        .map(|post| {
+            let authors = post.get_authors(&conn)?;
+            Ok((post, authors))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(render!(posts::print(
+        &(&conn, &rockets).to_context(),
+        series.title,
+        posts
+    )))
+}
+
+#[derive(Default, FromForm)]
+pub struct EditSeriesForm {
+    pub description: String,
+}
+
+#[post("/~/<blog>/series/<slug>/edit", data = "<form>")]
+pub fn update(
+    blog: String,
+    slug: String,
+    form: LenientForm<EditSeriesForm>,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let b = Blog::find_by_fqn(&conn, &blog)?;
+    let mut series = Series::find_by_slug(&conn, &slug, b.id)?;
+    if !user.is_author_in(&conn, &b)? {
+        return Ok(Flash::error(
+            Redirect::to(uri!(details: blog = blog, slug = slug)),
+            i18n!(rockets.intl.catalog, "You are not an author of this blog."),
+        ));
+    }
+
+    series.description = form.description.trim().to_owned();
+    series.update(&conn)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(details: blog = blog, slug = slug)),
+        i18n!(rockets.intl.catalog, "The series has been updated."),
+    ))
+}
+
+#[derive(Default, FromForm)]
+pub struct MovePostForm {
+    pub post: i32,
+    pub up: bool,
+}
+
+#[post("/~/<blog>/series/<slug>/move", data = "<form>")]
+pub fn move_post(
+    blog: String,
+    slug: String,
+    form: LenientForm<MovePostForm>,
+    user: User,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    let b = Blog::find_by_fqn(&conn, &blog)?;
+    let series = Series::find_by_slug(&conn, &slug, b.id)?;
+    if !user.is_author_in(&conn, &b)? {
+        return Ok(Flash::error(
+            Redirect::to(uri!(details: blog = blog, slug = slug)),
+            i18n!(rockets.intl.catalog, "You are not an author of this blog."),
+        ));
+    }
+
+    let post = Post::get(&conn, form.post)?;
+    series.move_post(&conn, &post, form.up)?;
+    Ok(Flash::success(
+        Redirect::to(uri!(details: blog = blog, slug = slug)),
+        i18n!(rockets.intl.catalog, "The series has been updated."),
+    ))
+}
@@ -16,8 +16,8 @@ pub fn details(
     let page = page.unwrap_or_default();
     let all_tl = Timeline::list_all_for_user(&conn, rockets.user.clone().map(|u| u.id))?;
     let tl = Timeline::get(&conn, id)?;
-    let posts = tl.get_page(&conn, page.limits())?;
-    let total_posts = tl.count_posts(&conn)?;
+    let posts = tl.get_page_for(&conn, rockets.user.as_ref(), page.limits())?;
+    let total_posts = tl.count_posts_for(&conn, rockets.user.as_ref())?;
     Ok(render!(timelines::details(
         &(&conn, &rockets).to_context(),
         tl,
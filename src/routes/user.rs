@@ -24,6 +24,8 @@ use plume_common::utils::md_to_html;
 use plume_models::{
     blogs::Blog,
     db_conn::DbConn,
+    deliveries::Delivery,
+    emojis::Emoji,
     follows,
     headers::Headers,
     inbox::inbox as local_inbox,
@@ -33,6 +35,7 @@ use plume_models::{
     reshares::Reshare,
     safe_string::SafeString,
     signups::{self, Strategy as SignupStrategy},
+    user_blocks::UserBlock,
     users::*,
     Error, PlumeRocket, CONFIG,
 };
@@ -48,7 +51,11 @@ pub fn me(user: Option<User>) -> RespondOrRedirect {
 #[get("/@/<name>", rank = 2)]
 pub fn details(name: String, rockets: PlumeRocket, conn: DbConn) -> Result<Ructe, ErrorPage> {
     let user = User::find_by_fqn(&conn, &name)?;
-    let recents = Post::get_recents_for_author(&conn, &user, 6)?;
+    let pinned = Post::list_pinned_for_author(&conn, &user)?;
+    let recents = Post::get_recents_for_author(&conn, &user, 6)?
+        .into_iter()
+        .filter(|p| p.pinned_at.is_none())
+        .collect();
     let reshares = Reshare::get_recents_for_author(&conn, &user, 6)?;
 
     if !user.get_instance(&conn)?.local {
@@ -66,6 +73,7 @@ pub fn details(name: String, rockets: PlumeRocket, conn: DbConn) -> Result<Ructe
             .unwrap_or(false),
         user.instance_id != Instance::get_local()?.id,
         user.get_instance(&conn)?.public_domain,
+        pinned,
         recents,
         reshares
             .into_iter()
@@ -103,6 +111,14 @@ pub fn follow(
     rockets: PlumeRocket,
 ) -> Result<Flash<Redirect>, ErrorPage> {
     let target = User::find_by_fqn(&conn, &name)?;
+    if UserBlock::is_blocked(&conn, &user, &target)?
+        || UserBlock::is_blocked(&conn, &target, &user)?
+    {
+        return Ok(Flash::error(
+            Redirect::to(uri!(details: name = name)),
+            i18n!(rockets.intl.catalog, "You can't subscribe to {}."; target.name()),
+        ));
+    }
     let message = if let Ok(follow) = follows::Follow::find(&conn, user.id, target.id) {
         let delete_act = follow.build_undo(&conn)?;
         local_inbox(
@@ -111,9 +127,7 @@ pub fn follow(
         )?;
 
         let msg = i18n!(rockets.intl.catalog, "You are no longer following {}."; target.name());
-        rockets
-            .worker
-            .execute(move || broadcast(&user, delete_act, vec![target], CONFIG.proxy().cloned()));
+        Delivery::broadcast(&conn, &user, delete_act, vec![target])?;
         msg
     } else {
         let f = follows::Follow::insert(
@@ -128,9 +142,7 @@ pub fn follow(
 
         let act = f.to_activity(&conn)?;
         let msg = i18n!(rockets.intl.catalog, "You are now following {}."; target.name());
-        rockets
-            .worker
-            .execute(move || broadcast(&user, act, vec![target], CONFIG.proxy().cloned()));
+        Delivery::broadcast(&conn, &user, act, vec![target])?;
         msg
     };
     Ok(Flash::success(
@@ -358,6 +370,7 @@ pub fn update(
             None,
             false,
             Some(Media::get_media_processor(&conn, vec![&user])),
+            Some(Emoji::get_emoji_processor(&conn)),
         )
         .0,
     );
@@ -374,6 +387,84 @@ pub fn update(
     ))
 }
 
+#[derive(FromForm)]
+pub struct AliasesForm {
+    pub aliases: String,
+}
+
+#[post("/@/<name>/aliases", data = "<form>")]
+pub fn update_aliases(
+    name: String,
+    mut user: User,
+    form: LenientForm<AliasesForm>,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    if user.username != name {
+        return Err(Error::Unauthorized.into());
+    }
+
+    Ok(match user.set_aliases(&conn, &form.aliases) {
+        Ok(()) => Flash::success(
+            Redirect::to(uri!(edit: name = name)),
+            i18n!(
+                rockets.intl.catalog,
+                "Your other accounts have been updated."
+            ),
+        ),
+        Err(_) => Flash::error(
+            Redirect::to(uri!(edit: name = name)),
+            i18n!(
+                rockets.intl.catalog,
+                "One of these accounts couldn't be found."
+            ),
+        ),
+    })
+}
+
+#[derive(FromForm)]
+pub struct MoveForm {
+    pub target: String,
+}
+
+#[post("/@/<name>/move", data = "<form>")]
+pub fn move_account(
+    name: String,
+    mut user: User,
+    form: LenientForm<MoveForm>,
+    conn: DbConn,
+    rockets: PlumeRocket,
+) -> Result<Flash<Redirect>, ErrorPage> {
+    if user.username != name {
+        return Err(Error::Unauthorized.into());
+    }
+
+    let target = match User::find_by_fqn(&conn, form.target.trim().trim_start_matches('@')) {
+        Ok(target) => target,
+        Err(_) => {
+            return Ok(Flash::error(
+                Redirect::to(uri!(edit: name = name)),
+                i18n!(rockets.intl.catalog, "This account couldn't be found."),
+            ))
+        }
+    };
+    match user.move_to(&conn, &target) {
+        Ok(()) => Ok(Flash::success(
+            Redirect::to(uri!(details: name = name)),
+            i18n!(rockets.intl.catalog, "Your account has moved to {}."; target.name()),
+        )),
+        Err(Error::Unauthorized) => Ok(Flash::error(
+            Redirect::to(uri!(edit: name = name)),
+            i18n!(rockets.intl.catalog, "{} must list this account as one of its other accounts first."; target.name()),
+        )),
+        Err(Error::InvalidValue) => Ok(Flash::error(
+            Redirect::to(uri!(edit: name = name)),
+            i18n!(rockets.intl.catalog, "You can't move to this account."),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
 #[post("/@/<name>/delete")]
 pub fn delete(
     name: String,
@@ -543,6 +634,13 @@ pub fn outbox_page(
     let user = User::find_by_fqn(&conn, &name).ok()?;
     user.outbox_page(&conn, page.limits()).ok()
 }
+
+#[get("/@/<name>/featured")]
+pub fn featured(name: String, conn: DbConn) -> Option<ActivityStream<OrderedCollection>> {
+    let user = User::find_by_fqn(&conn, &name).ok()?;
+    user.featured(&conn).ok()
+}
+
 #[post("/@/<name>/inbox", data = "<data>")]
 pub fn inbox(
     name: String,